@@ -0,0 +1,223 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    fields,
+    incident::Incident,
+    stats::{Aggregate, WindowedStats},
+};
+
+/// A condition on the stream, configured as `[[alerts]]`.
+///
+/// Lines matching `pattern` are counted over the last `window` seconds,
+/// or, with `field`, the mean of that numeric JSON/logfmt field is taken.
+/// The alert fires while the value is `above` the threshold,
+/// or while it grew by more than `increase` percent compared with the window before.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertConfig {
+    pub name: String,
+    pub pattern: String,
+    pub field: Option<String>,
+    #[serde(default = "default_window")]
+    pub window: u64,
+    pub above: Option<f64>,
+    pub increase: Option<f64>,
+}
+
+fn default_window() -> u64 {
+    60
+}
+
+pub struct Alert {
+    pub name: String,
+    pattern: Regex,
+    field: Option<String>,
+    above: Option<f64>,
+    increase: Option<f64>,
+    stats: WindowedStats,
+    /// Why the alert is firing, if it is.
+    pub firing: Option<String>,
+    config: AlertConfig,
+}
+
+impl Alert {
+    /// Create the alert, counting seconds from `origin`.
+    pub fn try_new(config: &AlertConfig, origin: Instant) -> anyhow::Result<Self> {
+        Ok(Alert {
+            name: config.name.clone(),
+            pattern: Regex::new(&config.pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid pattern in alert {}: {e}", config.name))?,
+            field: config.field.clone(),
+            above: config.above,
+            increase: config.increase,
+            stats: WindowedStats::new(Duration::from_secs(config.window), origin),
+            firing: None,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the alert was created from.
+    pub fn config(&self) -> &AlertConfig {
+        &self.config
+    }
+
+    /// The value the conditions are checked against, for a window.
+    fn value(&self, aggregate: Aggregate) -> Option<f64> {
+        match self.field {
+            Some(_) => aggregate.mean(),
+            None => Some(aggregate.count as f64),
+        }
+    }
+
+    fn evaluate(&mut self, now: Instant) -> Option<String> {
+        let (current, previous) = self.stats.windows(now);
+        let value = self.value(current)?;
+        let window = self.stats.window().as_secs();
+        let subject = match &self.field {
+            Some(field) => format!("mean {field} {value:.1} over {window}s"),
+            None => format!("{value} lines in {window}s"),
+        };
+
+        if let Some(above) = self.above {
+            if value > above {
+                return Some(format!("{}: {subject} (above {above})", self.name));
+            }
+        }
+        if let (Some(increase), true) = (self.increase, self.stats.has_previous(now)) {
+            let before = self.value(previous).filter(|before| *before > 0.0)?;
+            let growth = (value - before) * 100.0 / before;
+            if growth > increase {
+                return Some(format!(
+                    "{}: {subject} (+{growth:.0}% over the previous {window}s)",
+                    self.name
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// The alerts checked against every line that passed the pipeline.
+#[derive(Default)]
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    /// The profile applied when an alert starts firing.
+    pub incident: Option<Incident>,
+}
+
+pub type SharedAlerts = Arc<RwLock<Alerts>>;
+
+impl Alerts {
+    pub fn try_new(configs: &[AlertConfig]) -> anyhow::Result<Self> {
+        let origin = Instant::now();
+        let alerts = configs
+            .iter()
+            .map(|config| Alert::try_new(config, origin))
+            .collect::<anyhow::Result<Vec<Alert>>>()?;
+        Ok(Self {
+            alerts,
+            incident: None,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    /// Count the line towards the alerts it matches,
+    /// returning whether any of them is firing.
+    pub fn observe(&mut self, line: &str, now: Instant) -> bool {
+        let mut firing = false;
+        for alert in &mut self.alerts {
+            if !alert.pattern.is_match(line) {
+                continue;
+            }
+            firing |= alert.firing.is_some();
+            match &alert.field {
+                Some(field) => {
+                    let parsed = fields::parse(line);
+                    if let Some(value) =
+                        fields::find(&parsed, &[field]).and_then(|value| value.parse::<f64>().ok())
+                    {
+                        alert.stats.record(now, value);
+                    }
+                }
+                None => alert.stats.record(now, 1.0),
+            }
+        }
+        firing
+    }
+
+    /// Check the conditions again, returning the reasons of the alerts that started firing.
+    pub fn evaluate(&mut self, now: Instant) -> Vec<String> {
+        let mut fired = Vec::new();
+        for alert in &mut self.alerts {
+            let firing = alert.evaluate(now);
+            if let (None, Some(reason)) = (&alert.firing, &firing) {
+                fired.push(reason.clone());
+            }
+            alert.firing = firing;
+        }
+        fired
+    }
+
+    /// Reasons of the alerts currently firing.
+    pub fn firing(&self) -> Vec<String> {
+        self.alerts
+            .iter()
+            .filter_map(|alert| alert.firing.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increase_fires_on_growth_over_the_previous_window() {
+        let mut alerts = Alerts::try_new(&[AlertConfig {
+            name: String::from("errors"),
+            pattern: String::from("ERROR"),
+            field: None,
+            window: 10,
+            above: None,
+            increase: Some(50.0),
+        }])
+        .unwrap();
+        let origin = Instant::now();
+        let at = |secs: u64| origin + Duration::from_secs(secs);
+
+        for _ in 0..4 {
+            alerts.observe("ERROR timeout", at(5));
+        }
+        alerts.observe("INFO ok", at(12));
+        assert!(alerts.evaluate(at(19)).is_empty());
+
+        for _ in 0..5 {
+            alerts.observe("ERROR timeout", at(15));
+        }
+        // 5 errors against 4 in the previous window is +25%.
+        assert!(alerts.evaluate(at(20)).is_empty());
+
+        alerts.observe("ERROR timeout", at(19));
+        alerts.observe("ERROR timeout", at(19));
+        // The running second is not counted yet.
+        assert!(alerts.evaluate(at(19)).is_empty());
+        assert_eq!(
+            alerts.evaluate(at(20)),
+            vec![String::from(
+                "errors: 7 lines in 10s (+75% over the previous 10s)"
+            )]
+        );
+        // Already firing
+        assert!(alerts.evaluate(at(20)).is_empty());
+        assert_eq!(alerts.firing().len(), 1);
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Mutex,
+};
+#[cfg(unix)]
+use std::{
+    fs::Permissions,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+};
+
+use crate::crypt::Cipher;
+
+/// Number of spilled lines loaded at once while scanning the archive.
+const SCAN_CHUNK: usize = 65536;
+
+/// Lines retained from the stream.
+///
+/// The latest `capacity` lines are kept in memory.
+/// If a spill file is configured, older lines are appended to it instead of being dropped,
+/// so that the whole session stays searchable with bounded memory.
+pub struct Archive {
+    recent: VecDeque<String>,
+    capacity: usize,
+    spill: Option<Spill>,
+    // Lines lost for good, evicted without a spill file or dropped before reaching the archive
+    dropped: usize,
+}
+
+struct Spill {
+    file: Mutex<File>,
+    // Byte offset of each spilled line in the file
+    offsets: Vec<u64>,
+    // Size of the file, which is the offset of the next line
+    end: u64,
+    // Set to write the lines encrypted (see `--encrypt`)
+    cipher: Option<Cipher>,
+}
+
+impl Archive {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            recent: VecDeque::with_capacity(capacity + 1),
+            capacity,
+            spill: None,
+            dropped: 0,
+        }
+    }
+
+    /// Create an archive that spills old lines to the given file, truncating it,
+    /// and encrypting them with `cipher` if any.
+    /// The file is made readable by the owner only, as the lines may be sensitive.
+    pub fn with_spill_file(
+        capacity: usize,
+        path: &Path,
+        cipher: Option<Cipher>,
+    ) -> anyhow::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options.open(path)?;
+        #[cfg(unix)]
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        Ok(Self {
+            spill: Some(Spill {
+                file: Mutex::new(file),
+                offsets: Vec::new(),
+                end: 0,
+                cipher,
+            }),
+            ..Self::new(capacity)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.spilled_len() + self.recent.len()
+    }
+
+    fn spilled_len(&self) -> usize {
+        self.spill.as_ref().map_or(0, |spill| spill.offsets.len())
+    }
+
+    /// Append a line, evicting (or spilling) the oldest one if the memory is full.
+    pub fn push(&mut self, line: String) -> anyhow::Result<()> {
+        // Pushing before evicting also spills the line right away when the capacity is 0.
+        self.recent.push_back(line);
+        if self.recent.len() > self.capacity {
+            let evicted = self.recent.pop_front().expect("recent lines exist");
+            if let Some(spill) = &mut self.spill {
+                let evicted = match &spill.cipher {
+                    Some(cipher) => cipher.encrypt(&evicted),
+                    None => evicted,
+                };
+                let mut file = spill.file.lock().expect("spill file lock poisoned");
+                file.seek(SeekFrom::Start(spill.end))?;
+                file.write_all(evicted.as_bytes())?;
+                file.write_all(b"\n")?;
+                spill.offsets.push(spill.end);
+                spill.end += evicted.len() as u64 + 1;
+            } else {
+                self.dropped += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of lines of the session missing from the archive.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Account for lines dropped before they reached the archive.
+    pub fn add_dropped(&mut self, count: usize) {
+        self.dropped += count;
+    }
+
+    /// Get the line at the given index, where 0 is the oldest line in the archive.
+    pub fn get(&self, index: usize) -> anyhow::Result<String> {
+        let spilled = self.spilled_len();
+        if index >= spilled {
+            return self
+                .recent
+                .get(index - spilled)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("line {index} is out of the archive"));
+        }
+
+        let spill = self.spill.as_ref().expect("spilled lines exist");
+        let start = spill.offsets[index];
+        let end = spill.offsets.get(index + 1).copied().unwrap_or(spill.end);
+        let mut buf = vec![0; (end - start).saturating_sub(1) as usize];
+        let mut file = spill.file.lock().expect("spill file lock poisoned");
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut buf)?;
+        spill.read(&buf)
+    }
+
+    /// Visit all lines from the oldest, in chunks along with the index of their first line.
+    /// Spilled lines are read from the file chunk by chunk to keep memory bounded.
+    pub fn scan<F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(usize, &[String]),
+    {
+        let mut first = 0;
+        if let Some(spill) = &self.spill {
+            let mut file = spill.file.lock().expect("spill file lock poisoned");
+            file.seek(SeekFrom::Start(0))?;
+            let mut reader = BufReader::new(&mut *file);
+            let mut chunk = Vec::with_capacity(SCAN_CHUNK.min(spill.offsets.len()));
+            let mut buf = Vec::new();
+            // Split by the offsets rather than by newlines,
+            // since a multi-line record is stored as a single entry.
+            for (i, start) in spill.offsets.iter().enumerate() {
+                let end = spill.offsets.get(i + 1).copied().unwrap_or(spill.end);
+                buf.resize((end - start) as usize, 0);
+                reader.read_exact(&mut buf)?;
+                buf.pop();
+                chunk.push(spill.read(&buf)?);
+                if chunk.len() == SCAN_CHUNK {
+                    f(first, &chunk);
+                    first += chunk.len();
+                    chunk.clear();
+                }
+            }
+            if !chunk.is_empty() {
+                f(first, &chunk);
+                first += chunk.len();
+            }
+        }
+
+        let (a, b) = self.recent.as_slices();
+        f(first, a);
+        f(first + a.len(), b);
+        Ok(())
+    }
+}
+
+impl Spill {
+    /// The line stored as the given bytes of the file.
+    fn read(&self, stored: &[u8]) -> anyhow::Result<String> {
+        let line = String::from_utf8_lossy(stored);
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(&line),
+            None => Ok(line.into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use proptest::prelude::*;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::spawn::{self, DropPolicy};
+
+    fn scanned(archive: &Archive) -> Vec<String> {
+        let mut lines = Vec::new();
+        archive
+            .scan(|first, chunk| {
+                assert_eq!(first, lines.len());
+                lines.extend_from_slice(chunk);
+            })
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn push_evicts_oldest_lines_without_spill() {
+        let mut archive = Archive::new(2);
+        for line in ["a", "b", "c"] {
+            archive.push(line.to_string()).unwrap();
+        }
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.get(0).unwrap(), "b");
+        assert_eq!(scanned(&archive), ["b", "c"]);
+    }
+
+    #[test]
+    fn push_spills_oldest_lines_to_file() {
+        let path = std::env::temp_dir().join(format!("sig-spill-test-{}", std::process::id()));
+        let mut archive = Archive::with_spill_file(2, &path, None).unwrap();
+        #[cfg(unix)]
+        {
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        for line in ["a", "", "c\ncc", "d", "e"] {
+            archive.push(line.to_string()).unwrap();
+        }
+        assert_eq!(archive.len(), 5);
+        assert_eq!(archive.get(1).unwrap(), "");
+        assert_eq!(archive.get(2).unwrap(), "c\ncc");
+        assert_eq!(archive.get(4).unwrap(), "e");
+        assert_eq!(scanned(&archive), ["a", "", "c\ncc", "d", "e"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn spilled_lines_are_encrypted_with_a_cipher() {
+        let path = std::env::temp_dir().join(format!("sig-spill-crypt-{}", std::process::id()));
+        let mut archive = Archive::with_spill_file(1, &path, Some(Cipher::random())).unwrap();
+        for line in ["secret a", "secret\nb", "c"] {
+            archive.push(line.to_string()).unwrap();
+        }
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+        assert_eq!(archive.get(1).unwrap(), "secret\nb");
+        assert_eq!(scanned(&archive), ["secret a", "secret\nb", "c"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// A step on the archive: pushing a line, or reading the line at an index.
+    #[derive(Clone, Debug)]
+    enum Step {
+        Push(String),
+        Get(usize),
+    }
+
+    fn line() -> impl Strategy<Value = String> {
+        // Newlines and multi-byte characters, as in multi-line records, shift the offsets.
+        "[a-c\né]{0,12}"
+    }
+
+    fn steps() -> impl Strategy<Value = Vec<Step>> {
+        prop::collection::vec(
+            prop_oneof![
+                3 => line().prop_map(Step::Push),
+                1 => (0..48usize).prop_map(Step::Get),
+            ],
+            0..48,
+        )
+    }
+
+    fn spill_path(name: &str) -> std::path::PathBuf {
+        static CASES: AtomicUsize = AtomicUsize::new(0);
+        let case = CASES.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("sig-{name}-{}-{case}", std::process::id()))
+    }
+
+    proptest! {
+        #[test]
+        fn pushes_keep_the_latest_lines_in_arrival_order(
+            capacity in 0..6usize,
+            spilled in any::<bool>(),
+            steps in steps(),
+        ) {
+            let path = spill_path("spill-prop");
+            let mut archive = if spilled {
+                Archive::with_spill_file(capacity, &path, None).unwrap()
+            } else {
+                Archive::new(capacity)
+            };
+
+            let mut pushed = Vec::new();
+            for step in steps {
+                match step {
+                    Step::Push(line) => {
+                        pushed.push(line.clone());
+                        archive.push(line).unwrap();
+                    }
+                    Step::Get(index) => {
+                        let kept = &pushed[archive.dropped()..];
+                        match kept.get(index) {
+                            Some(line) => prop_assert_eq!(&archive.get(index).unwrap(), line),
+                            None => prop_assert!(archive.get(index).is_err()),
+                        }
+                    }
+                }
+                // Memory never holds more than the capacity, and no line is lost with a spill file.
+                prop_assert!(archive.recent.len() <= capacity);
+                prop_assert_eq!(archive.len() + archive.dropped(), pushed.len());
+                if spilled {
+                    prop_assert_eq!(archive.dropped(), 0);
+                } else {
+                    prop_assert_eq!(archive.len(), pushed.len().min(capacity));
+                }
+                prop_assert_eq!(scanned(&archive), &pushed[archive.dropped()..]);
+            }
+            if spilled {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+
+        #[test]
+        fn concurrent_producers_evict_the_oldest_lines(
+            producers in 1..5usize,
+            lines in 0..40usize,
+            capacity in 0..8usize,
+            spilled in any::<bool>(),
+        ) {
+            let path = spill_path("spill-producers");
+            let archive = if spilled {
+                Archive::with_spill_file(capacity, &path, None).unwrap()
+            } else {
+                Archive::new(capacity)
+            };
+            // The lines in the order the producers got to push them
+            let archive = Arc::new(Mutex::new((archive, Vec::new())));
+            let threads = (0..producers)
+                .map(|producer| {
+                    let archive = Arc::clone(&archive);
+                    std::thread::spawn(move || {
+                        for seq in 0..lines {
+                            let line = format!("{producer}:{seq}");
+                            let (archive, pushed) = &mut *archive.lock().unwrap();
+                            pushed.push(line.clone());
+                            archive.push(line).unwrap();
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+
+            let (archive, pushed) = &*archive.lock().unwrap();
+            prop_assert_eq!(pushed.len(), producers * lines);
+            prop_assert_eq!(archive.len() + archive.dropped(), pushed.len());
+            let kept = if spilled {
+                pushed.len()
+            } else {
+                pushed.len().min(capacity)
+            };
+            prop_assert_eq!(archive.len(), kept);
+            // Exactly the oldest lines were evicted, whichever producer they came from.
+            let scanned = scanned(archive);
+            prop_assert_eq!(&scanned, &pushed[pushed.len() - kept..]);
+            for producer in 0..producers {
+                let seqs = scanned
+                    .iter()
+                    .filter_map(|line| line.strip_prefix(&format!("{producer}:")))
+                    .map(|seq| seq.parse::<usize>().unwrap())
+                    .collect::<Vec<_>>();
+                // Each producer's latest lines, without gaps.
+                let first = lines - seqs.len();
+                prop_assert_eq!(seqs, (first..lines).collect::<Vec<_>>());
+            }
+            if spilled {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+
+        #[test]
+        fn concurrent_producers_keep_their_order_through_the_buffer(
+            policy in prop_oneof![
+                Just(DropPolicy::Block),
+                Just(DropPolicy::DropOldest),
+                Just(DropPolicy::DropNewest),
+            ],
+            buffer_capacity in 1..5usize,
+            capacity in 1..9usize,
+            yields in prop::collection::vec(any::<bool>(), 150),
+        ) {
+            const PRODUCERS: usize = 3;
+            const LINES: usize = 50;
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(4)
+                .build()
+                .unwrap();
+            let (archive, received, dropped) = runtime.block_on(async {
+                let mut archive = Archive::new(capacity);
+                let (source_tx, source_rx) = mpsc::channel(1);
+                let (tx, mut rx) = mpsc::channel(1);
+                let dropped = Arc::new(AtomicUsize::new(0));
+                let buffer =
+                    spawn::spawn_buffer(buffer_capacity, policy, source_rx, tx, dropped.clone());
+                for producer in 0..PRODUCERS {
+                    let source_tx = source_tx.clone();
+                    tokio::spawn(async move {
+                        for seq in 0..LINES {
+                            source_tx.send(format!("{producer}:{seq}")).await.unwrap();
+                        }
+                    });
+                }
+                drop(source_tx);
+
+                let mut received = Vec::new();
+                while let Some(line) = rx.recv().await {
+                    // A slow reader, now and then, lets the buffer fill up.
+                    if yields[received.len() % yields.len()] {
+                        tokio::task::yield_now().await;
+                    }
+                    received.push(line.clone());
+                    archive.push(line).unwrap();
+                }
+                buffer.handle.await.unwrap().unwrap();
+                let dropped = dropped.load(Ordering::Relaxed);
+                archive.add_dropped(dropped);
+                (archive, received, dropped)
+            });
+
+            prop_assert_eq!(archive.len() + archive.dropped(), PRODUCERS * LINES);
+            if let DropPolicy::Block = policy {
+                prop_assert_eq!(dropped, 0);
+            }
+            prop_assert_eq!(scanned(&archive), &received[received.len() - archive.len()..]);
+            for producer in 0..PRODUCERS {
+                let seqs = received
+                    .iter()
+                    .filter_map(|line| line.strip_prefix(&format!("{producer}:")))
+                    .map(|seq| seq.parse::<usize>().unwrap())
+                    .collect::<Vec<_>>();
+                prop_assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+    }
+}
@@ -1,60 +1,972 @@
+use std::{
+    collections::BTreeSet,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use rayon::prelude::*;
 
 use promkit::{async_trait::async_trait, Prompt};
 use promkit_core::{
     crossterm::{
-        self,
         event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
-        style::ContentStyle,
+        style::{Color, ContentStyle},
     },
     grapheme::StyledGraphemes,
+    pane::Pane,
     render::Renderer,
     PaneFactory,
 };
 use promkit_widgets::{
-    listbox::{self, Listbox},
-    text_editor,
+    listbox, text,
+    text_editor::{self, TextEditor},
 };
+use regex::Regex;
+use tokio::task::JoinHandle;
 
 use crate::{
+    archive::Archive,
+    audit,
+    backend::Backend,
+    clipboard::Clipboard,
     config::{matches_keybind, ArchivedKeybinds},
-    highlight::highlight,
+    diff::Diffs,
+    export::{self, ExportFormat},
+    highlight::{highlight, highlight_over, SharedHighlights},
+    layout::{self, Overflow},
+    minimap::{self, Minimap},
+    schema::{FieldPicker, Pick, Schema},
+    spawn, Signal,
 };
 
+/// How long the cursor stays on a line before the preview command is run for it.
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(150);
+/// How often the event loop wakes up to show the output of the preview command.
+const PREVIEW_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Output of the `--preview` command for the line under the cursor, shown on the right.
+struct Preview {
+    template: String,
+    // Line the output is for
+    shown: Option<usize>,
+    output: Vec<String>,
+    // Line under the cursor and since when it is
+    target: Option<(usize, Instant)>,
+    // Line the command is running for
+    running: Option<(usize, JoinHandle<anyhow::Result<Vec<String>>>)>,
+}
+
+impl Preview {
+    fn new(template: String) -> Self {
+        Self {
+            template,
+            shown: None,
+            output: Vec::new(),
+            target: None,
+            running: None,
+        }
+    }
+
+    /// Follow the line under the cursor, stopping the command running for another line.
+    fn retarget(&mut self, line: Option<usize>) {
+        if self.target.map(|(target, _)| target) == line {
+            return;
+        }
+        self.target = line.map(|line| (line, Instant::now()));
+        if let Some((running, task)) = &self.running {
+            if Some(*running) != line {
+                task.abort();
+                self.running = None;
+            }
+        }
+    }
+
+    /// Take the output of the finished command, or run it for the target line once settled.
+    /// Returns true if the output was updated.
+    async fn poll(&mut self, lines: &Archive) -> anyhow::Result<bool> {
+        if let Some((line, task)) = self.running.take_if(|(_, task)| task.is_finished()) {
+            self.output = match task.await? {
+                Ok(output) => output,
+                Err(e) => vec![format!("Failed to run the preview command: {e}")],
+            };
+            self.shown = Some(line);
+            return Ok(true);
+        }
+
+        match self.target {
+            Some((line, since))
+                if self.running.is_none()
+                    && self.shown != Some(line)
+                    && since.elapsed() >= PREVIEW_DEBOUNCE =>
+            {
+                let template = self.template.clone();
+                let text = lines.get(line)?;
+                self.running = Some((
+                    line,
+                    tokio::spawn(async move { spawn::run_preview(&template, &text).await }),
+                ));
+                Ok(false)
+            }
+            None if self.shown.is_some() => {
+                self.shown = None;
+                self.output.clear();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Index {
     Readline = 0,
-    Text = 1,
+    Status = 1,
+    Text = 2,
 }
 
 struct Archived {
-    renderer: Renderer<Index>,
+    backend: Arc<dyn Backend>,
+    // Created on the first render, from the position of the cursor on the terminal
+    renderer: Option<Renderer<Index>>,
     readline: text_editor::State,
     // To track changes in the text editor
     prev_query: String,
-    // Initial text to search
-    init_text: Listbox,
-    // Search results
-    text: listbox::State,
+    // All lines kept from the stream
+    lines: Archive,
+    // Indexes of the lines matching the current query, in ascending order
+    matches: Vec<usize>,
+    // Whether to show only the matched lines or the whole archive
+    filtered: bool,
+    // Position of the cursor in the current view
+    cursor: usize,
+    // Position of the first item rendered in the current view
+    offset: usize,
+    // Number of lines and of the rows they take in the viewport, as last rendered
+    viewport: (usize, usize),
+    // Line where the selection started, if any
+    selection: Option<usize>,
+    listbox: listbox::Config,
+    selected_item_style: ContentStyle,
+    clipboard: Clipboard,
+    export_format: ExportFormat,
     highlight_style: ContentStyle,
+    highlights: SharedHighlights,
     case_insensitive: bool,
-    cmd: Option<String>,
+    retryable: bool,
+    on_select: Option<String>,
+    preview: Option<Preview>,
+    correlation_id: Option<Regex>,
+    // Query and filter to go back to from the lines sharing a correlation ID
+    correlated_from: Option<(String, bool)>,
+    overflow: Overflow,
+    // Built when the minimap is first shown, and kept while it is hidden
+    minimap: Option<Minimap>,
+    show_minimap: bool,
+    // Built when the schema is first shown, like the minimap,
+    // keeping the fields picked to project, color and aggregate
+    picker: Option<FieldPicker>,
+    show_schema: bool,
+    diffs: Diffs,
+    // Hunks of the diffs whose lines are hidden under their header
+    folded: BTreeSet<usize>,
+    // Lines of the view left once the folded hunks are hidden, if any is folded
+    unfolded: Option<Vec<usize>>,
+    // Whether the status bar is hidden, see `--quiet`
+    quiet: bool,
+    // Shown in the status bar until the next key, e.g. why copying failed
+    notice: Option<String>,
+    // Whether Enter picks the line under the cursor rather than running `on_select`
+    pick: bool,
+    // Lines marked to be picked together, in ascending order
+    marked: BTreeSet<usize>,
+    picked: Option<Vec<String>>,
+    aborted: bool,
     keybinds: ArchivedKeybinds,
+    // Set when quitting sig rather than going back to streaming, to whether to print the lines
+    exit: Option<bool>,
 }
 
 impl Archived {
-    fn evaluate_internal(&mut self, event: &Event) -> anyhow::Result<promkit::Signal> {
-        if matches_keybind(event, &self.keybinds.retry) {
-            if self.cmd.is_some() {
-                // Exiting archive mode here allows
-                // the caller to re-enter streaming mode,
-                // as it is running in an infinite loop.
-                return Ok(promkit::Signal::Quit);
+    /// The archived view of the lines, searched right away for the query of `readline` if any.
+    fn new(
+        readline: text_editor::State,
+        lines: Archive,
+        options: ArchivedOptions,
+        overflow: Overflow,
+        passthrough: bool,
+    ) -> anyhow::Result<Self> {
+        let ArchivedOptions {
+            backend,
+            listbox,
+            selected_item_style,
+            clipboard,
+            export_format,
+            highlight_style,
+            highlights,
+            keybinds,
+            case_insensitive,
+            retryable,
+            on_select,
+            preview,
+            correlation_id,
+            quiet,
+            pick,
+        } = options;
+        let diffs = Diffs::scan(&lines)?;
+        let mut archived = Archived {
+            backend,
+            renderer: None,
+            readline,
+            prev_query: String::new(),
+            matches: (0..lines.len()).collect(),
+            lines,
+            filtered: !passthrough,
+            cursor: 0,
+            offset: 0,
+            viewport: (0, 0),
+            selection: None,
+            listbox,
+            selected_item_style,
+            clipboard,
+            export_format,
+            highlight_style,
+            highlights,
+            case_insensitive,
+            retryable,
+            on_select,
+            preview: preview.map(Preview::new),
+            correlation_id,
+            correlated_from: None,
+            overflow,
+            minimap: None,
+            show_minimap: false,
+            picker: None,
+            show_schema: false,
+            diffs,
+            folded: BTreeSet::new(),
+            unfolded: None,
+            quiet,
+            notice: None,
+            pick,
+            marked: BTreeSet::new(),
+            picked: None,
+            aborted: false,
+            keybinds,
+            exit: None,
+        };
+
+        // Apply the query pre-filled in the text editor, if any.
+        let query = archived
+            .readline
+            .texteditor
+            .text_without_cursor()
+            .to_string();
+        if !query.is_empty() {
+            archived.update_matches(&query)?;
+            archived.prev_query = query;
+        }
+        Ok(archived)
+    }
+
+    /// Lines of the current view, or None if they are all the lines of the archive.
+    fn view(&self) -> Option<&[usize]> {
+        match (&self.unfolded, self.filtered) {
+            (Some(unfolded), _) => Some(unfolded),
+            (None, true) => Some(&self.matches),
+            (None, false) => None,
+        }
+    }
+
+    /// Number of items in the current view.
+    fn view_len(&self) -> usize {
+        match self.view() {
+            Some(view) => view.len(),
+            None => self.lines.len(),
+        }
+    }
+
+    /// Index of the line shown at the given position of the current view.
+    fn line_at(&self, position: usize) -> usize {
+        match self.view() {
+            Some(view) => view[position],
+            None => position,
+        }
+    }
+
+    /// Position of the given line in the current view.
+    /// If the line is hidden by the filter, the position of the next matched line is returned.
+    fn position_of(&self, line: usize) -> usize {
+        let position = match self.view() {
+            Some(view) => view.partition_point(|&l| l < line),
+            None => line,
+        };
+        position.min(self.view_len().saturating_sub(1))
+    }
+
+    /// Recompute the lines of the view left by the folded hunks.
+    fn unfold_view(&mut self) {
+        self.unfolded = None;
+        if self.folded.is_empty() {
+            return;
+        }
+        let hidden = |line: &usize| {
+            self.diffs.hunk_at(*line).is_some_and(|idx| {
+                self.folded.contains(&idx) && self.diffs.hunk(idx).start != *line
+            })
+        };
+        self.unfolded = Some(match self.view() {
+            Some(view) => view.iter().copied().filter(|line| !hidden(line)).collect(),
+            None => (0..self.lines.len()).filter(|line| !hidden(line)).collect(),
+        });
+    }
+
+    /// Fold the hunk under the cursor under its header, or unfold it.
+    fn toggle_fold(&mut self) {
+        if self.view_len() == 0 {
+            return;
+        }
+        let Some(idx) = self.diffs.hunk_at(self.line_at(self.cursor)) else {
+            return;
+        };
+        if !self.folded.remove(&idx) {
+            self.folded.insert(idx);
+        }
+        self.unfold_view();
+        self.cursor = self.position_of(self.diffs.hunk(idx).start);
+    }
+
+    fn move_cursor_to(&mut self, position: usize) {
+        self.cursor = position.min(self.view_len().saturating_sub(1));
+    }
+
+    fn goto_next_match(&mut self) {
+        if self.view_len() == 0 {
+            return;
+        }
+        let line = self.line_at(self.cursor);
+        let next = self.matches.partition_point(|&m| m <= line);
+        if let Some(&m) = self.matches.get(next) {
+            self.cursor = self.position_of(m);
+        }
+    }
+
+    fn goto_prev_match(&mut self) {
+        if self.view_len() == 0 {
+            return;
+        }
+        let line = self.line_at(self.cursor);
+        let prev = self.matches.partition_point(|&m| m < line);
+        if prev > 0 {
+            self.cursor = self.position_of(self.matches[prev - 1]);
+        }
+    }
+
+    /// Positions of the current view between the given lines, inclusive.
+    fn positions_between(&self, first: usize, last: usize) -> Range<usize> {
+        match self.view() {
+            Some(view) => {
+                view.partition_point(|&l| l < first)..view.partition_point(|&l| l <= last)
             }
+            None => first..(last + 1).min(self.lines.len()),
+        }
+    }
+
+    /// Positions of the selected items in the current view.
+    fn selected_positions(&self) -> Option<Range<usize>> {
+        if self.view_len() == 0 {
+            return None;
+        }
+        let anchor = self.selection?;
+        let line = self.line_at(self.cursor);
+        Some(self.positions_between(anchor.min(line), anchor.max(line)))
+    }
+
+    fn toggle_selection(&mut self) {
+        self.selection = match self.selection {
+            Some(_) => None,
+            None => (self.view_len() > 0).then(|| self.line_at(self.cursor)),
+        };
+    }
+
+    /// Mark or unmark the line under the cursor, moving on to the next one.
+    fn toggle_mark(&mut self) {
+        if self.view_len() == 0 {
+            return;
+        }
+        let line = self.line_at(self.cursor);
+        if !self.marked.remove(&line) {
+            self.marked.insert(line);
+        }
+        self.move_cursor_to(self.cursor + 1);
+    }
+
+    /// The marked lines, or the line under the cursor if none is marked.
+    fn picked_lines(&self) -> anyhow::Result<Vec<String>> {
+        if self.marked.is_empty() {
+            return Ok(vec![self.lines.get(self.line_at(self.cursor))?]);
+        }
+        self.marked
+            .iter()
+            .map(|&line| self.lines.get(line))
+            .collect()
+    }
+
+    /// Copy the selected lines, or the line under the cursor, to the clipboard.
+    fn copy(&mut self) -> anyhow::Result<()> {
+        let positions = match self.selected_positions() {
+            Some(positions) => positions,
+            None if self.view_len() > 0 => self.cursor..self.cursor + 1,
+            None => return Ok(()),
+        };
+        let text = positions
+            .map(|position| {
+                self.lines.get(self.line_at(position)).map(|line| {
+                    export::format_line(
+                        &line,
+                        &self.prev_query,
+                        self.case_insensitive,
+                        self.export_format,
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<String>>>()?
+            .join("\n");
+        self.clipboard.copy(&text)?;
+        audit::record("archived", "copy", &text);
+        self.selection = None;
+        Ok(())
+    }
+
+    fn toggle_filter(&mut self) {
+        let line = (self.view_len() > 0).then(|| self.line_at(self.cursor));
+        self.filtered = !self.filtered;
+        self.unfold_view();
+        self.cursor = line.map(|l| self.position_of(l)).unwrap_or_default();
+    }
+
+    /// Filter the archive to the lines sharing the correlation ID of the line under the cursor,
+    /// or go back to the query from before if they are shown.
+    fn correlate(&mut self) -> anyhow::Result<()> {
+        if let Some((query, filtered)) = self.correlated_from.take() {
+            self.readline.texteditor = TextEditor::new(query);
+            if self.filtered != filtered {
+                self.toggle_filter();
+            }
+            return Ok(());
+        }
+
+        let (Some(pattern), true) = (&self.correlation_id, self.view_len() > 0) else {
+            return Ok(());
+        };
+        let line = self.line_at(self.cursor);
+        let text = self.lines.get(line)?;
+        let Some(id) = pattern
+            .captures(&text)
+            .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+        else {
+            return Ok(());
+        };
+
+        // The query would match more than the ID, as it is split on `|` and its parts trimmed.
+        if id.as_str().contains('|') || id.as_str().trim() != id.as_str() {
+            self.notice = Some(format!(
+                "Cannot filter by the ID {:?}, which holds `|` or surrounding spaces",
+                id.as_str()
+            ));
+            return Ok(());
+        }
+        let query = regex::escape(id.as_str());
+        self.correlated_from = Some((self.prev_query.clone(), self.filtered));
+        self.readline.texteditor = TextEditor::new(&query);
+        self.filtered = true;
+        audit::record("archived", "query", &query);
+        self.update_matches(&query)?;
+        self.prev_query = query;
+        // Stay on the line the ID was taken from.
+        self.cursor = self.position_of(line);
+        Ok(())
+    }
+
+    /// Match the lines again if the query was edited.
+    fn follow_query(&mut self) -> anyhow::Result<()> {
+        let current_query = self.readline.texteditor.text_without_cursor().to_string();
+        if self.prev_query != current_query {
+            // Update the matched lines based on the current query
+            audit::record("archived", "query", &current_query);
+            self.update_matches(&current_query)?;
+            // Editing the query leaves the lines of the correlation ID.
+            self.correlated_from = None;
+
+            // Update previous query
+            self.prev_query = current_query;
+        }
+        Ok(())
+    }
+
+    /// Rebuild the index of matched lines for the given query.
+    fn update_matches(&mut self, query: &str) -> anyhow::Result<()> {
+        let line = (self.view_len() > 0).then(|| self.line_at(self.cursor));
+        let mut matches = Vec::new();
+        self.lines.scan(|first, chunk| {
+            matches.par_extend(chunk.par_iter().enumerate().filter_map(|(i, line)| {
+                highlight(query, line, self.highlight_style, self.case_insensitive)
+                    .map(|_| first + i)
+            }));
+        })?;
+        self.matches = matches;
+        self.unfold_view();
+
+        // Restart from the first result when filtering,
+        // otherwise stay on the same line of the archive.
+        self.cursor = match line {
+            Some(line) if !self.filtered => self.position_of(line),
+            _ => 0,
+        };
+        self.offset = 0;
+        Ok(())
+    }
+
+    /// Number of rows available to render the lines.
+    fn text_height(&self, width: u16, height: u16) -> usize {
+        let readline_rows = self.readline.create_pane(width, height).visible_row_count();
+        let status_rows = if self.quiet && self.notice.is_none() {
+            0
+        } else {
+            1
+        };
+        let rows = (height as usize).saturating_sub(readline_rows + status_rows);
+        match self.listbox.lines {
+            Some(lines) => lines.min(rows),
+            None => rows,
+        }
+    }
+
+    fn styled_item(&self, position: usize, width: usize) -> anyhow::Result<StyledGraphemes> {
+        let index = self.line_at(position);
+        let line = self.lines.get(index)?;
+        let line = &match self
+            .picker
+            .as_ref()
+            .and_then(|picker| picker.project(&line))
+        {
+            Some(projected) => projected,
+            None => line,
+        };
+        let base_style = match self.selected_positions() {
+            Some(selected) if selected.contains(&position) => self.selected_item_style,
+            _ if self.marked.contains(&index) => self.selected_item_style,
+            _ => self
+                .diffs
+                .kind(index)
+                .map(|kind| kind.style())
+                .unwrap_or_default(),
+        };
+        let base = self
+            .highlights
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .styled(line, base_style);
+        let base = match &self.picker {
+            Some(picker) => picker.color(line, base),
+            None => base,
+        };
+        let styled = highlight_over(
+            &self.prev_query,
+            line,
+            base.clone(),
+            self.highlight_style,
+            self.case_insensitive,
+        )
+        .unwrap_or(base);
+        let styled = match self.diffs.hunk_at(index) {
+            Some(idx) if self.folded.contains(&idx) && self.diffs.hunk(idx).start == index => {
+                let label = StyledGraphemes::from_str(
+                    self.diffs.fold_label(idx),
+                    ContentStyle {
+                        foreground_color: Some(Color::DarkGrey),
+                        ..Default::default()
+                    },
+                );
+                StyledGraphemes::from_iter([&styled, &label])
+            }
+            _ => styled,
+        };
+        let cursor_width = StyledGraphemes::from(&self.listbox.cursor).widths();
+        // Cut the line rather than the cursor, so that each line takes a single row.
+        let styled = match self.overflow {
+            Overflow::Truncate(scroll) => layout::indent(
+                layout::truncate_lines(&styled, width.saturating_sub(cursor_width), scroll),
+                cursor_width,
+            ),
+            Overflow::Wrap => {
+                layout::wrap_marked(&styled, width.saturating_sub(cursor_width), cursor_width)
+            }
+        };
+
+        Ok(if position == self.cursor {
+            let item =
+                StyledGraphemes::from_iter([&StyledGraphemes::from(&self.listbox.cursor), &styled]);
+            match &self.listbox.active_item_style {
+                Some(style) => item.apply_style(*style),
+                None => item,
+            }
+        } else {
+            let item = StyledGraphemes::from_iter([
+                &StyledGraphemes::from(
+                    " ".repeat(StyledGraphemes::from(&self.listbox.cursor).widths()),
+                ),
+                &styled,
+            ]);
+            match &self.listbox.inactive_item_style {
+                Some(style) => item.apply_style(*style),
+                None => item,
+            }
+        })
+    }
+
+    /// Adjust the offset so that the whole item under the cursor fits in the viewport.
+    fn scroll_into_view(&mut self, width: usize, height: usize) -> anyhow::Result<()> {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+            return Ok(());
+        }
+
+        // Every line takes a row at least, so only the last `height` lines up to the
+        // cursor can be visible, and only those are laid out to find how many fit.
+        let lowest = self.offset.max((self.cursor + 1).saturating_sub(height));
+        let mut offset = self.cursor;
+        let mut total = 0;
+        for position in (lowest..=self.cursor).rev() {
+            total += layout::rows(&self.styled_item(position, width)?, width, height).len();
+            if total > height {
+                break;
+            }
+            offset = position;
+        }
+        self.offset = offset;
+        Ok(())
+    }
+
+    fn create_text_pane(&mut self, width: u16, height: usize) -> anyhow::Result<Pane> {
+        if let (true, Some(picker)) = (self.show_schema, &self.picker) {
+            let rows = picker
+                .rows(height)
+                .iter()
+                .map(|row| layout::truncate(row, width as usize, 0))
+                .collect();
+            return Ok(Pane::new(rows, 0));
+        }
+        let Some(preview) = &self.preview else {
+            return Ok(Pane::new(self.list_rows(width, height)?, 0));
+        };
+
+        // Split the rows into the lines (left) and the preview (right).
+        let left = width.saturating_sub(1) / 2;
+        let right = width.saturating_sub(left + 1) as usize;
+        let separator = StyledGraphemes::from_str(
+            "│",
+            ContentStyle {
+                foreground_color: Some(Color::DarkGrey),
+                ..Default::default()
+            },
+        );
+        let output = preview
+            .output
+            .iter()
+            .map(|line| layout::fit(&StyledGraphemes::from(line.as_str()), right))
+            .collect::<Vec<_>>();
+        let text_rows = self.list_rows(left, height)?;
+        let rows = (0..height)
+            .map(|row| {
+                let text = text_rows.get(row).cloned().unwrap_or_default();
+                StyledGraphemes::from_iter([
+                    &layout::fit(&text, left as usize),
+                    &separator,
+                    output.get(row).unwrap_or(&StyledGraphemes::default()),
+                ])
+            })
+            .collect();
+        Ok(Pane::new(rows, 0))
+    }
+
+    /// Lay out the lines into the rows, with the minimap on the right if it is shown,
+    /// or else a scrollbar if the lines do not all fit.
+    fn list_rows(&mut self, width: u16, height: usize) -> anyhow::Result<Vec<StyledGraphemes>> {
+        let side_width = if self.show_minimap {
+            minimap::WIDTH
+        } else if self.view_len() > height {
+            1
+        } else {
+            0
+        };
+        if side_width == 0 || width as usize <= side_width {
+            return self.text_rows(width, height);
+        }
+
+        let text_width = width - side_width as u16;
+        let text_rows = self.text_rows(text_width, height)?;
+        let viewport = self.offset..self.offset + self.viewport.0;
+        let side_rows = if self.show_minimap {
+            self.minimap_rows(height, viewport)
+        } else {
+            // Every line in the filtered view is a match, so the matches are ticked only in the whole archive.
+            let marks = match (self.filtered, &self.unfolded) {
+                (true, _) => vec![],
+                (false, Some(unfolded)) => self
+                    .matches
+                    .iter()
+                    .filter_map(|m| unfolded.binary_search(m).ok())
+                    .collect(),
+                (false, None) => self.matches.clone(),
+            };
+            layout::scrollbar(height, self.view_len(), viewport, &marks)
+                .into_iter()
+                .map(|cell| StyledGraphemes::from_iter([cell]))
+                .collect()
+        };
+        Ok(side_rows
+            .iter()
+            .enumerate()
+            .map(|(row, side)| {
+                let text = layout::fit(
+                    text_rows.get(row).unwrap_or(&StyledGraphemes::default()),
+                    text_width as usize,
+                );
+                StyledGraphemes::from_iter([&text, side])
+            })
+            .collect())
+    }
+
+    /// Lay out the rows of the minimap, each standing for the same number of lines of the view.
+    fn minimap_rows(&self, height: usize, viewport: Range<usize>) -> Vec<StyledGraphemes> {
+        let Some(minimap) = &self.minimap else {
+            return vec![StyledGraphemes::default(); height];
+        };
+        let len = self.view_len();
+        let per_row = minimap::lines_per_row(len, height);
+        (0..height)
+            .map(|row| {
+                let start = (row * per_row).min(len);
+                let end = ((row + 1) * per_row).min(len);
+                let matched = match (self.filtered, &self.unfolded) {
+                    (true, _) => end - start,
+                    (false, Some(unfolded)) => unfolded[start..end]
+                        .iter()
+                        .filter(|line| self.matches.binary_search(line).is_ok())
+                        .count(),
+                    (false, None) => {
+                        self.matches.partition_point(|&m| m < end)
+                            - self.matches.partition_point(|&m| m < start)
+                    }
+                };
+                let level = (start..end)
+                    .filter_map(|position| minimap.level(self.line_at(position)))
+                    .max();
+                let in_view = start < viewport.end && viewport.start < end;
+                minimap::row(matched, end - start, level, in_view)
+            })
+            .collect()
+    }
+
+    fn text_rows(&mut self, width: u16, height: usize) -> anyhow::Result<Vec<StyledGraphemes>> {
+        if self.view_len() == 0 || height == 0 {
+            self.viewport = (0, 0);
+            return Ok(vec![]);
+        }
+        self.scroll_into_view(width as usize, height)?;
+
+        let mut rows = Vec::with_capacity(height);
+        let mut lines = 0;
+        for position in self.offset..self.view_len() {
+            if rows.len() >= height {
+                break;
+            }
+            rows.extend(layout::rows(
+                &self.styled_item(position, width as usize)?,
+                width as usize,
+                height,
+            ));
+            lines += 1;
+        }
+        rows.truncate(height);
+        self.viewport = (lines, rows.len());
+        Ok(rows)
+    }
+
+    fn create_status_pane(&self, width: u16, height: u16) -> Pane {
+        if let Some(notice) = &self.notice {
+            return text::State {
+                text: text::Text::from(notice.as_str()),
+                config: text::Config {
+                    style: Some(ContentStyle {
+                        foreground_color: Some(Color::Red),
+                        ..Default::default()
+                    }),
+                    lines: Some(1),
+                },
+            }
+            .create_pane(width, height);
+        }
+        if self.quiet {
+            return Pane::new(vec![], 0);
+        }
+        let badge = if self.show_schema {
+            "[SCHEMA]"
+        } else if self.filtered {
+            "[FILTER]"
+        } else {
+            "[SEARCH]"
+        };
+        let (match_label, line_label) = if self.view_len() == 0 {
+            (String::from("0"), String::from("0"))
+        } else {
+            let line = self.line_at(self.cursor);
+            let ordinal = self.matches.partition_point(|&m| m < line);
+            let match_label = if self.matches.get(ordinal) == Some(&line) {
+                (ordinal + 1).to_string()
+            } else {
+                String::from("-")
+            };
+            (match_label, (line + 1).to_string())
+        };
+
+        let dropped_label = match self.lines.dropped() {
+            0 => String::new(),
+            dropped => format!(" ({dropped} dropped)"),
+        };
+
+        let selected_label = match self.selected_positions() {
+            Some(selected) => format!(" | {} selected", selected.len()),
+            None => String::new(),
+        };
+        let marked_label = match self.marked.len() {
+            0 => String::new(),
+            marked => format!(" | {marked} marked"),
+        };
+        let (viewport_lines, viewport_rows) = self.viewport;
+        let viewport_label = if self.show_schema {
+            String::new()
+        } else {
+            format!(", view {viewport_lines} lines in {viewport_rows} rows")
+        };
+
+        text::State {
+            text: text::Text::from(format!(
+                "{badge} match {match_label}/{}, line {line_label}/{}{dropped_label}{viewport_label}{selected_label}{marked_label}",
+                self.matches.len(),
+                self.lines.len(),
+            )),
+            config: text::Config {
+                style: Some(ContentStyle {
+                    foreground_color: Some(Color::DarkGrey),
+                    ..Default::default()
+                }),
+                lines: Some(1),
+            },
+        }
+        .create_pane(width, height)
+    }
+
+    async fn render(&mut self) -> anyhow::Result<()> {
+        let target = (self.view_len() > 0).then(|| self.line_at(self.cursor));
+        if let Some(preview) = self.preview.as_mut() {
+            preview.retarget(target);
+        }
+        let (width, height) = self.backend.size()?;
+        let text_height = self.text_height(width, height);
+        // The text pane comes first to count the rows the status bar shows.
+        let text_pane = self.create_text_pane(width, text_height)?;
+        let status_pane = self.create_status_pane(width, height);
+        if self.renderer.is_none() {
+            self.renderer = Some(Renderer::try_new()?);
+        }
+        let renderer = self.renderer.as_ref().unwrap();
+        renderer
+            .update([
+                (Index::Readline, self.readline.create_pane(width, height)),
+                (Index::Status, status_pane),
+                (Index::Text, text_pane),
+            ])
+            .render()
+            .await
+    }
+
+    /// Run the `on_select` command for the line under the cursor.
+    async fn exec(&mut self) -> anyhow::Result<()> {
+        if let Some(template) = &self.on_select {
+            if self.view_len() > 0 {
+                let line = self.lines.get(self.line_at(self.cursor))?;
+                audit::record("archived", "exec", &line);
+                spawn::run_on_select(template, &line).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate_internal(
+        &mut self,
+        event: &Event,
+        page_size: usize,
+    ) -> anyhow::Result<promkit::Signal> {
+        if matches_keybind(event, &self.keybinds.retry) && self.retryable {
+            // Exiting archive mode here allows
+            // the caller to re-enter streaming mode,
+            // as it is running in an infinite loop.
+            audit::record("archived", "retry", "");
+            return Ok(promkit::Signal::Quit);
         }
 
         if matches_keybind(event, &self.keybinds.exit) {
-            return Err(anyhow::anyhow!("exit"));
+            self.exit = Some(false);
+            return Ok(promkit::Signal::Quit);
+        }
+
+        if matches_keybind(event, &self.keybinds.exit_and_print) {
+            self.exit = Some(true);
+            return Ok(promkit::Signal::Quit);
+        }
+
+        if matches_keybind(event, &self.keybinds.toggle_schema) {
+            if self.picker.is_none() {
+                self.picker = Some(FieldPicker::new(Schema::infer(&self.lines)?));
+            }
+            self.show_schema = !self.show_schema;
+            return Ok(promkit::Signal::Continue);
+        }
+
+        // The field summary takes the keys while shown, so that fields are picked by letter.
+        if let (true, Some(picker)) = (self.show_schema, self.picker.as_mut()) {
+            let keybinds = &self.keybinds.schema;
+            let pick = if matches_keybind(event, &keybinds.up) {
+                picker.up();
+                None
+            } else if matches_keybind(event, &keybinds.down) {
+                picker.down();
+                None
+            } else if matches_keybind(event, &keybinds.project) {
+                Some(Pick::Project)
+            } else if matches_keybind(event, &keybinds.color) {
+                Some(Pick::Color)
+            } else if matches_keybind(event, &keybinds.aggregate) {
+                Some(Pick::Aggregate)
+            } else {
+                None
+            };
+            if let Some(pick) = pick {
+                picker.toggle(pick, &self.lines)?;
+            }
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if self.pick && matches_keybind(event, &self.keybinds.abort) {
+            self.aborted = true;
+            return Ok(promkit::Signal::Quit);
+        }
+
+        if self.pick && matches_keybind(event, &self.keybinds.toggle_mark) {
+            self.toggle_mark();
+            return Ok(promkit::Signal::Continue);
         }
 
         if matches_keybind(event, &self.keybinds.editor.backward) {
@@ -78,12 +990,106 @@ impl Archived {
         }
 
         if matches_keybind(event, &self.keybinds.up) {
-            self.text.listbox.backward();
+            self.move_cursor_to(self.cursor.saturating_sub(1));
             return Ok(promkit::Signal::Continue);
         }
 
         if matches_keybind(event, &self.keybinds.down) {
-            self.text.listbox.forward();
+            self.move_cursor_to(self.cursor + 1);
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.page_up) {
+            self.move_cursor_to(self.cursor.saturating_sub(page_size.max(1)));
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.page_down) {
+            self.move_cursor_to(self.cursor + page_size.max(1));
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.correlate) {
+            self.correlate()?;
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.toggle_fold) {
+            self.toggle_fold();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.toggle_minimap) {
+            if self.minimap.is_none() {
+                self.minimap = Some(Minimap::try_new(&self.lines)?);
+            }
+            self.show_minimap = !self.show_minimap;
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if self.show_minimap {
+            let per_row = minimap::lines_per_row(self.view_len(), page_size);
+            let row = self.cursor / per_row;
+            if matches_keybind(event, &self.keybinds.minimap_up) {
+                self.move_cursor_to(row.saturating_sub(1) * per_row);
+                return Ok(promkit::Signal::Continue);
+            }
+            if matches_keybind(event, &self.keybinds.minimap_down) {
+                self.move_cursor_to((row + 1) * per_row);
+                return Ok(promkit::Signal::Continue);
+            }
+        }
+
+        if matches_keybind(event, &self.keybinds.goto_head) {
+            self.move_cursor_to(0);
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.goto_tail) {
+            self.move_cursor_to(self.view_len());
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.next_match) {
+            self.goto_next_match();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.prev_match) {
+            self.goto_prev_match();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.toggle_wrap) {
+            self.overflow = self.overflow.toggle();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.scroll_left) {
+            self.overflow = self.overflow.scroll_left();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.scroll_right) {
+            self.overflow = self.overflow.scroll_right();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.toggle_filter) {
+            self.toggle_filter();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.select) {
+            self.toggle_selection();
+            return Ok(promkit::Signal::Continue);
+        }
+
+        if matches_keybind(event, &self.keybinds.copy) {
+            // Keep the session on failure, e.g. when no clipboard is available.
+            if let Err(e) = self.copy() {
+                self.notice = Some(format!("Failed to copy: {e}"));
+            }
             return Ok(promkit::Signal::Continue);
         }
 
@@ -123,44 +1129,58 @@ impl Archived {
 
 #[async_trait]
 impl Prompt for Archived {
+    /// Run the loop of `Prompt`, waking up between the events to show the output of the preview command.
+    async fn run(&mut self) -> anyhow::Result<Self::Return> {
+        self.backend.enable_raw_mode()?;
+        self.backend.hide_cursor()?;
+
+        let result = async {
+            self.initialize().await?;
+            loop {
+                if !self.backend.poll(PREVIEW_POLL_INTERVAL)? {
+                    if let Some(preview) = self.preview.as_mut() {
+                        if preview.poll(&self.lines).await? {
+                            self.render().await?;
+                        }
+                    }
+                    continue;
+                }
+                if self.evaluate(&self.backend.read()?).await? == promkit::Signal::Quit {
+                    break;
+                }
+            }
+            self.finalize()
+        }
+        .await;
+
+        self.backend.show_cursor().ok();
+        self.backend.disable_raw_mode().ok();
+        result
+    }
+
     async fn initialize(&mut self) -> anyhow::Result<()> {
-        Ok(())
+        self.render().await
     }
 
     async fn evaluate(&mut self, event: &Event) -> anyhow::Result<promkit::Signal> {
-        let signal = self.evaluate_internal(event);
-        let (width, height) = crossterm::terminal::size()?;
-
-        let current_query = self.readline.texteditor.text_without_cursor().to_string();
-        if self.prev_query != current_query {
-            // Update listbox items based on the current query
-            self.text.listbox = Listbox::from_styled_graphemes(
-                self.init_text
-                    .items()
-                    .par_iter()
-                    .filter_map(|line| {
-                        highlight(
-                            &current_query,
-                            &line.to_string(),
-                            self.highlight_style,
-                            self.case_insensitive,
-                        )
-                    })
-                    .collect::<Vec<StyledGraphemes>>(),
-            );
-
-            // Update previous query
-            self.prev_query = current_query;
+        self.notice = None;
+        let (width, height) = self.backend.size()?;
+        let page_size = self.text_height(width, height);
+        if matches_keybind(event, &self.keybinds.exec) {
+            if self.pick {
+                if self.view_len() > 0 {
+                    self.picked = Some(self.picked_lines()?);
+                    return Ok(promkit::Signal::Quit);
+                }
+            } else {
+                self.exec().await?;
+            }
         }
+        let signal = self.evaluate_internal(event, page_size);
+        self.follow_query()?;
 
         // TODO: determine whether to render to check cursor was moved or not
-        self.renderer
-            .update([
-                (Index::Readline, self.readline.create_pane(width, height)),
-                (Index::Text, self.text.create_pane(width, height)),
-            ])
-            .render()
-            .await?;
+        self.render().await?;
 
         signal
     }
@@ -172,33 +1192,368 @@ impl Prompt for Archived {
     }
 }
 
+/// How `run` lists the archive.
+pub struct ArchivedOptions {
+    /// Terminal the archive is listed on and the keys are read from.
+    pub backend: Arc<dyn Backend>,
+    pub listbox: listbox::Config,
+    pub selected_item_style: ContentStyle,
+    pub clipboard: Clipboard,
+    pub export_format: ExportFormat,
+    pub highlight_style: ContentStyle,
+    pub highlights: SharedHighlights,
+    pub keybinds: ArchivedKeybinds,
+    pub case_insensitive: bool,
+    /// Whether `retry` goes back to a restarted stream.
+    pub retryable: bool,
+    /// Command run on the line under the cursor, see `--on-select`.
+    pub on_select: Option<String>,
+    /// Command previewing the line under the cursor, see `--preview`.
+    pub preview: Option<String>,
+    /// Pattern of the ID the lines are correlated by, see `--correlation-id`.
+    pub correlation_id: Option<Regex>,
+    pub quiet: bool,
+    pub pick: bool,
+}
+
+/// List the archive until exiting or going back to streaming,
+/// keeping whether long lines are wrapped and unmatched lines shown across the modes.
 pub async fn run(
     readline: text_editor::State,
-    text: listbox::State,
-    highlight_style: ContentStyle,
-    keybinds: ArchivedKeybinds,
-    case_insensitive: bool,
-    cmd: Option<String>,
-) -> anyhow::Result<()> {
-    let (width, height) = crossterm::terminal::size()?;
-    Archived {
-        renderer: Renderer::try_new_with_panes(
-            [
-                (Index::Readline, readline.create_pane(width, height)),
-                (Index::Text, text.create_pane(width, height)),
-            ],
-            true,
+    lines: Archive,
+    options: ArchivedOptions,
+    overflow: &mut Overflow,
+    passthrough: &mut bool,
+) -> anyhow::Result<(Signal, Archive)> {
+    let mut archived = Archived::new(readline, lines, options, *overflow, *passthrough)?;
+    archived.run().await?;
+    *overflow = archived.overflow;
+    *passthrough = !archived.filtered;
+    let signal = match (archived.picked, archived.exit) {
+        (Some(line), _) => Signal::Pick(line),
+        (None, _) if archived.aborted => Signal::Abort,
+        (None, Some(print)) => Signal::Exit {
+            query: archived.prev_query,
+            print,
+        },
+        (None, None) => Signal::GotoStreaming,
+    };
+    Ok((signal, archived.lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        backend::CrosstermBackend,
+        config::{Config, DEFAULT_CONFIG},
+    };
+
+    use super::*;
+
+    /// Rows of the text pane the keys page by.
+    const PAGE: usize = 10;
+
+    fn archived(
+        lines: &[&str],
+        query: &str,
+        options: impl FnOnce(&mut ArchivedOptions),
+    ) -> Archived {
+        let config = Config::load_from(DEFAULT_CONFIG).unwrap();
+        let mut archive = Archive::new(lines.len());
+        for line in lines {
+            archive.push(line.to_string()).unwrap();
+        }
+        let mut archived_options = ArchivedOptions {
+            backend: Arc::new(CrosstermBackend),
+            listbox: config.archived.listbox.clone(),
+            selected_item_style: config.archived.selected_item_style,
+            clipboard: Clipboard::Unavailable,
+            export_format: ExportFormat::default(),
+            highlight_style: config.highlight_style,
+            highlights: Default::default(),
+            keybinds: config.archived.keybinds.clone(),
+            case_insensitive: false,
+            retryable: false,
+            on_select: None,
+            preview: None,
+            correlation_id: None,
+            quiet: false,
+            pick: false,
+        };
+        options(&mut archived_options);
+        let readline = text_editor::State {
+            texteditor: TextEditor::new(query),
+            history: Default::default(),
+            config: config.archived.editor.clone(),
+        };
+        Archived::new(
+            readline,
+            archive,
+            archived_options,
+            Overflow::default(),
+            false,
         )
-        .await?,
-        prev_query: String::new(),
-        readline,
-        init_text: text.listbox.clone(),
-        text,
-        highlight_style,
-        case_insensitive,
-        cmd,
-        keybinds,
-    }
-    .run()
-    .await
+        .unwrap()
+    }
+
+    fn press(archived: &mut Archived, key: fn(&ArchivedKeybinds) -> &HashSet<Event>) {
+        let event = key(&archived.keybinds).iter().next().unwrap().clone();
+        archived.evaluate_internal(&event, PAGE).unwrap();
+        archived.follow_query().unwrap();
+    }
+
+    fn current_line(archived: &Archived) -> usize {
+        archived.line_at(archived.cursor)
+    }
+
+    #[test]
+    fn paging_stays_within_the_view() {
+        let lines = (0..25)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<String>>();
+        let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+        let mut archived = archived(&lines, "", |_| ());
+
+        press(&mut archived, |keys| &keys.page_down);
+        assert_eq!(archived.cursor, 10);
+        // The last page is short, so the cursor stops at the last line.
+        for _ in 0..3 {
+            press(&mut archived, |keys| &keys.page_down);
+        }
+        assert_eq!(archived.cursor, 24);
+        press(&mut archived, |keys| &keys.page_up);
+        assert_eq!(archived.cursor, 14);
+        press(&mut archived, |keys| &keys.goto_head);
+        assert_eq!(archived.cursor, 0);
+        press(&mut archived, |keys| &keys.page_up);
+        assert_eq!(archived.cursor, 0);
+        press(&mut archived, |keys| &keys.goto_tail);
+        assert_eq!(archived.cursor, 24);
+
+        // Nothing to page through in an empty view.
+        archived.update_matches("nothing").unwrap();
+        assert_eq!(archived.view_len(), 0);
+        let keys: [fn(&ArchivedKeybinds) -> &HashSet<Event>; 3] = [
+            |keys| &keys.page_down,
+            |keys| &keys.goto_tail,
+            |keys| &keys.next_match,
+        ];
+        for key in keys {
+            press(&mut archived, key);
+            assert_eq!(archived.cursor, 0);
+        }
+    }
+
+    #[test]
+    fn scrolling_keeps_the_wrapped_cursor_line_in_view() {
+        let long = "x".repeat(25);
+        let lines = (0..1000)
+            .map(|i| if i % 2 == 0 { long.clone() } else { format!("line {i}") })
+            .collect::<Vec<String>>();
+        let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+        let mut archived = archived(&lines, "", |_| ());
+        let rows = |archived: &Archived, position| {
+            let item = archived.styled_item(position, 10).unwrap();
+            layout::rows(&item, 10, PAGE).len()
+        };
+
+        archived.cursor = 999;
+        archived.scroll_into_view(10, PAGE).unwrap();
+        // The long lines wrap into 4 rows, so the last 4 lines fill the 10 rows.
+        assert_eq!(archived.offset, 996);
+        let shown = (996..=999).map(|p| rows(&archived, p)).sum::<usize>();
+        assert_eq!(shown, PAGE);
+
+        // A cursor in view keeps the offset, and one above it becomes the offset.
+        archived.cursor = 997;
+        archived.scroll_into_view(10, PAGE).unwrap();
+        assert_eq!(archived.offset, 996);
+        archived.cursor = 10;
+        archived.scroll_into_view(10, PAGE).unwrap();
+        assert_eq!(archived.offset, 10);
+    }
+
+    #[test]
+    fn jumps_between_matches_stop_at_the_first_and_last() {
+        let lines = [
+            "start", "ok", "ERROR a", "ok", "ok", "ERROR b", "ok", "ok", "ok", "ERROR c",
+        ];
+        let mut archived = archived(&lines, "ERROR", |_| ());
+        // Jump over the unmatched lines of the whole archive.
+        press(&mut archived, |keys| &keys.toggle_filter);
+        assert_eq!(archived.view_len(), 10);
+
+        assert_eq!(current_line(&archived), 2);
+
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            press(&mut archived, |keys| &keys.next_match);
+            visited.push(current_line(&archived));
+        }
+        // No wrap-around past the last match.
+        assert_eq!(visited, [5, 9, 9]);
+        press(&mut archived, |keys| &keys.prev_match);
+        assert_eq!(current_line(&archived), 5);
+        press(&mut archived, |keys| &keys.goto_head);
+        press(&mut archived, |keys| &keys.prev_match);
+        assert_eq!(current_line(&archived), 0);
+    }
+
+    #[test]
+    fn toggling_the_filter_keeps_the_line_under_the_cursor() {
+        let lines = ["ERROR a", "ok", "ERROR b", "ok", "ERROR c"];
+        let mut archived = archived(&lines, "ERROR", |_| ());
+        assert_eq!(archived.view_len(), 3);
+        press(&mut archived, |keys| &keys.down);
+        assert_eq!(current_line(&archived), 2);
+
+        press(&mut archived, |keys| &keys.toggle_filter);
+        assert_eq!(archived.view_len(), 5);
+        assert_eq!(current_line(&archived), 2);
+        // From an unmatched line, the filtered view goes on to the next match.
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.toggle_filter);
+        assert_eq!(current_line(&archived), 4);
+
+        archived.update_matches("nothing").unwrap();
+        press(&mut archived, |keys| &keys.toggle_filter);
+        press(&mut archived, |keys| &keys.toggle_filter);
+        assert_eq!(archived.cursor, 0);
+    }
+
+    #[test]
+    fn picked_lines_is_the_line_under_the_cursor_without_marks() {
+        let lines = ["ERROR a", "ok", "ERROR b", "ok", "ERROR c"];
+        let mut whole = archived(&lines, "", |options| options.pick = true);
+        press(&mut whole, |keys| &keys.down);
+        assert_eq!(whole.picked_lines().unwrap(), ["ok"]);
+
+        // In the filtered view, the cursor is on the second match rather than the second line.
+        let mut filtered = archived(&lines, "ERROR", |options| options.pick = true);
+        press(&mut filtered, |keys| &keys.down);
+        assert_eq!(filtered.picked_lines().unwrap(), ["ERROR b"]);
+        // Back in the whole archive, the cursor stays on that line and moves on from it.
+        press(&mut filtered, |keys| &keys.toggle_filter);
+        press(&mut filtered, |keys| &keys.down);
+        assert_eq!(filtered.picked_lines().unwrap(), ["ok"]);
+    }
+
+    #[test]
+    fn picked_lines_are_the_marked_ones_in_archive_order() {
+        let lines = ["ERROR a", "ok", "ERROR b", "ok", "ERROR c"];
+        let mut archived = archived(&lines, "ERROR", |options| options.pick = true);
+        // Marking moves on to the next line, so "ERROR b" is skipped.
+        press(&mut archived, |keys| &keys.toggle_mark);
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.toggle_mark);
+        assert_eq!(archived.picked_lines().unwrap(), ["ERROR a", "ERROR c"]);
+
+        // The marks outlive the filter, and a line marked later is picked in archive order.
+        press(&mut archived, |keys| &keys.toggle_filter);
+        press(&mut archived, |keys| &keys.up);
+        assert_eq!(current_line(&archived), 3);
+        press(&mut archived, |keys| &keys.toggle_mark);
+        assert_eq!(
+            archived.picked_lines().unwrap(),
+            ["ERROR a", "ok", "ERROR c"]
+        );
+
+        // Marking a line again unmarks it, and without marks the cursor line is picked.
+        for line in [0, 3, 4] {
+            archived.move_cursor_to(line);
+            press(&mut archived, |keys| &keys.toggle_mark);
+        }
+        archived.move_cursor_to(1);
+        assert_eq!(archived.picked_lines().unwrap(), ["ok"]);
+    }
+
+    #[test]
+    fn copy_writes_the_selected_range_or_tells_why_it_failed() {
+        let path = std::env::temp_dir().join(format!("sig-archived-copy-{}", std::process::id()));
+        let lines = ["first", "second", "third", "fourth"];
+        let mut archived = archived(&lines, "", |options| {
+            options.clipboard = Clipboard::Command(format!("tee {}", path.display()));
+        });
+
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.select);
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.down);
+        assert_eq!(archived.selected_positions(), Some(1..4));
+        press(&mut archived, |keys| &keys.copy);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "second\nthird\nfourth"
+        );
+        assert_eq!(archived.selection, None);
+
+        // Without a selection, the line under the cursor is copied.
+        press(&mut archived, |keys| &keys.goto_head);
+        press(&mut archived, |keys| &keys.copy);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+        std::fs::remove_file(&path).unwrap();
+
+        archived.clipboard = Clipboard::Unavailable;
+        press(&mut archived, |keys| &keys.copy);
+        assert!(archived
+            .notice
+            .as_deref()
+            .is_some_and(|notice| notice.starts_with("Failed to copy: ")));
+    }
+
+    #[test]
+    fn correlate_filters_by_the_id_and_goes_back() {
+        let lines = [
+            "id=req1 start",
+            "id=req2 start",
+            "no id",
+            "id=req1 end",
+            "id=x|y odd",
+        ];
+        let mut archived = archived(&lines, "", |options| {
+            options.correlation_id = Some(Regex::new(r"id=(\S+)").unwrap());
+        });
+        // Shown unfiltered, as from streaming with passthrough.
+        press(&mut archived, |keys| &keys.toggle_filter);
+        press(&mut archived, |keys| &keys.goto_tail);
+
+        press(&mut archived, |keys| &keys.correlate);
+        assert!(archived.notice.is_some());
+        assert_eq!(archived.correlated_from, None);
+
+        press(&mut archived, |keys| &keys.goto_head);
+        archived.notice = None;
+        press(&mut archived, |keys| &keys.correlate);
+        assert_eq!(archived.view(), Some(&[0, 3][..]));
+        assert_eq!(current_line(&archived), 0);
+        assert_eq!(archived.prev_query, "req1");
+
+        press(&mut archived, |keys| &keys.correlate);
+        assert_eq!(
+            archived
+                .readline
+                .texteditor
+                .text_without_cursor()
+                .to_string(),
+            ""
+        );
+        assert_eq!(archived.view(), None);
+
+        // A line without an ID is left alone.
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.down);
+        press(&mut archived, |keys| &keys.correlate);
+        assert_eq!(archived.correlated_from, None);
+        assert_eq!(archived.notice, None);
+
+        // Nor is there a line to take the ID from in an empty view.
+        archived.readline.texteditor = TextEditor::new("nothing");
+        archived.follow_query().unwrap();
+        press(&mut archived, |keys| &keys.toggle_filter);
+        assert_eq!(archived.view_len(), 0);
+        press(&mut archived, |keys| &keys.correlate);
+        assert_eq!(archived.correlated_from, None);
+    }
 }
@@ -0,0 +1,76 @@
+#[cfg(unix)]
+use std::{
+    fs::Permissions,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use serde_json::json;
+
+use crate::{crypt::Cipher, timestamp};
+
+/// The audit file, shared by every mode since actions are recorded all over the UI.
+static LOG: OnceLock<AuditLog> = OnceLock::new();
+
+struct AuditLog {
+    file: Mutex<File>,
+    user: String,
+    cipher: Option<Cipher>,
+}
+
+/// Start recording the user actions in the file at `path`, appending to it,
+/// encrypted with `cipher` if any.
+pub fn open(path: &Path, cipher: Option<Cipher>) -> anyhow::Result<()> {
+    // Created readable by the owner only, as queries and copied lines may be sensitive.
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open audit log {}: {e}", path.display()))?;
+    // The mode only applies to a file created here; an existing one is restricted too.
+    #[cfg(unix)]
+    if file.metadata()?.is_file() {
+        file.set_permissions(Permissions::from_mode(0o600))
+            .map_err(|e| anyhow::anyhow!("Failed to restrict audit log {}: {e}", path.display()))?;
+    }
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_default();
+    LOG.set(AuditLog {
+        file: Mutex::new(file),
+        user,
+        cipher,
+    })
+    .map_err(|_| anyhow::anyhow!("The audit log is already open"))
+}
+
+/// Record an action as a JSON line, e.g.
+/// `{"time":"2024-05-01T12:34:56.789012Z","user":"alice","mode":"streaming","action":"query","detail":"ERROR"}`.
+/// Does nothing unless the audit log is open.
+pub fn record(mode: &str, action: &str, detail: &str) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+    let entry = json!({
+        "time": timestamp::rfc3339(SystemTime::now()),
+        "user": log.user,
+        "mode": mode,
+        "action": action,
+        "detail": detail,
+    });
+    let entry = match &log.cipher {
+        Some(cipher) => cipher.encrypt(&entry.to_string()),
+        None => entry.to_string(),
+    };
+    let mut file = log.file.lock().expect("audit log lock poisoned");
+    // Written at once so that concurrent sig processes auditing to the same file do not interleave.
+    let _ = file.write_all(format!("{entry}\n").as_bytes());
+}
@@ -0,0 +1,124 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use promkit_core::{
+    crossterm::{
+        self, cursor,
+        event::{self, Event},
+        style, terminal,
+    },
+    grapheme::StyledGraphemes,
+};
+
+/// The primitives the streaming, archived and dashboard views are drawn with and read keys from.
+///
+/// The positions are 0-based, except for the scroll region which follows the 1-based DECSTBM.
+/// Implementing this for a web terminal (e.g. xterm.js over wasm-bindgen)
+/// lets the views be rendered outside of a tty.
+pub trait Backend: Send + Sync {
+    /// Columns and rows of the terminal.
+    fn size(&self) -> io::Result<(u16, u16)>;
+    /// Wait up to `timeout` for an event, returning whether one is ready to `read`.
+    fn poll(&self, timeout: Duration) -> io::Result<bool>;
+    /// The next event, waiting for one if none is ready.
+    fn read(&self) -> io::Result<Event>;
+    fn enable_raw_mode(&self) -> io::Result<()>;
+    fn disable_raw_mode(&self) -> io::Result<()>;
+    fn show_cursor(&self) -> io::Result<()>;
+    fn hide_cursor(&self) -> io::Result<()>;
+    /// Clear the entire terminal, moving the cursor to the top left.
+    fn clear(&self) -> io::Result<()>;
+    fn move_to(&self, x: u16, y: u16) -> io::Result<()>;
+    fn clear_line(&self) -> io::Result<()>;
+    fn print(&self, row: &StyledGraphemes) -> io::Result<()>;
+    /// Scroll the rows of the scroll region up, leaving blank rows at the bottom.
+    fn scroll_up(&self, rows: u16) -> io::Result<()>;
+    /// Scroll the rows of the scroll region down, leaving blank rows at the top.
+    fn scroll_down(&self, rows: u16) -> io::Result<()>;
+    /// Restrict scrolling to the rows [top, bottom], where both are 1-based.
+    fn set_scroll_region(&self, top: u16, bottom: u16) -> io::Result<()>;
+    /// Reset the scroll region to the entire terminal.
+    fn reset_scroll_region(&self) -> io::Result<()>;
+    fn flush(&self) -> io::Result<()>;
+}
+
+/// Backend queueing ANSI sequences to stdout with crossterm.
+pub struct CrosstermBackend;
+
+impl Backend for CrosstermBackend {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn poll(&self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&self) -> io::Result<Event> {
+        event::read()
+    }
+
+    fn enable_raw_mode(&self) -> io::Result<()> {
+        terminal::enable_raw_mode()
+    }
+
+    fn disable_raw_mode(&self) -> io::Result<()> {
+        terminal::disable_raw_mode()
+    }
+
+    fn show_cursor(&self) -> io::Result<()> {
+        crossterm::execute!(io::stdout(), cursor::Show)
+    }
+
+    fn hide_cursor(&self) -> io::Result<()> {
+        crossterm::execute!(io::stdout(), cursor::Hide)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        crossterm::execute!(
+            io::stdout(),
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+        )
+    }
+
+    fn move_to(&self, x: u16, y: u16) -> io::Result<()> {
+        crossterm::queue!(io::stdout(), cursor::MoveTo(x, y))
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        crossterm::queue!(
+            io::stdout(),
+            terminal::Clear(terminal::ClearType::CurrentLine)
+        )
+    }
+
+    fn print(&self, row: &StyledGraphemes) -> io::Result<()> {
+        crossterm::queue!(io::stdout(), style::Print(row.styled_display()))
+    }
+
+    fn scroll_up(&self, rows: u16) -> io::Result<()> {
+        crossterm::queue!(io::stdout(), terminal::ScrollUp(rows))
+    }
+
+    fn scroll_down(&self, rows: u16) -> io::Result<()> {
+        crossterm::queue!(io::stdout(), terminal::ScrollDown(rows))
+    }
+
+    fn set_scroll_region(&self, top: u16, bottom: u16) -> io::Result<()> {
+        crossterm::queue!(
+            io::stdout(),
+            style::Print(format!(crossterm::csi!("{};{}r"), top, bottom))
+        )
+    }
+
+    fn reset_scroll_region(&self) -> io::Result<()> {
+        crossterm::queue!(io::stdout(), style::Print(crossterm::csi!("r")))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
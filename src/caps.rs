@@ -0,0 +1,191 @@
+//! What the terminal is able to render, so that sig does not emit sequences
+//! a terminal would print as garbage instead.
+
+use std::sync::OnceLock;
+
+use promkit_core::crossterm::style::{Color, ContentStyle};
+
+/// The capabilities of the terminal sig is run in, see `init`.
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 24-bit colors, otherwise RGB colors are drawn with the nearest of the 256 colors.
+    pub truecolor: bool,
+    /// Setting the clipboard with OSC 52.
+    pub osc52: bool,
+    /// Alternate scroll mode, to scroll the archived mode with the mouse wheel.
+    pub mouse: bool,
+    /// The alternate screen, which keeps the scrollback of the shell as it was.
+    pub alt_screen: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            truecolor: true,
+            osc52: true,
+            mouse: true,
+            alt_screen: true,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Guess the capabilities from the environment variables terminals set:
+    /// `TERM`, `COLORTERM`, `TERM_PROGRAM` and the ones of terminal multiplexers.
+    pub fn detect(env: impl Fn(&str) -> Option<String>) -> Self {
+        let term = env("TERM").unwrap_or_default();
+        let program = env("TERM_PROGRAM").unwrap_or_default();
+        // Consoles and hardware terminals, which know none of the xterm extensions
+        let legacy = term.is_empty()
+            || term.starts_with("vt")
+            || ["dumb", "linux", "ansi", "cons25"].contains(&term.as_str());
+
+        let truecolor = matches!(env("COLORTERM").as_deref(), Some("truecolor" | "24bit"))
+            || term.ends_with("-direct")
+            || ["iTerm.app", "WezTerm", "vscode", "ghostty"].contains(&program.as_str())
+            || env("KITTY_WINDOW_ID").is_some()
+            || env("WT_SESSION").is_some();
+        // GNU screen swallows OSC 52 unless it runs in tmux, as does Terminal.app.
+        let osc52 = !legacy
+            && program != "Apple_Terminal"
+            && !(term.starts_with("screen") && env("TMUX").is_none());
+        Self {
+            truecolor: truecolor && !legacy,
+            osc52,
+            mouse: !legacy,
+            alt_screen: !legacy,
+        }
+    }
+
+    /// Turn the capabilities on or off as given to `--caps`, e.g. `no-mouse` or `truecolor`.
+    pub fn apply_overrides(&mut self, overrides: &[String]) -> anyhow::Result<()> {
+        for name in overrides {
+            let (value, cap) = match name.strip_prefix("no-") {
+                Some(cap) => (false, cap),
+                None => (true, name.as_str()),
+            };
+            match cap {
+                "truecolor" => self.truecolor = value,
+                "osc52" => self.osc52 = value,
+                "mouse" => self.mouse = value,
+                "alt-screen" => self.alt_screen = value,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown terminal capability {name:?}, expected one of \
+                         truecolor, osc52, mouse, alt-screen, optionally prefixed with no-"
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The capabilities as `--caps` takes them, e.g. `no-truecolor,osc52,mouse,alt-screen`.
+    pub fn summary(&self) -> String {
+        [
+            (self.truecolor, "truecolor"),
+            (self.osc52, "osc52"),
+            (self.mouse, "mouse"),
+            (self.alt_screen, "alt-screen"),
+        ]
+        .iter()
+        .map(|(on, cap)| {
+            if *on {
+                cap.to_string()
+            } else {
+                format!("no-{cap}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Set the capabilities used from then on, once at startup.
+pub fn init(capabilities: Capabilities) {
+    let _ = CAPABILITIES.set(capabilities);
+}
+
+/// The capabilities given to `init`, or all of them if sig is embedded without calling it.
+pub fn get() -> Capabilities {
+    CAPABILITIES.get().copied().unwrap_or_default()
+}
+
+/// The nearest of the 256 colors: the 6x6x6 cube or the gray ramp.
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..8 => 16,
+            249.. => 231,
+            // The ramp goes from 8 to 238 by 10.
+            _ => 232 + ((r - 8 + 5) / 10).min(23),
+        };
+    }
+    let level = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// The style with its RGB colors replaced by the nearest 256 colors
+/// if the terminal has no truecolor.
+pub fn fit_style(style: ContentStyle) -> ContentStyle {
+    if get().truecolor {
+        return style;
+    }
+    let fit = |color: Option<Color>| match color {
+        Some(Color::Rgb { r, g, b }) => Some(Color::AnsiValue(ansi256(r, g, b))),
+        color => color,
+    };
+    ContentStyle {
+        foreground_color: fit(style.foreground_color),
+        background_color: fit(style.background_color),
+        underline_color: fit(style.underline_color),
+        attributes: style.attributes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_guesses_from_the_environment() {
+        let detect = |vars: &[(&str, &str)]| {
+            Capabilities::detect(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]),
+            Capabilities::default()
+        );
+        assert_eq!(
+            detect(&[("TERM", "screen-256color")]).summary(),
+            "no-truecolor,no-osc52,mouse,alt-screen"
+        );
+        assert_eq!(
+            detect(&[
+                ("TERM", "screen-256color"),
+                ("TMUX", "/tmp/tmux-1000/default,1,0")
+            ])
+            .summary(),
+            "no-truecolor,osc52,mouse,alt-screen"
+        );
+        assert_eq!(
+            detect(&[("TERM", "linux"), ("COLORTERM", "truecolor")]).summary(),
+            "no-truecolor,no-osc52,no-mouse,no-alt-screen"
+        );
+
+        let mut caps = detect(&[("TERM", "linux")]);
+        caps.apply_overrides(&[String::from("mouse"), String::from("no-osc52")])
+            .unwrap();
+        assert!(caps.mouse && !caps.osc52);
+        assert!(caps.apply_overrides(&[String::from("sixel")]).is_err());
+
+        assert_eq!(ansi256(255, 0, 0), 196);
+        assert_eq!(ansi256(128, 128, 128), 244);
+    }
+}
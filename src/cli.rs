@@ -0,0 +1,1126 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use tokio::{sync::RwLock, time::Duration};
+
+use promkit_core::crossterm::style::ContentStyle;
+use promkit_widgets::text_editor::{self, TextEditor};
+
+use crate::{
+    alert::Alerts,
+    archive::Archive,
+    archived::{self, ArchivedOptions},
+    audit,
+    backend::{Backend, CrosstermBackend},
+    caps::{self, Capabilities},
+    clipboard::Clipboard,
+    config::{Config, DEFAULT_CONFIG},
+    crypt::Cipher,
+    dashboard, enter_terminal,
+    export::{self, ExportFormat},
+    folded::FoldedStacks,
+    highlight::{Highlights, SharedHighlights},
+    incident::Incident,
+    layout::Overflow,
+    leave_terminal,
+    mask::Mask,
+    pipeline::{Pipeline, SharedPipeline},
+    sink::Sinks,
+    slots::QuerySlots,
+    source::{self, Source},
+    spawn::{DropPolicy, FilterMode, Records},
+    streaming::{self, Ranking, StreamingOptions},
+    words::WordCounts,
+    SessionOptions, Signal,
+};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Collect lines without a terminal, serving them to `sig attach` clients.
+    #[cfg(all(unix, feature = "server"))]
+    Serve {
+        /// Path of the Unix socket to listen on.
+        socket: PathBuf,
+    },
+    /// View the lines collected by `sig serve`, with a query of its own.
+    #[cfg(all(unix, feature = "server"))]
+    Attach {
+        /// Path of the Unix socket the collector listens on.
+        socket: PathBuf,
+        /// Watch with the query and the pipeline stages of the collector,
+        /// without being able to change them, to retry or to run commands.
+        ///
+        /// This is enforced by this client only: it still runs its own --filter-cmd
+        /// and sinks, the archive can still be searched, and anyone who can open
+        /// the socket can attach without --read-only.
+        #[arg(long = "read-only")]
+        read_only: bool,
+    },
+    /// Print the lines of files written with --encrypt, decrypted with the same key.
+    #[cfg(feature = "encryption")]
+    Decrypt {
+        /// Files to decrypt (stdin if none).
+        files: Vec<PathBuf>,
+    },
+    /// Pick a line inside `tmux display-popup`, printing it to stdout.
+    ///
+    /// The layout is compact (see --quiet) and lines are picked as with --pick.
+    TmuxPopup,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Output {
+    /// Print nothing.
+    #[default]
+    None,
+    /// Print the archived lines matching the last query.
+    Matches,
+    /// Print all the archived lines.
+    All,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Preset {
+    /// Rank folded stacks by their samples.
+    Folded,
+    /// Rank the words of the matched lines.
+    Words,
+}
+
+/// Interactive grep (for streaming)
+#[derive(Parser)]
+#[command(
+    name = "sig",
+    version,
+    help_template = "
+{about}
+
+Usage: {usage}
+
+Examples:
+
+$ stern --context kind-kind etcd |& sig
+Or the method to retry command by pressing ctrl+r:
+$ sig --cmd \"stern --context kind-kind etcd\"
+
+Static input (switches to archived view after EOF):
+$ cat README.md |& sig
+
+Headless collector and clients viewing it with their own queries:
+$ sig --query ERROR --cmd \"stern --context kind-kind etcd\" serve /tmp/sig.sock
+$ sig attach /tmp/sig.sock
+$ sig attach --read-only /tmp/sig.sock
+
+Commands:
+{subcommands}
+
+Options:
+{options}
+"
+)]
+pub struct Args {
+    #[arg(
+        long = "retrieval-timeout",
+        default_value = "10",
+        help = "Timeout to read a next line from the stream in milliseconds."
+    )]
+    pub retrieval_timeout_millis: u64,
+
+    #[arg(
+        long = "sink-drain-timeout",
+        default_value = "5000",
+        help = "Time given to the sinks to deliver their last lines on exit in milliseconds.",
+        long_help = "Time given to the sinks to deliver their last lines on exit in milliseconds.
+        The sinks still busy after it are stopped, and the number of lines they could not
+        deliver is reported."
+    )]
+    pub sink_drain_timeout_millis: u64,
+
+    #[arg(
+        long = "render-interval",
+        default_value = None,
+        help = "Interval to render a line in milliseconds.",
+        long_help = "Adjust this value to prevent screen flickering
+        when a large volume of lines is rendered in a short period."
+    )]
+    pub render_interval_millis: Option<u64>,
+
+    #[arg(
+        short = 'q',
+        long = "queue-capacity",
+        default_value = "1000",
+        help = "Queue capacity to store lines.",
+        long_help = "Queue capacity for storing lines.
+        This value is used for temporary storage of lines
+        and should be adjusted based on the system's memory capacity.
+        Increasing this value allows for more lines to be stored temporarily,
+        which can be beneficial when digging deeper into lines with the digger."
+    )]
+    pub queue_capacity: usize,
+
+    #[arg(
+        long = "channel-capacity",
+        default_value = "1",
+        help = "Number of lines buffered from the source ahead of rendering.",
+        long_help = "Lines read from the source wait in this buffer
+        while the rest of sig is busy (e.g. with --render-interval).
+        What happens once it is full is decided by --drop-policy."
+    )]
+    pub channel_capacity: usize,
+
+    #[arg(
+        long = "rate",
+        value_parser = parse_rate,
+        help = "Read at most this many lines per second from the source.",
+        long_help = "The source is slowed down as a producer of this speed would be,
+        e.g. to replay a log file piped to sig at a steady pace for a demo
+        or to reproduce a stream the view cannot keep up with."
+    )]
+    pub rate: Option<f64>,
+
+    #[arg(
+        long = "idle-dim",
+        value_name = "SECONDS",
+        help = "Dim the stream after this many seconds without a new match.",
+        long_help = "A banner tells how long ago the last line matched,
+        so that a wall display shows at a glance that the stream went quiet.
+        The stream is restored as soon as a line matches or a key is pressed."
+    )]
+    pub idle_dim_secs: Option<u64>,
+
+    #[arg(
+        long = "caps",
+        value_name = "CAPS",
+        value_delimiter = ',',
+        help = "Override the detected terminal capabilities, e.g. --caps no-mouse,truecolor.",
+        long_help = "The capabilities are guessed from TERM, COLORTERM and TERM_PROGRAM:
+        truecolor (otherwise RGB colors are drawn with the nearest of 256 colors),
+        osc52 (otherwise the clipboard is set with pbcopy, wl-copy, xclip or xsel),
+        mouse (wheel scrolling in archived mode) and alt-screen.
+        Prefix a capability with no- to turn it off. The result is recorded in the --audit-log."
+    )]
+    pub caps: Vec<String>,
+
+    #[arg(
+        long = "demo-mask",
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        help = "Fake the values of these JSON/logfmt fields, e.g. --demo-mask ip,user_id.",
+        long_help = "The values are replaced as soon as the lines are read, keeping their shape
+        (IPv4 addresses stay addresses, in 10.0.0.0/8), so that sig can be used
+        in public demos and screen shares against real streams.
+        A value is faked the same way throughout the session,
+        so that the lines sharing it can still be followed."
+    )]
+    pub demo_mask: Vec<String>,
+
+    #[arg(
+        long = "sequence-field",
+        help = "JSON/logfmt field carrying a sequence number, to flag dropped or repeated messages.",
+        long_help = "The field is expected to increase by one from line to line.
+        A marker is drawn in the stream where numbers were skipped
+        or did not increase, and the status bar counts them.
+        e.g. --sequence-field offset"
+    )]
+    pub sequence_field: Option<String>,
+
+    #[arg(
+        long = "drop-policy",
+        value_enum,
+        default_value_t = DropPolicy::Block,
+        help = "What to do with new lines while the buffer of --channel-capacity is full.",
+        long_help = "block: stop reading the source until there is room, slowing down the producer.
+        drop-oldest / drop-newest: keep reading, dropping the oldest buffered or the new line.
+        Dropped lines are counted in the hint and in archived mode."
+    )]
+    pub drop_policy: DropPolicy,
+
+    #[arg(
+        long = "spill-file",
+        help = "File to spill lines evicted from the queue into.",
+        long_help = "Lines exceeding the queue capacity are appended to this file
+        instead of being dropped, so that archived mode can search
+        the whole session while memory usage stays bounded.
+        The file is truncated whenever the stream (re)starts,
+        and is readable by its owner only."
+    )]
+    pub spill_file: Option<PathBuf>,
+
+    #[arg(
+        short = 'i',
+        long = "ignore-case",
+        default_value = "false",
+        help = "Case insensitive search."
+    )]
+    pub case_insensitive: bool,
+
+    #[arg(
+        long = "cmd",
+        help = "Command to execute on initial and retries.",
+        long_help = "This command is invoked initially and
+        whenever a retry is triggered according to key mappings."
+    )]
+    pub cmd: Option<String>,
+
+    #[arg(
+        long = "filter-cmd",
+        help = "Command to stream every line through.",
+        long_help = "This command is started once and receives the lines on stdin,
+        and its output is displayed instead of (or after) the original lines.
+        Make sure the command does not buffer its output,
+        e.g. --filter-cmd 'jq -c --unbuffered .msg'"
+    )]
+    pub filter_cmd: Option<String>,
+
+    #[arg(
+        long = "filter-mode",
+        value_enum,
+        default_value_t = FilterMode::Replace,
+        help = "How the output of --filter-cmd is displayed."
+    )]
+    pub filter_mode: FilterMode,
+
+    #[arg(
+        long = "multiline-start",
+        help = "Regex matching the first line of a multi-line record.",
+        long_help = "Lines are grouped into records, each starting at a line matching this regex
+        (e.g. a timestamp prefix), and the query is applied to whole records,
+        so that a match on an exception message brings the entire stack trace along.
+        e.g. --multiline-start '^\\d{4}-\\d{2}-\\d{2}'"
+    )]
+    pub multiline_start: Option<Regex>,
+
+    #[arg(
+        long = "xml-record",
+        value_name = "TAG",
+        conflicts_with = "multiline_start",
+        help = "Tag of the XML elements to reassemble into single records.",
+        long_help = "The lines from a start tag to its end tag are joined into a single record,
+        e.g. --xml-record event for systems logging multi-line <event> elements.
+        Their fields are extracted as XPath-lite paths relative to the element:
+        @id for an attribute, user/name for the text of a child element
+        and user/@id for an attribute of a child element."
+    )]
+    pub xml_record: Option<String>,
+
+    #[arg(
+        long = "multiline-timeout",
+        default_value = "200",
+        help = "Timeout to flush a pending multi-line record in milliseconds."
+    )]
+    pub multiline_timeout_millis: u64,
+
+    #[arg(
+        long = "split",
+        default_value = "false",
+        help = "Start with the split view of all lines (left) and matched lines (right)."
+    )]
+    pub split: bool,
+
+    #[arg(
+        long = "reverse",
+        default_value = "false",
+        help = "Show the newest line at the top of the stream, just below the query editor."
+    )]
+    pub reverse: bool,
+
+    #[arg(
+        long = "gutter",
+        default_value = "false",
+        help = "Flag each line in a gutter ahead of it: matched (•) or feeding a firing alert (!)."
+    )]
+    pub gutter: bool,
+
+    #[arg(
+        long = "quiet",
+        default_value = "false",
+        help = "Show only the query line and the stream, without the status bar and alert banners."
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long = "pick",
+        default_value = "false",
+        help = "Print the line picked with Enter to stdout and exit (Esc exits with status 1).",
+        long_help = "Enter picks the last line while streaming,
+        or the line under the cursor in Archived mode,
+        printing it to stdout and exiting with status 0.
+        Esc exits with status 1 without printing anything."
+    )]
+    pub pick: bool,
+
+    #[arg(
+        long = "preset",
+        value_enum,
+        help = "Preset for a well-known kind of stream.",
+        long_help = "folded: lines are folded stacks (e.g. `perf script | stackcollapse-perf.pl`),
+        whose samples are aggregated per stack and ranked in a live table
+        of the top stacks matching the query.
+        words: the words of the last matched lines (see --word-window)
+        are ranked in a live table by the lines they occur in,
+        to find what else co-occurs with what the query matches."
+    )]
+    pub preset: Option<Preset>,
+
+    #[arg(
+        long = "top",
+        default_value = "10",
+        help = "Number of rows in the table of --preset."
+    )]
+    pub top: usize,
+
+    #[arg(
+        long = "word-window",
+        default_value = "1000",
+        help = "Number of the last matched lines whose words are ranked with --preset words."
+    )]
+    pub word_window: usize,
+
+    #[arg(
+        long = "passthrough",
+        default_value = "false",
+        help = "Show the unmatched lines too, highlighting the matches.",
+        long_help = "All lines keep flowing, like `grep --color=always` but with an editable query,
+        instead of only the lines matching the query.
+        This can also be toggled while streaming (Ctrl+T by default)."
+    )]
+    pub passthrough: bool,
+
+    #[arg(
+        long = "archived",
+        default_value = "false",
+        help = "Read the source without showing the stream, then enter archived mode.",
+        long_help = "Useful with short-lived --cmd invocations, whose output is browsed
+        and searched (with --query applied) once the command finishes.
+        Press the goto_archived keys (Ctrl+F by default) to stop reading earlier."
+    )]
+    pub archived: bool,
+
+    #[arg(
+        long = "plain",
+        default_value = "false",
+        conflicts_with_all = ["archived", "dashboard"],
+        help = "Print the matching lines with [[ ]] around the matches, without the UI.",
+        long_help = "The lines matching --query are printed to stdout as they come,
+        for environments the UI cannot be drawn in.
+        This is the default when TERM=dumb or there is no terminal, e.g. in some CI shells,
+        where --archived, --dashboard and --pick fail instead."
+    )]
+    pub plain: bool,
+
+    #[arg(
+        long = "dashboard",
+        default_value = "false",
+        conflicts_with = "archived",
+        help = "Show the [dashboard] of the configuration instead of the stream.",
+        long_help = "For dedicated monitoring screens: the counters, the alert states
+        and the last lines matching the query of the [dashboard] profile are shown
+        in large print, without a query editor.
+        Press the goto_archived keys (Ctrl+F by default) to browse the lines read."
+    )]
+    pub dashboard: bool,
+
+    #[arg(
+        short = 'Q',
+        long = "query",
+        help = "Initial query.",
+        long_help = "This query is set as the initial text
+        in the text editor when the program starts."
+    )]
+    pub query: Option<String>,
+
+    #[arg(short = 'c', long = "config", help = "Path to the configuration file.")]
+    pub config_file: Option<PathBuf>,
+
+    #[arg(
+        long = "output",
+        value_enum,
+        default_value_t = Output::None,
+        help = "Lines to print to stdout on exit.",
+        long_help = "The lines are printed after leaving the alternate screen,
+        so that sig can sit in the middle of a pipeline, e.g. `cmd | sig --output matches | less`,
+        in which case the TUI is drawn on the terminal instead of stdout.
+        Exiting with the exit_and_print keys (Ctrl+X by default) prints the matches
+        even with --output none."
+    )]
+    pub output: Output,
+
+    #[arg(
+        long = "output-format",
+        value_enum,
+        default_value_t = ExportFormat::Text,
+        help = "Format of the lines printed by --output and copied to the clipboard.",
+        long_help = "jsonl: one JSON object per line, e.g.
+        {\"line\":\"GET /api 503\",\"matches\":[{\"start\":9,\"end\":12,\"text\":\"503\"}]},
+        where the matches are the byte offsets and text of what the query highlighted."
+    )]
+    pub output_format: ExportFormat,
+
+    #[arg(
+        long = "audit-log",
+        help = "File to record the user actions in.",
+        long_help = "Query changes, stage toggles, pauses, retries, copies and
+        --on-select runs are appended to this file with a timestamp and $USER,
+        one JSON object per line."
+    )]
+    pub audit_log: Option<PathBuf>,
+
+    #[cfg(feature = "encryption")]
+    #[arg(
+        long = "encrypt",
+        default_value = "false",
+        help = "Encrypt the lines written to the spill file, route files, audit log and --output.",
+        long_help = "Each line is sealed with AES-256-GCM and written as a line of base64.
+        The key, 32 bytes as 64 hex digits or in base64, is read from $SIG_ENCRYPTION_KEY,
+        or else printed by encryption_key_command of the configuration
+        (e.g. `secret-tool lookup service sig` to keep it in the keyring).
+        The spill file, which only this process reads back, is sealed with a key of its own.
+        Read the other files with `sig decrypt`."
+    )]
+    pub encrypt: bool,
+
+    #[arg(
+        long = "on-select",
+        help = "Command to run for the selected line.",
+        long_help = "This command is run with `sh -c` when the selected line is executed
+        in archived mode (the line under the cursor)
+        or while streaming is paused (the last rendered line).
+        `{}` in the command is replaced by the shell-quoted line.
+        e.g. --on-select 'echo {} | pbcopy'"
+    )]
+    pub on_select: Option<String>,
+
+    #[arg(
+        long = "preview",
+        help = "Command to preview the line under the cursor in Archived mode.",
+        long_help = "This command is run with `sh -c` once the cursor stays on a line
+        in archived mode, and its output is shown on the right.
+        `{}` in the command is replaced by the shell-quoted line.
+        e.g. --preview 'echo {} | jq .'"
+    )]
+    pub preview: Option<String>,
+
+    #[arg(
+        long = "correlation-id",
+        help = "Regex capturing the ID shared by the lines of a request, e.g. 'req=(\\w+)'.",
+        long_help = "In archived mode, the `correlate` key filters the archive
+        to the lines containing the ID captured from the line under the cursor
+        (by the first capture group, or the whole match without one),
+        to follow a request through interleaved logs.
+        e.g. --correlation-id 'trace_id=([0-9a-f]+)'"
+    )]
+    pub correlation_id: Option<Regex>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Drop for Args {
+    fn drop(&mut self) {
+        let _ = leave_terminal();
+    }
+}
+
+/// Ensure that the specified file exists.
+/// If it does not exist, creates the file and its parent directories if necessary,
+/// and writes the default configuration content to it.
+fn ensure_file_exists(path: &PathBuf) -> anyhow::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| anyhow!("Failed to create directory: {e}"))?;
+    }
+
+    std::fs::File::create(path)?.write_all(DEFAULT_CONFIG.as_bytes())?;
+    Ok(())
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("{s} is not a positive number of lines per second")),
+    }
+}
+
+/// Determine the configuration file path.
+fn determine_config_file(config_path: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    if let Some(path) = config_path {
+        ensure_file_exists(&path)?;
+        return Ok(path);
+    }
+
+    let default_path = dirs::config_dir()
+        .ok_or_else(|| anyhow!("Failed to determine the configuration directory"))?
+        .join("sig")
+        .join("config.toml");
+
+    ensure_file_exists(&default_path)?;
+    Ok(default_path)
+}
+
+/// How often the configuration file is checked for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reload the highlight rules whenever the configuration file changes.
+/// A configuration that fails to load keeps the current rules.
+fn spawn_highlights_reloader(config_file: PathBuf, highlights: SharedHighlights) {
+    let modified = |path: &PathBuf| -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+    tokio::spawn(async move {
+        let mut last_modified = modified(&config_file);
+        loop {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+            let current = modified(&config_file);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            let reloaded = std::fs::read_to_string(&config_file)
+                .map_err(Into::into)
+                .and_then(|content| Config::load_from(&content))
+                .and_then(|config| Highlights::try_new(&config.highlights, config.latency));
+            if let Ok(reloaded) = reloaded {
+                *highlights.write().unwrap_or_else(|e| e.into_inner()) = reloaded;
+            }
+        }
+    });
+}
+
+/// Whether the UI cannot be drawn: `TERM=dumb`, or no terminal at all (e.g. in some CI shells).
+fn is_dumb_terminal() -> bool {
+    std::env::var("TERM").is_ok_and(|term| term == "dumb")
+        || (!io::stdout().is_terminal() && File::open("/dev/tty").is_err())
+}
+
+/// Draw on the terminal rather than on stdout if stdout is not one (e.g. a pipe),
+/// returning the original stdout to print the `--output` lines to.
+#[cfg(unix)]
+fn redirect_stdout_to_tty() -> anyhow::Result<Option<File>> {
+    use std::{
+        fs::OpenOptions,
+        os::fd::{AsFd, AsRawFd},
+    };
+
+    if io::stdout().is_terminal() {
+        return Ok(None);
+    }
+    let original = io::stdout().as_fd().try_clone_to_owned()?;
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| anyhow!("Failed to open the terminal: {e}"))?;
+    // SAFETY: both file descriptors are open for the duration of the call.
+    if unsafe { libc::dup2(tty.as_raw_fd(), io::stdout().as_raw_fd()) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(Some(File::from(original)))
+}
+
+/// Stdout cannot be swapped for the console here, so it has to be one to draw on.
+#[cfg(not(unix))]
+fn redirect_stdout_to_tty() -> anyhow::Result<Option<File>> {
+    if io::stdout().is_terminal() {
+        return Ok(None);
+    }
+    Err(anyhow!(
+        "The UI is drawn on stdout, which is not a console; \
+         run without redirecting it, or pass --plain to print the matching lines"
+    ))
+}
+
+/// Print the lines of the archive selected by `output` and the last query,
+/// encrypted with `cipher` if any.
+fn print_output(
+    out: &mut dyn Write,
+    archive: &Archive,
+    output: Output,
+    format: ExportFormat,
+    query: &str,
+    case_insensitive: bool,
+    cipher: Option<&Cipher>,
+) -> anyhow::Result<()> {
+    let mut result = Ok(());
+    archive.scan(|_, chunk| {
+        for line in chunk {
+            if result.is_err() {
+                return;
+            }
+            let selected = match output {
+                Output::None => false,
+                Output::Matches => {
+                    crate::styled(query, line, ContentStyle::default(), case_insensitive).is_some()
+                }
+                Output::All => true,
+            };
+            if selected {
+                let line = export::format_line(line, query, case_insensitive, format);
+                result = match cipher {
+                    Some(cipher) => writeln!(out, "{}", cipher.encrypt(&line)),
+                    None => writeln!(out, "{line}"),
+                };
+            }
+        }
+    })?;
+    result?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Print the lines of `files`, or of stdin if none, decrypted with `cipher`.
+#[cfg(feature = "encryption")]
+fn decrypt(cipher: &Cipher, files: &[PathBuf]) -> anyhow::Result<()> {
+    use std::io::BufRead;
+
+    let readers: Vec<Box<dyn BufRead>> = match files {
+        [] => vec![Box::new(io::stdin().lock())],
+        files => files
+            .iter()
+            .map(|path| {
+                File::open(path)
+                    .map(|file| Box::new(io::BufReader::new(file)) as Box<dyn BufRead>)
+                    .map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))
+            })
+            .collect::<anyhow::Result<_>>()?,
+    };
+    let mut out = io::stdout().lock();
+    for reader in readers {
+        for line in reader.lines() {
+            writeln!(out, "{}", cipher.decrypt(&line?)?)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// How lines are grouped into records, see `--multiline-start` and `--xml-record`.
+fn records(args: &Args) -> Option<(Records, Duration)> {
+    let records = match (&args.multiline_start, &args.xml_record) {
+        (Some(start), _) => Records::Start(start.clone()),
+        (None, Some(tag)) => Records::Xml(tag.clone()),
+        (None, None) => return None,
+    };
+    Some((
+        records,
+        Duration::from_millis(args.multiline_timeout_millis),
+    ))
+}
+
+/// Options of the sessions run without the streaming view.
+fn session_options(args: &Args, mask: Option<&Mask>, pipeline: &SharedPipeline) -> SessionOptions {
+    SessionOptions {
+        retrieval_timeout: Duration::from_millis(args.retrieval_timeout_millis),
+        channel_capacity: args.channel_capacity,
+        drop_policy: args.drop_policy,
+        filter: args.filter_cmd.clone().map(|f| (f, args.filter_mode)),
+        multiline: records(args),
+        rate: args.rate,
+        mask: mask.cloned(),
+        pipeline: Arc::clone(pipeline),
+    }
+}
+
+/// Run `sig` with the arguments of the process, as its binary does.
+pub async fn run() -> anyhow::Result<()> {
+    // Changed by `attach --read-only` only
+    #[cfg_attr(not(all(unix, feature = "server")), allow(unused_mut))]
+    let mut args = Args::parse();
+    let mut capabilities = Capabilities::detect(|name| std::env::var(name).ok());
+    capabilities.apply_overrides(&args.caps)?;
+    caps::init(capabilities);
+    let config_file = determine_config_file(args.config_file.clone());
+    let mut config = config_file
+        .as_ref()
+        .map_err(|e| anyhow!("{e}"))
+        .and_then(|config_file| {
+            std::fs::read_to_string(config_file)
+                .map_err(|e| anyhow!("Failed to read configuration file: {e}"))
+        })
+        .and_then(|content| Config::load_from(&content))
+        .unwrap_or_else(|e| {
+            eprintln!("sig: using the default configuration, since loading the configuration failed: {e:#}");
+            Config::load_from(DEFAULT_CONFIG).expect("Failed to load default configuration")
+        });
+    config.highlight_style = caps::fit_style(config.highlight_style);
+    config.archived.selected_item_style = caps::fit_style(config.archived.selected_item_style);
+    #[cfg(feature = "encryption")]
+    if let Some(Command::Decrypt { files }) = &args.command {
+        return decrypt(
+            &Cipher::load(config.encryption_key_command.as_deref())?,
+            files,
+        );
+    }
+    // The key of the files written, and the one of the spill file
+    #[cfg(feature = "encryption")]
+    let (cipher, spill_cipher) = if args.encrypt {
+        (
+            Some(Cipher::load(config.encryption_key_command.as_deref())?),
+            Some(Cipher::random()),
+        )
+    } else {
+        (None, None)
+    };
+    #[cfg(not(feature = "encryption"))]
+    let (cipher, spill_cipher) = (None::<Cipher>, None::<Cipher>);
+    #[cfg(all(unix, feature = "server"))]
+    if let Some(Command::Attach {
+        socket,
+        read_only: true,
+    }) = &args.command
+    {
+        // Watch with the query and the stages of the collector rather than ones of our own.
+        let published = crate::server::published(socket).await?;
+        args.query = Some(published.query);
+        config.pipeline = published.stages;
+    }
+
+    let dashboard = match (args.dashboard, &config.dashboard) {
+        (false, _) => None,
+        (true, Some(dashboard)) => Some(dashboard.clone()),
+        (true, None) => {
+            return Err(anyhow!(
+                "--dashboard needs a [dashboard] in the configuration"
+            ))
+        }
+    };
+    let pipeline = Arc::new(RwLock::new(Pipeline::try_new(&config.pipeline)?));
+    // Created once, so that values are faked the same way across retries
+    let mask = (!args.demo_mask.is_empty()).then(|| Mask::new(args.demo_mask.clone()));
+    let mut alerts = Alerts::try_new(&config.alerts)?;
+    alerts.incident = config.incident.clone().map(Incident::new);
+    let alerts = Arc::new(RwLock::new(alerts));
+    let highlights = Arc::new(std::sync::RwLock::new(Highlights::try_new(
+        &config.highlights,
+        config.latency.clone(),
+    )?));
+    let source: Box<dyn Source> = match (&args.command, &args.cmd) {
+        #[cfg(all(unix, feature = "server"))]
+        (Some(Command::Attach { socket, .. }), _) => Box::new(source::Socket(socket.clone())),
+        (_, Some(cmd)) => Box::new(source::Cmd(cmd.clone())),
+        (_, None) => Box::new(source::Stdin),
+    };
+
+    #[cfg(all(unix, feature = "server"))]
+    if let Some(Command::Serve { socket }) = &args.command {
+        return crate::server::serve(
+            source.as_ref(),
+            session_options(&args, mask.as_ref(), &pipeline),
+            &crate::server::Published {
+                query: args.query.clone().unwrap_or_default(),
+                stages: config.pipeline.clone(),
+            },
+            socket,
+            args.queue_capacity,
+        )
+        .await;
+    }
+
+    if let Some(path) = &args.audit_log {
+        audit::open(path, cipher.clone())?;
+    }
+    audit::record("streaming", "caps", &capabilities.summary());
+    audit::record(
+        "streaming",
+        "start",
+        &match (&args.command, &args.cmd) {
+            #[cfg(all(unix, feature = "server"))]
+            (Some(Command::Attach { socket, .. }), _) => format!("attach {}", socket.display()),
+            (_, Some(cmd)) => cmd.clone(),
+            (_, None) => String::from("stdin"),
+        },
+    );
+
+    #[cfg(not(feature = "sinks"))]
+    if let Some(name) = config.sinks.unsupported.keys().next() {
+        return Err(anyhow!(
+            "[sinks.{name}] needs sig to be built with the `sinks` feature"
+        ));
+    }
+    let sinks = Sinks::spawn(&config.sinks, cipher.clone());
+    #[cfg(all(unix, feature = "server"))]
+    let read_only = matches!(
+        args.command,
+        Some(Command::Attach {
+            read_only: true,
+            ..
+        })
+    );
+    #[cfg(not(all(unix, feature = "server")))]
+    let read_only = false;
+    let popup = matches!(args.command, Some(Command::TmuxPopup));
+    let pick = args.pick || popup;
+    let quiet = args.quiet || popup;
+    // The lines picked with Enter, printed to stdout on exit
+    let mut picked = None;
+    // Whether picking was given up with Esc
+    let mut aborted = false;
+
+    // Whether long lines are wrapped or truncated, kept across the modes
+    let mut overflow = Overflow::default();
+    // Whether the unmatched lines are shown too, kept across the modes
+    let mut passthrough = args.passthrough;
+
+    let config_file = config_file.ok();
+    let mut slots = QuerySlots::load(config_file.as_deref());
+    if let Some(config_file) = &config_file {
+        spawn_highlights_reloader(config_file.clone(), Arc::clone(&highlights));
+    }
+
+    let dumb_terminal = is_dumb_terminal();
+    if dumb_terminal && !args.plain {
+        // Printing the matching lines instead would not do what these were asked for.
+        let interactive = [
+            (args.archived, "--archived"),
+            (dashboard.is_some(), "--dashboard"),
+            (popup, "tmux-popup"),
+            (args.pick, "--pick"),
+        ];
+        if let Some((_, name)) = interactive.iter().find(|(given, _)| *given) {
+            return Err(anyhow!(
+                "{name} needs a terminal to draw on (TERM=dumb or none), \
+                 pass --plain to print the matching lines instead"
+            ));
+        }
+    }
+    if args.plain || dumb_terminal {
+        audit::record("plain", "start", "");
+        streaming::print_matches(
+            source.as_ref(),
+            session_options(&args, mask.as_ref(), &pipeline),
+            &args.query.clone().unwrap_or_default(),
+            args.case_insensitive,
+            sinks.sender(),
+            &mut io::stdout(),
+        )
+        .await?;
+        sinks
+            .close(Duration::from_millis(args.sink_drain_timeout_millis))
+            .await?;
+        return Ok(());
+    }
+
+    let redirected = redirect_stdout_to_tty()?;
+
+    enter_terminal()?;
+    let backend: Arc<dyn Backend> = Arc::new(CrosstermBackend);
+
+    let exit = loop {
+        let archive = match &args.spill_file {
+            Some(path) => {
+                Archive::with_spill_file(args.queue_capacity, path, spill_cipher.clone())?
+            }
+            None => Archive::new(args.queue_capacity),
+        };
+        let result = if args.archived {
+            streaming::collect(
+                backend.as_ref(),
+                &config.streaming.keybinds,
+                archive,
+                source.as_ref(),
+                session_options(&args, mask.as_ref(), &pipeline),
+                &args.query.clone().unwrap_or_default(),
+                args.case_insensitive,
+                sinks.sender(),
+            )
+            .await
+        } else if let Some(dashboard) = &dashboard {
+            dashboard::run(
+                backend.as_ref(),
+                dashboard,
+                &config.streaming.keybinds,
+                archive,
+                source.as_ref(),
+                session_options(&args, mask.as_ref(), &pipeline),
+                Arc::clone(&alerts),
+                config.highlight_style,
+                args.case_insensitive,
+                sinks.sender(),
+            )
+            .await
+        } else {
+            streaming::run(
+                text_editor::State {
+                    texteditor: TextEditor::new(args.query.clone().unwrap_or_default()),
+                    history: Default::default(),
+                    config: config.streaming.editor.clone(),
+                },
+                archive,
+                source.as_ref(),
+                StreamingOptions {
+                    backend: Arc::clone(&backend),
+                    highlight_style: config.highlight_style,
+                    keybinds: config.streaming.keybinds.clone(),
+                    render_interval: args.render_interval_millis.map(Duration::from_millis),
+                    case_insensitive: args.case_insensitive,
+                    session: session_options(&args, mask.as_ref(), &pipeline),
+                    sequence_field: args.sequence_field.clone(),
+                    on_select: args.on_select.clone(),
+                    alerts: Arc::clone(&alerts),
+                    highlights: Arc::clone(&highlights),
+                    config_file: config_file.as_deref(),
+                    ranking: args.preset.map(|preset| {
+                        let ranking = match preset {
+                            Preset::Folded => Ranking::Stacks(FoldedStacks::default()),
+                            Preset::Words => Ranking::Words(WordCounts::new(args.word_window)),
+                        };
+                        (ranking, args.top)
+                    }),
+                    split: args.split,
+                    reverse: args.reverse,
+                    gutter: args.gutter,
+                    editor_position: config.streaming.editor_position,
+                    read_only,
+                    quiet,
+                    pick,
+                    sink: sinks.sender(),
+                    idle_dim: args.idle_dim_secs.map(Duration::from_secs),
+                    overflow: &mut overflow,
+                    passthrough: &mut passthrough,
+                    slots: &mut slots,
+                },
+            )
+            .await
+        };
+        let (signal, archive) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                audit::record("streaming", "exit", &e.to_string());
+                leave_terminal()?;
+                // Deliver what the sinks have received before leaving.
+                sinks
+                    .close(Duration::from_millis(args.sink_drain_timeout_millis))
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        backend.clear()?;
+
+        match signal {
+            Signal::GotoArchived => {
+                match archived::run(
+                    text_editor::State {
+                        // Searching the archive right away if it is all the user is going to see
+                        texteditor: TextEditor::new(if args.archived {
+                            args.query.clone().unwrap_or_default()
+                        } else {
+                            String::new()
+                        }),
+                        history: Default::default(),
+                        config: config.archived.editor.clone(),
+                    },
+                    archive,
+                    ArchivedOptions {
+                        backend: Arc::clone(&backend),
+                        listbox: config.archived.listbox.clone(),
+                        selected_item_style: config.archived.selected_item_style,
+                        clipboard: Clipboard::new(config.clipboard_command.clone()),
+                        export_format: args.output_format,
+                        highlight_style: config.highlight_style,
+                        highlights: Arc::clone(&highlights),
+                        keybinds: config.archived.keybinds.clone(),
+                        case_insensitive: args.case_insensitive,
+                        retryable: source.restartable() && !read_only,
+                        on_select: args.on_select.clone().filter(|_| !read_only),
+                        preview: args.preview.clone().filter(|_| !read_only),
+                        correlation_id: args.correlation_id.clone(),
+                        quiet,
+                        pick,
+                    },
+                    &mut overflow,
+                    &mut passthrough,
+                )
+                .await
+                {
+                    Ok((Signal::Exit { query, print }, archive)) => {
+                        audit::record("archived", "exit", "");
+                        break Some((query, print, archive));
+                    }
+                    Ok((Signal::Pick(lines), _)) => {
+                        audit::record("archived", "pick", &lines.join("\n"));
+                        picked = Some(lines);
+                        break None;
+                    }
+                    Ok((Signal::Abort, _)) => {
+                        aborted = true;
+                        break None;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // Deliver what the sinks have received before leaving.
+                        sinks
+                            .close(Duration::from_millis(args.sink_drain_timeout_millis))
+                            .await?;
+                        return Err(e);
+                    }
+                }
+
+                // Re-enable raw mode and hide the cursor again here
+                // because they are disabled and shown, respectively, by promkit.
+                enter_terminal()?;
+
+                backend.clear()?;
+            }
+            Signal::GotoStreaming => {
+                continue;
+            }
+            Signal::Exit { query, print } => {
+                audit::record("streaming", "exit", "");
+                break Some((query, print, archive));
+            }
+            Signal::Pick(lines) => {
+                picked = Some(lines);
+                break None;
+            }
+            Signal::Abort => {
+                aborted = true;
+                break None;
+            }
+        }
+    };
+
+    leave_terminal()?;
+    let mut out: Box<dyn Write> = match redirected {
+        Some(file) => Box::new(file),
+        None => Box::new(io::stdout()),
+    };
+    if let Some(lines) = picked {
+        for line in lines {
+            writeln!(out, "{line}")?;
+        }
+        out.flush()?;
+    }
+    if let Some((query, print, archive)) = exit {
+        let output = match (args.output, print) {
+            (Output::None, true) => Output::Matches,
+            (output, _) => output,
+        };
+        if output != Output::None {
+            print_output(
+                &mut out,
+                &archive,
+                output,
+                args.output_format,
+                &query,
+                args.case_insensitive,
+                cipher.as_ref(),
+            )?;
+        }
+    }
+    sinks
+        .close(Duration::from_millis(args.sink_drain_timeout_millis))
+        .await?;
+    if aborted {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use crate::caps;
+
+/// Clipboard commands tried when the terminal has no OSC 52, with the binary they need.
+const FALLBACK_COMMANDS: [(&str, &str); 4] = [
+    ("pbcopy", "pbcopy"),
+    ("wl-copy", "wl-copy"),
+    ("xclip", "xclip -selection clipboard"),
+    ("xsel", "xsel --clipboard --input"),
+];
+
+/// Backend to copy text to the system clipboard.
+pub enum Clipboard {
+    /// Ask the terminal emulator to set the clipboard with OSC 52,
+    /// which also works over SSH.
+    Osc52,
+    /// Pipe the text into an external command, e.g. `pbcopy` or `wl-copy`.
+    Command(String),
+    /// Neither OSC 52 nor a clipboard command is available.
+    Unavailable,
+}
+
+impl Clipboard {
+    /// The given command, otherwise OSC 52 if the terminal has it (see `caps`),
+    /// otherwise the first clipboard command found in `PATH`.
+    /// Always unavailable without the `clipboard` feature.
+    pub fn new(command: Option<String>) -> Self {
+        if cfg!(not(feature = "clipboard")) {
+            return Self::Unavailable;
+        }
+        match command {
+            Some(command) => Self::Command(command),
+            None if caps::get().osc52 => Self::Osc52,
+            None => FALLBACK_COMMANDS
+                .iter()
+                .find(|(binary, _)| in_path(binary))
+                .map_or(Self::Unavailable, |(_, command)| {
+                    Self::Command(command.to_string())
+                }),
+        }
+    }
+
+    pub fn copy(&self, text: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Osc52 => {
+                let mut stdout = io::stdout();
+                write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+                stdout.flush()?;
+                Ok(())
+            }
+            Self::Command(cmdstr) => {
+                let args: Vec<&str> = cmdstr.split_whitespace().collect();
+                let (program, args) = args
+                    .split_first()
+                    .ok_or_else(|| anyhow::anyhow!("clipboard command is empty"))?;
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+                child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("stdin is not available"))?
+                    .write_all(text.as_bytes())?;
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(anyhow::anyhow!("clipboard command failed: {status}"));
+                }
+                Ok(())
+            }
+            Self::Unavailable if cfg!(not(feature = "clipboard")) => Err(anyhow::anyhow!(
+                "sig was built without the clipboard feature"
+            )),
+            Self::Unavailable => Err(anyhow::anyhow!(
+                "The terminal has no OSC 52, set clipboard_command in the configuration"
+            )),
+        }
+    }
+}
+
+fn in_path(binary: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(binary).is_file()))
+}
+
+/// Encode bytes with the standard base64 alphabet and padding.
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,102 @@
+use crate::highlight;
+
+/// Numbers of lines matching either query of a comparison.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub a: u64,
+    pub b: u64,
+    pub both: u64,
+    pub neither: u64,
+}
+
+/// Live comparison of two queries over the stream, e.g. "500" against "timeout":
+/// `a` is pinned with the `compare` key, `b` is the query being edited.
+/// The counts restart whenever `b` is edited, so that both are counted over the same lines.
+pub struct Comparison {
+    a: String,
+    b: String,
+    pub counts: Counts,
+}
+
+impl Comparison {
+    pub fn new(a: String) -> Self {
+        Self {
+            a,
+            b: String::new(),
+            counts: Counts::default(),
+        }
+    }
+
+    /// Count the line, given the query it was matched against and whether it matched.
+    pub fn observe(&mut self, line: &str, b: &str, b_matched: bool, case_insensitive: bool) {
+        if self.b != b {
+            self.b = b.to_string();
+            self.counts = Counts::default();
+        }
+        let a_matched = highlight::is_match(&self.a, line, case_insensitive);
+        let counts = &mut self.counts;
+        match (a_matched, b_matched) {
+            (true, true) => {
+                counts.a += 1;
+                counts.b += 1;
+                counts.both += 1;
+            }
+            (true, false) => counts.a += 1,
+            (false, true) => counts.b += 1,
+            (false, false) => counts.neither += 1,
+        }
+    }
+
+    /// The counts and the ratio of A to B, shown in the hint.
+    pub fn hint(&self) -> String {
+        let Counts {
+            a,
+            b,
+            both,
+            neither,
+        } = self.counts;
+        let ratio = if b == 0 {
+            String::from("-")
+        } else {
+            format!("{:.2}", a as f64 / b as f64)
+        };
+        format!(
+            " | A({}) {a}, B({}) {b}, both {both}, neither {neither}, A/B {ratio}",
+            self.a, self.b,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observe_counts_both_queries_since_b_was_edited() {
+        let mut comparison = Comparison::new(String::from("500"));
+        let lines = [
+            ("GET /a 500 timeout", true),
+            ("GET /b 500", false),
+            ("GET /c 200 timeout", true),
+            ("GET /d 200", false),
+        ];
+        comparison.observe("GET /z 500", "time", false, false);
+        for (line, matched) in lines {
+            comparison.observe(line, "timeout", matched, false);
+        }
+
+        assert_eq!(
+            comparison.counts,
+            Counts {
+                a: 2,
+                b: 2,
+                both: 1,
+                neither: 1
+            }
+        );
+        assert_eq!(
+            comparison.hint(),
+            " | A(500) 2, B(timeout) 2, both 1, neither 1, A/B 1.00"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::OnceLock};
 
 use promkit_core::crossterm::{
     event::{Event, KeyEvent, MouseEvent},
@@ -8,8 +8,106 @@ use promkit_widgets::{listbox, text_editor};
 use serde::{Deserialize, Serialize};
 use termcfg::crossterm_config::{content_style_serde, event_set_serde};
 
+use crate::{
+    alert::AlertConfig,
+    dashboard::DashboardConfig,
+    highlight::{HighlightConfig, LatencyConfig},
+    incident::IncidentConfig,
+    layout::EditorPosition,
+    pipeline::StageConfig,
+    sink::SinksConfig,
+};
+
 pub static DEFAULT_CONFIG: &str = include_str!("../default.toml");
 
+/// The value of a setting in default.toml, given by its dotted path.
+fn default_value(path: &str) -> toml::Value {
+    static DEFAULT: OnceLock<toml::Value> = OnceLock::new();
+    let mut value = DEFAULT
+        .get_or_init(|| toml::from_str(DEFAULT_CONFIG).expect("default.toml must be valid TOML"));
+    for key in path.split('.') {
+        value = &value[key];
+    }
+    value.clone()
+}
+
+/// Defaults of the settings added since the first release, taken from default.toml,
+/// so that the configurations written for older versions keep loading.
+mod defaults {
+    use super::*;
+
+    macro_rules! keybinds {
+        ($($name:ident = $path:literal,)*) => {
+            $(
+                pub fn $name() -> HashSet<Event> {
+                    event_set_serde::deserialize(default_value($path))
+                        .expect(concat!("invalid ", $path, " in default.toml"))
+                }
+            )*
+        };
+    }
+
+    keybinds! {
+        streaming_exit_and_print = "streaming.keybinds.exit_and_print",
+        streaming_toggle_passthrough = "streaming.keybinds.toggle_passthrough",
+        streaming_exec = "streaming.keybinds.exec",
+        streaming_toggle_inspector = "streaming.keybinds.toggle_inspector",
+        streaming_toggle_rules = "streaming.keybinds.toggle_rules",
+        streaming_dismiss_incident = "streaming.keybinds.dismiss_incident",
+        streaming_abort = "streaming.keybinds.abort",
+        streaming_save_query_slot = "streaming.keybinds.save_query_slot",
+        streaming_toggle_split = "streaming.keybinds.toggle_split",
+        streaming_toggle_wrap = "streaming.keybinds.toggle_wrap",
+        streaming_scroll_left = "streaming.keybinds.scroll_left",
+        streaming_scroll_right = "streaming.keybinds.scroll_right",
+        streaming_compare = "streaming.keybinds.compare",
+        streaming_lock = "streaming.keybinds.lock",
+        streaming_unlock = "streaming.keybinds.unlock",
+        archived_exit_and_print = "archived.keybinds.exit_and_print",
+        archived_page_up = "archived.keybinds.page_up",
+        archived_page_down = "archived.keybinds.page_down",
+        archived_goto_head = "archived.keybinds.goto_head",
+        archived_goto_tail = "archived.keybinds.goto_tail",
+        archived_next_match = "archived.keybinds.next_match",
+        archived_prev_match = "archived.keybinds.prev_match",
+        archived_toggle_filter = "archived.keybinds.toggle_filter",
+        archived_select = "archived.keybinds.select",
+        archived_copy = "archived.keybinds.copy",
+        archived_exec = "archived.keybinds.exec",
+        archived_toggle_wrap = "archived.keybinds.toggle_wrap",
+        archived_scroll_left = "archived.keybinds.scroll_left",
+        archived_scroll_right = "archived.keybinds.scroll_right",
+        archived_abort = "archived.keybinds.abort",
+        archived_toggle_mark = "archived.keybinds.toggle_mark",
+        archived_toggle_minimap = "archived.keybinds.toggle_minimap",
+        archived_minimap_up = "archived.keybinds.minimap_up",
+        archived_minimap_down = "archived.keybinds.minimap_down",
+        archived_correlate = "archived.keybinds.correlate",
+        archived_toggle_schema = "archived.keybinds.toggle_schema",
+        archived_toggle_fold = "archived.keybinds.toggle_fold",
+    }
+
+    pub fn inspector() -> InspectorKeybinds {
+        InspectorKeybinds::deserialize(default_value("streaming.keybinds.inspector"))
+            .expect("invalid streaming.keybinds.inspector in default.toml")
+    }
+
+    pub fn rules() -> RulesKeybinds {
+        RulesKeybinds::deserialize(default_value("streaming.keybinds.rules"))
+            .expect("invalid streaming.keybinds.rules in default.toml")
+    }
+
+    pub fn schema() -> SchemaKeybinds {
+        SchemaKeybinds::deserialize(default_value("archived.keybinds.schema"))
+            .expect("invalid archived.keybinds.schema in default.toml")
+    }
+
+    pub fn selected_item_style() -> ContentStyle {
+        content_style_serde::deserialize(default_value("archived.selected_item_style"))
+            .expect("invalid archived.selected_item_style in default.toml")
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct EditorKeybinds {
     #[serde(with = "event_set_serde")]
@@ -26,40 +124,188 @@ pub struct EditorKeybinds {
     pub erase_all: HashSet<Event>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InspectorKeybinds {
+    #[serde(with = "event_set_serde")]
+    pub up: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub down: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub toggle: HashSet<Event>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RulesKeybinds {
+    #[serde(with = "event_set_serde")]
+    pub up: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub down: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub cycle: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub apply: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub save: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub cancel: HashSet<Event>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct StreamingKeybinds {
     #[serde(with = "event_set_serde")]
     pub exit: HashSet<Event>,
+    #[serde(
+        default = "defaults::streaming_exit_and_print",
+        with = "event_set_serde"
+    )]
+    pub exit_and_print: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub goto_archived: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub retry: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub toggle_pause: HashSet<Event>,
+    #[serde(
+        default = "defaults::streaming_toggle_passthrough",
+        with = "event_set_serde"
+    )]
+    pub toggle_passthrough: HashSet<Event>,
+    #[serde(default = "defaults::streaming_exec", with = "event_set_serde")]
+    pub exec: HashSet<Event>,
+    #[serde(
+        default = "defaults::streaming_toggle_inspector",
+        with = "event_set_serde"
+    )]
+    pub toggle_inspector: HashSet<Event>,
+    #[serde(default = "defaults::streaming_toggle_rules", with = "event_set_serde")]
+    pub toggle_rules: HashSet<Event>,
+    #[serde(
+        default = "defaults::streaming_dismiss_incident",
+        with = "event_set_serde"
+    )]
+    pub dismiss_incident: HashSet<Event>,
+    #[serde(default = "defaults::streaming_abort", with = "event_set_serde")]
+    pub abort: HashSet<Event>,
+    #[serde(
+        default = "defaults::streaming_save_query_slot",
+        with = "event_set_serde"
+    )]
+    pub save_query_slot: HashSet<Event>,
+    #[serde(default = "defaults::streaming_toggle_split", with = "event_set_serde")]
+    pub toggle_split: HashSet<Event>,
+    #[serde(default = "defaults::streaming_toggle_wrap", with = "event_set_serde")]
+    pub toggle_wrap: HashSet<Event>,
+    #[serde(default = "defaults::streaming_scroll_left", with = "event_set_serde")]
+    pub scroll_left: HashSet<Event>,
+    #[serde(default = "defaults::streaming_scroll_right", with = "event_set_serde")]
+    pub scroll_right: HashSet<Event>,
+    #[serde(default = "defaults::streaming_compare", with = "event_set_serde")]
+    pub compare: HashSet<Event>,
+    #[serde(default = "defaults::streaming_lock", with = "event_set_serde")]
+    pub lock: HashSet<Event>,
+    #[serde(default = "defaults::streaming_unlock", with = "event_set_serde")]
+    pub unlock: HashSet<Event>,
     pub editor: EditorKeybinds,
+    #[serde(default = "defaults::inspector")]
+    pub inspector: InspectorKeybinds,
+    #[serde(default = "defaults::rules")]
+    pub rules: RulesKeybinds,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SchemaKeybinds {
+    #[serde(with = "event_set_serde")]
+    pub up: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub down: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub project: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub color: HashSet<Event>,
+    #[serde(with = "event_set_serde")]
+    pub aggregate: HashSet<Event>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ArchivedKeybinds {
     #[serde(with = "event_set_serde")]
     pub exit: HashSet<Event>,
+    #[serde(
+        default = "defaults::archived_exit_and_print",
+        with = "event_set_serde"
+    )]
+    pub exit_and_print: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub retry: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub up: HashSet<Event>,
     #[serde(with = "event_set_serde")]
     pub down: HashSet<Event>,
+    #[serde(default = "defaults::archived_page_up", with = "event_set_serde")]
+    pub page_up: HashSet<Event>,
+    #[serde(default = "defaults::archived_page_down", with = "event_set_serde")]
+    pub page_down: HashSet<Event>,
+    #[serde(default = "defaults::archived_goto_head", with = "event_set_serde")]
+    pub goto_head: HashSet<Event>,
+    #[serde(default = "defaults::archived_goto_tail", with = "event_set_serde")]
+    pub goto_tail: HashSet<Event>,
+    #[serde(default = "defaults::archived_next_match", with = "event_set_serde")]
+    pub next_match: HashSet<Event>,
+    #[serde(default = "defaults::archived_prev_match", with = "event_set_serde")]
+    pub prev_match: HashSet<Event>,
+    #[serde(default = "defaults::archived_toggle_filter", with = "event_set_serde")]
+    pub toggle_filter: HashSet<Event>,
+    #[serde(default = "defaults::archived_select", with = "event_set_serde")]
+    pub select: HashSet<Event>,
+    #[serde(default = "defaults::archived_copy", with = "event_set_serde")]
+    pub copy: HashSet<Event>,
+    #[serde(default = "defaults::archived_exec", with = "event_set_serde")]
+    pub exec: HashSet<Event>,
+    #[serde(default = "defaults::archived_toggle_wrap", with = "event_set_serde")]
+    pub toggle_wrap: HashSet<Event>,
+    #[serde(default = "defaults::archived_scroll_left", with = "event_set_serde")]
+    pub scroll_left: HashSet<Event>,
+    #[serde(default = "defaults::archived_scroll_right", with = "event_set_serde")]
+    pub scroll_right: HashSet<Event>,
+    #[serde(default = "defaults::archived_abort", with = "event_set_serde")]
+    pub abort: HashSet<Event>,
+    #[serde(default = "defaults::archived_toggle_mark", with = "event_set_serde")]
+    pub toggle_mark: HashSet<Event>,
+    #[serde(
+        default = "defaults::archived_toggle_minimap",
+        with = "event_set_serde"
+    )]
+    pub toggle_minimap: HashSet<Event>,
+    #[serde(default = "defaults::archived_minimap_up", with = "event_set_serde")]
+    pub minimap_up: HashSet<Event>,
+    #[serde(default = "defaults::archived_minimap_down", with = "event_set_serde")]
+    pub minimap_down: HashSet<Event>,
+    #[serde(default = "defaults::archived_correlate", with = "event_set_serde")]
+    pub correlate: HashSet<Event>,
+    #[serde(default = "defaults::archived_toggle_schema", with = "event_set_serde")]
+    pub toggle_schema: HashSet<Event>,
+    #[serde(default = "defaults::archived_toggle_fold", with = "event_set_serde")]
+    pub toggle_fold: HashSet<Event>,
     pub editor: EditorKeybinds,
+    #[serde(default = "defaults::schema")]
+    pub schema: SchemaKeybinds,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct StreamingConfig {
+    #[serde(default)]
+    pub editor_position: EditorPosition,
     pub editor: text_editor::Config,
     pub keybinds: StreamingKeybinds,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ArchivedConfig {
+    #[serde(
+        default = "defaults::selected_item_style",
+        with = "content_style_serde"
+    )]
+    pub selected_item_style: ContentStyle,
     pub editor: text_editor::Config,
     pub listbox: listbox::Config,
     pub keybinds: ArchivedKeybinds,
@@ -71,6 +317,19 @@ pub struct Config {
     pub archived: ArchivedConfig,
     #[serde(with = "content_style_serde")]
     pub highlight_style: ContentStyle,
+    #[serde(default)]
+    pub highlights: Vec<HighlightConfig>,
+    pub latency: Option<LatencyConfig>,
+    pub clipboard_command: Option<String>,
+    pub encryption_key_command: Option<String>,
+    #[serde(default)]
+    pub pipeline: Vec<StageConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertConfig>,
+    pub incident: Option<IncidentConfig>,
+    #[serde(default)]
+    pub sinks: SinksConfig,
+    pub dashboard: Option<DashboardConfig>,
 }
 
 impl Config {
@@ -102,4 +361,24 @@ mod tests {
     fn default_config_is_valid_toml() {
         Config::load_from(DEFAULT_CONFIG).expect("default.toml must be valid");
     }
+
+    #[test]
+    fn config_of_the_first_release_gets_the_new_defaults() {
+        let config = Config::load_from(include_str!("../testdata/config/baseline.toml"))
+            .expect("configurations of older versions must keep loading");
+        let default = Config::load_from(DEFAULT_CONFIG).unwrap();
+        assert_eq!(
+            config.streaming.keybinds.lock,
+            default.streaming.keybinds.lock
+        );
+        assert_eq!(
+            config.archived.keybinds.schema.project,
+            default.archived.keybinds.schema.project
+        );
+        assert_eq!(
+            config.archived.selected_item_style,
+            default.archived.selected_item_style
+        );
+        assert!(config.pipeline.is_empty() && config.dashboard.is_none());
+    }
 }
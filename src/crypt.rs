@@ -0,0 +1,171 @@
+//! Encryption of the files sig writes lines to (see `--encrypt`).
+//!
+//! Each line is sealed on its own with AES-256-GCM and written as the base64 of
+//! its nonce followed by the ciphertext, so that files stay appendable line by line
+//! and the spill file can still be read at any line.
+
+#[cfg(feature = "encryption")]
+mod aes {
+    use aes_gcm::{
+        aead::{Aead, AeadCore, KeyInit, OsRng},
+        Aes256Gcm, Key, Nonce,
+    };
+
+    /// Environment variable holding the key, as 64 hex digits or the base64 of 32 bytes.
+    const KEY_ENV: &str = "SIG_ENCRYPTION_KEY";
+
+    /// Length of the nonce prepended to each sealed line.
+    const NONCE_LEN: usize = 12;
+
+    #[derive(Clone)]
+    pub struct Cipher(Aes256Gcm);
+
+    impl Cipher {
+        /// A cipher with a key of its own, for files read back by this process only.
+        pub fn random() -> Self {
+            Self(Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)))
+        }
+
+        /// The cipher of the key in `SIG_ENCRYPTION_KEY`, or else printed by `key_command`
+        /// (e.g. `secret-tool lookup service sig` to take it from the keyring).
+        pub fn load(key_command: Option<&str>) -> anyhow::Result<Self> {
+            let key = match (std::env::var(KEY_ENV), key_command) {
+                (Ok(key), _) => key,
+                (Err(_), Some(command)) => {
+                    let output = std::process::Command::new("sh")
+                        .args(["-c", command])
+                        .output()
+                        .map_err(|e| anyhow::anyhow!("Failed to run {command:?}: {e}"))?;
+                    if !output.status.success() {
+                        return Err(anyhow::anyhow!(
+                            "{command:?} failed with {}: {}",
+                            output.status,
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        ));
+                    }
+                    String::from_utf8_lossy(&output.stdout).into_owned()
+                }
+                (Err(_), None) => {
+                    return Err(anyhow::anyhow!(
+                        "No encryption key, set {KEY_ENV} or encryption_key_command"
+                    ))
+                }
+            };
+            Self::from_key(key.trim())
+        }
+
+        pub(super) fn from_key(key: &str) -> anyhow::Result<Self> {
+            let bytes = if key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                (0..64)
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&key[i..i + 2], 16).expect("hex digits"))
+                    .collect()
+            } else {
+                crate::decode::base64(key).unwrap_or_default()
+            };
+            if bytes.len() != 32 {
+                return Err(anyhow::anyhow!(
+                    "The encryption key must be 32 bytes, as 64 hex digits or in base64"
+                ));
+            }
+            Ok(Self(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes))))
+        }
+
+        /// Seal a line, which may hold newlines, into a single line of base64.
+        pub fn encrypt(&self, line: &str) -> String {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let sealed = self
+                .0
+                .encrypt(&nonce, line.as_bytes())
+                .expect("a line fits in AES-GCM");
+            let mut bytes = nonce.to_vec();
+            bytes.extend(sealed);
+            super::base64(&bytes)
+        }
+
+        pub fn decrypt(&self, sealed: &str) -> anyhow::Result<String> {
+            let bytes = crate::decode::base64(sealed.trim())
+                .filter(|bytes| bytes.len() >= NONCE_LEN)
+                .ok_or_else(|| anyhow::anyhow!("Not an encrypted line"))?;
+            let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+            let line = self
+                .0
+                .decrypt(Nonce::from_slice(nonce), sealed)
+                .map_err(|_| anyhow::anyhow!("Failed to decrypt a line, is the key right?"))?;
+            Ok(String::from_utf8_lossy(&line).into_owned())
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+pub use aes::Cipher;
+
+/// Without the `encryption` feature there is no cipher, and lines are written as they are.
+#[cfg(not(feature = "encryption"))]
+#[derive(Clone)]
+pub enum Cipher {}
+
+#[cfg(not(feature = "encryption"))]
+impl Cipher {
+    pub fn encrypt(&self, _line: &str) -> String {
+        match *self {}
+    }
+
+    pub fn decrypt(&self, _sealed: &str) -> anyhow::Result<String> {
+        match *self {}
+    }
+}
+
+/// Standard base64 with padding.
+#[cfg(feature = "encryption")]
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 63) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_sealed_one_by_one() {
+        let cipher = Cipher::random();
+        let sealed = cipher.encrypt("user=alice\n  at main");
+        assert!(!sealed.contains('\n'));
+        assert!(!sealed.contains("alice"));
+        // Each line has a nonce of its own.
+        assert_ne!(cipher.encrypt("user=alice\n  at main"), sealed);
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "user=alice\n  at main");
+        assert!(Cipher::random().decrypt(&sealed).is_err());
+        assert!(cipher.decrypt("user=alice").is_err());
+    }
+
+    #[test]
+    fn keys_are_read_as_hex_or_base64() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        let sealed = Cipher::from_key(hex).unwrap().encrypt("line");
+        let bytes = (0..32).map(|i| i % 16 * 0x11).collect::<Vec<u8>>();
+        let b64 = base64(&bytes);
+        assert_eq!(
+            Cipher::from_key(&b64).unwrap().decrypt(&sealed).unwrap(),
+            "line"
+        );
+        assert!(Cipher::from_key("too short").is_err());
+        assert_eq!(base64(b"hi"), "aGk=");
+        assert_eq!(base64(b"hi?"), "aGk/");
+    }
+}
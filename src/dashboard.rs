@@ -0,0 +1,460 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use promkit_core::{
+    crossterm::{
+        event::Event,
+        style::{Attribute, Attributes, Color, ContentStyle},
+    },
+    grapheme::StyledGraphemes,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{watch, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    alert::{Alerts, SharedAlerts},
+    archive::Archive,
+    audit,
+    backend::Backend,
+    config::{matches_keybind, StreamingKeybinds},
+    highlight::{highlight, is_match},
+    layout::fit,
+    session::{Session, SessionOptions},
+    sink::SinkSender,
+    source::Source,
+    stats::WindowedStats,
+    streaming::format_keybinds,
+    Signal,
+};
+
+/// Interval to redraw the dashboard, which is not redrawn on each line.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The wall-dashboard profile, configured as `[dashboard]` and shown with `--dashboard`.
+///
+/// Only counters, the states of the alerts and the last lines matching `query` are shown,
+/// without a query editor, for screens nobody types into.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DashboardConfig {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub query: String,
+    /// Number of matching lines shown under the counters.
+    #[serde(default = "default_lines")]
+    pub lines: usize,
+    #[serde(default)]
+    pub counters: Vec<CounterConfig>,
+}
+
+fn default_lines() -> usize {
+    5
+}
+
+/// Lines matching `pattern`, counted since the start and over the last minute.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CounterConfig {
+    pub name: String,
+    pub pattern: String,
+}
+
+struct Counter {
+    name: String,
+    pattern: Regex,
+    total: u64,
+    stats: WindowedStats,
+}
+
+/// Digits drawn three rows high with block characters.
+const DIGITS: [[&str; 3]; 10] = [
+    ["█▀█", "█ █", "█▄█"],
+    ["▀█ ", " █ ", "▄█▄"],
+    ["▀▀█", "█▀▀", "█▄▄"],
+    ["▀▀█", " ▀█", "▄▄█"],
+    ["█ █", "▀▀█", "  █"],
+    ["█▀▀", "▀▀█", "▄▄█"],
+    ["█▀▀", "█▀█", "█▄█"],
+    ["▀▀█", "  █", "  █"],
+    ["█▀█", "█▀█", "█▄█"],
+    ["█▀█", "▀▀█", "▄▄█"],
+];
+
+/// The number drawn in large print, one string per row.
+fn large(number: u64) -> [String; 3] {
+    let digits = number.to_string();
+    [0, 1, 2].map(|row| {
+        digits
+            .bytes()
+            .map(|digit| DIGITS[(digit - b'0') as usize][row])
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+}
+
+fn bold(color: Option<Color>) -> ContentStyle {
+    ContentStyle {
+        foreground_color: color,
+        attributes: Attributes::from(Attribute::Bold),
+        ..Default::default()
+    }
+}
+
+pub struct Dashboard {
+    title: String,
+    query: String,
+    counters: Vec<Counter>,
+    // The last lines matching the query, the newest at the back
+    recent: VecDeque<String>,
+    lines: usize,
+}
+
+impl Dashboard {
+    pub fn try_new(config: &DashboardConfig, origin: Instant) -> anyhow::Result<Self> {
+        let counters = config
+            .counters
+            .iter()
+            .map(|counter| {
+                Ok(Counter {
+                    name: counter.name.clone(),
+                    pattern: Regex::new(&counter.pattern).map_err(|e| {
+                        anyhow::anyhow!("Invalid pattern in counter {}: {e}", counter.name)
+                    })?,
+                    total: 0,
+                    stats: WindowedStats::new(Duration::from_secs(60), origin),
+                })
+            })
+            .collect::<anyhow::Result<Vec<Counter>>>()?;
+        Ok(Self {
+            title: config.title.clone().unwrap_or_else(|| String::from("sig")),
+            query: config.query.clone(),
+            counters,
+            recent: VecDeque::with_capacity(config.lines),
+            lines: config.lines,
+        })
+    }
+
+    /// Count the line towards the counters, and keep it if it matches the query.
+    pub fn observe(&mut self, line: &str, matched: bool, now: Instant) {
+        for counter in &mut self.counters {
+            if counter.pattern.is_match(line) {
+                counter.total += 1;
+                counter.stats.record(now, 1.0);
+            }
+        }
+        if matched && self.lines > 0 {
+            if self.recent.len() == self.lines {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(line.to_string());
+        }
+    }
+
+    /// The counters side by side, each as a box with its name, its total in large print
+    /// and the count over the last minute, wrapped onto more bands if they do not fit.
+    fn counter_rows(&mut self, width: usize, now: Instant) -> Vec<StyledGraphemes> {
+        let mut rows = Vec::new();
+        // The tiles of the current band, each as its rows
+        let mut band: Vec<Vec<StyledGraphemes>> = Vec::new();
+        let mut used = 0;
+        let flush = |band: &mut Vec<Vec<StyledGraphemes>>, rows: &mut Vec<StyledGraphemes>| {
+            for row in 0..band.first().map_or(0, Vec::len) {
+                let mut joined = StyledGraphemes::default();
+                for (idx, tile) in band.iter().enumerate() {
+                    if idx > 0 {
+                        joined.push_back(' '.into());
+                    }
+                    joined.extend(tile[row].iter().cloned());
+                }
+                rows.push(joined);
+            }
+            band.clear();
+        };
+
+        for counter in &mut self.counters {
+            let per_minute = format!("{}/min", counter.stats.windows(now).0.count);
+            let digits = large(counter.total);
+            let inner = [
+                counter.name.chars().count(),
+                digits[0].chars().count(),
+                per_minute.len(),
+            ]
+            .into_iter()
+            .max()
+            .unwrap_or_default();
+            // The borders and padding, and the space to the previous tile
+            let tile_width = inner + 5;
+            if used > 0 && used + tile_width > width + 1 {
+                flush(&mut band, &mut rows);
+                used = 0;
+            }
+            used += tile_width;
+
+            let line = |text: &str, style: ContentStyle| {
+                let pad = inner - text.chars().count();
+                StyledGraphemes::from_iter([
+                    StyledGraphemes::from("│ "),
+                    StyledGraphemes::from_str(text, style),
+                    StyledGraphemes::from(format!("{} │", " ".repeat(pad))),
+                ])
+            };
+            let color = (counter.stats.windows(now).0.count > 0).then_some(Color::Yellow);
+            let mut tile = vec![StyledGraphemes::from(format!(
+                "┌{}┐",
+                "─".repeat(inner + 2)
+            ))];
+            tile.push(line(&counter.name, bold(None)));
+            tile.extend(digits.iter().map(|row| line(row, bold(color))));
+            tile.push(line(&per_minute, ContentStyle::default()));
+            tile.push(StyledGraphemes::from(format!(
+                "└{}┘",
+                "─".repeat(inner + 2)
+            )));
+            band.push(tile);
+        }
+        flush(&mut band, &mut rows);
+        rows
+    }
+
+    /// Rows of the whole screen: the title, the counters, the alerts and the last matches.
+    pub fn rows(
+        &mut self,
+        alerts: &Alerts,
+        status: &str,
+        (width, height): (u16, u16),
+        highlight_style: ContentStyle,
+        case_insensitive: bool,
+        now: Instant,
+    ) -> Vec<StyledGraphemes> {
+        let width = width as usize;
+        let mut rows = vec![StyledGraphemes::from_iter([
+            StyledGraphemes::from_str(&self.title, bold(None)),
+            StyledGraphemes::from(format!("  {status}")),
+        ])];
+        rows.push(StyledGraphemes::default());
+        rows.extend(self.counter_rows(width, now));
+
+        if !alerts.is_empty() {
+            rows.push(StyledGraphemes::default());
+            for alert in &alerts.alerts {
+                let (color, state) = match &alert.firing {
+                    Some(reason) => (Color::Red, format!("FIRING {reason}")),
+                    None => (Color::Green, String::from("ok")),
+                };
+                rows.push(StyledGraphemes::from_iter([
+                    StyledGraphemes::from_str("● ", bold(Some(color))),
+                    StyledGraphemes::from_str(&alert.name, bold(None)),
+                    StyledGraphemes::from_str(format!("  {state}"), bold(Some(color))),
+                ]));
+            }
+        }
+
+        if self.lines > 0 {
+            rows.push(StyledGraphemes::default());
+            rows.push(StyledGraphemes::from_str(
+                format!("Last matches of {:?}", self.query),
+                bold(None),
+            ));
+            // Keep the last matches on screen, dropping the oldest if there is no room.
+            let room = (height as usize).saturating_sub(rows.len());
+            let skip = self.recent.len().saturating_sub(room);
+            for line in self.recent.iter().skip(skip) {
+                rows.push(
+                    highlight(&self.query, line, highlight_style, case_insensitive)
+                        .unwrap_or_else(|| StyledGraphemes::from(line.as_str())),
+                );
+            }
+        }
+
+        rows.truncate(height as usize);
+        rows.iter().map(|row| fit(row, width)).collect()
+    }
+}
+
+/// Show the dashboard until exiting, counting the lines as they come without rendering them.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    backend: &dyn Backend,
+    config: &DashboardConfig,
+    keybinds: &StreamingKeybinds,
+    archive: Archive,
+    source: &dyn Source,
+    options: SessionOptions,
+    alerts: SharedAlerts,
+    highlight_style: ContentStyle,
+    case_insensitive: bool,
+    sink: SinkSender,
+) -> anyhow::Result<(Signal, Archive)> {
+    let hint = format!(
+        "Archived({}) | Exit({})",
+        format_keybinds(&keybinds.goto_archived),
+        format_keybinds(&keybinds.exit),
+    );
+    let unlock_hint = format!("[LOCKED] Unlock({})", format_keybinds(&keybinds.unlock));
+    let retrieval_timeout = options.retrieval_timeout;
+    let mut session = Session::start(source, options)?;
+    let (stop_tx, mut stop_rx) = watch::channel(());
+    let dashboard = Arc::new(RwLock::new(Dashboard::try_new(config, Instant::now())?));
+    let writable_dashboard = Arc::clone(&dashboard);
+    let writable_alerts = Arc::clone(&alerts);
+    let query = config.query.clone();
+
+    let counting: JoinHandle<anyhow::Result<Archive>> = tokio::spawn(async move {
+        let mut archive = archive;
+        loop {
+            tokio::select! {
+                biased;
+                _ = stop_rx.changed() => break,
+                maybe_line = session.recv() => match maybe_line {
+                    Some(line) => {
+                        let now = Instant::now();
+                        writable_alerts.write().await.observe(&line, now);
+                        sink.route(&line);
+                        let matched = is_match(&query, &line, case_insensitive);
+                        if matched {
+                            sink.send(&line);
+                        }
+                        writable_dashboard.write().await.observe(&line, matched, now);
+                        archive.push(line)?;
+                    }
+                    None => break,
+                },
+            }
+        }
+        archive.add_dropped(session.dropped());
+        session.stop().await;
+        Ok(archive)
+    });
+
+    let mut refreshed: Option<Instant> = None;
+    let mut locked = false;
+    let signal = loop {
+        if refreshed.is_none_or(|refreshed| refreshed.elapsed() >= REFRESH_INTERVAL) {
+            refreshed = Some(Instant::now());
+            let now = Instant::now();
+            let fired = alerts.write().await.evaluate(now);
+            for reason in &fired {
+                audit::record("dashboard", "alert", reason);
+            }
+            let status = match (counting.is_finished(), locked) {
+                (_, true) => unlock_hint.clone(),
+                (true, false) => format!("[ENDED] {hint}"),
+                (false, false) => format!("[RUNNING] {hint}"),
+            };
+            let size = backend.size()?;
+            let rows = dashboard.write().await.rows(
+                &*alerts.read().await,
+                &status,
+                size,
+                highlight_style,
+                case_insensitive,
+                now,
+            );
+            for y in 0..size.1 {
+                backend.move_to(0, y)?;
+                match rows.get(y as usize) {
+                    Some(row) => backend.print(row)?,
+                    None => backend.clear_line()?,
+                }
+            }
+            backend.flush()?;
+        }
+
+        if !backend.poll(retrieval_timeout)? {
+            continue;
+        }
+        let event = backend.read()?;
+        if let Event::Resize(..) = event {
+            refreshed = None;
+        } else if locked {
+            if matches_keybind(&event, &keybinds.unlock) {
+                locked = false;
+                refreshed = None;
+                audit::record("dashboard", "unlock", "");
+            }
+        } else if matches_keybind(&event, &keybinds.lock) {
+            locked = true;
+            refreshed = None;
+            audit::record("dashboard", "lock", "");
+        } else if matches_keybind(&event, &keybinds.goto_archived) {
+            break Signal::GotoArchived;
+        } else if matches_keybind(&event, &keybinds.exit) {
+            break Signal::Exit {
+                query: config.query.clone(),
+                print: false,
+            };
+        }
+    };
+
+    // Closing the stop channel ends the counting task, which stops the session.
+    drop(stop_tx);
+    Ok((signal, counting.await??))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_draw_counters_in_large_print() {
+        let mut dashboard = Dashboard::try_new(
+            &DashboardConfig {
+                title: Some(String::from("checkout")),
+                query: String::from("ERROR"),
+                lines: 2,
+                counters: vec![
+                    CounterConfig {
+                        name: String::from("errors"),
+                        pattern: String::from("ERROR"),
+                    },
+                    CounterConfig {
+                        name: String::from("all"),
+                        pattern: String::from(""),
+                    },
+                ],
+            },
+            Instant::now(),
+        )
+        .unwrap();
+        let now = Instant::now();
+        for line in ["ERROR a", "INFO b", "ERROR c", "ERROR d"] {
+            dashboard.observe(line, line.starts_with("ERROR"), now);
+        }
+
+        let rows = dashboard
+            .rows(
+                &Alerts::default(),
+                "[RUNNING]",
+                (40, 20),
+                ContentStyle::default(),
+                false,
+                now,
+            )
+            .iter()
+            .map(|row| row.to_string().trim_end().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                "checkout  [RUNNING]",
+                "",
+                "┌────────┐ ┌───────┐",
+                "│ errors │ │ all   │",
+                "│ ▀▀█    │ │ █ █   │",
+                "│  ▀█    │ │ ▀▀█   │",
+                "│ ▄▄█    │ │   █   │",
+                "│ 0/min  │ │ 0/min │",
+                "└────────┘ └───────┘",
+                "",
+                "Last matches of \"ERROR\"",
+                "ERROR c",
+                "ERROR d",
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// How the lines of a `decode` stage are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Base64, with the standard or the URL-safe alphabet and optional padding.
+    Base64,
+    /// Percent-encoding (`%2F`), as in URLs and form data.
+    Url,
+    /// The escapes of a JSON string (`\n`, `\"`, `\u00e9`), with or without its quotes.
+    Json,
+}
+
+/// The decoded line, or None if it is not validly encoded (or not UTF-8 once decoded).
+pub fn decode(line: &str, encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::Base64 => String::from_utf8(base64(line.trim())?).ok(),
+        Encoding::Url => String::from_utf8(percent(line)).ok(),
+        Encoding::Json => {
+            let trimmed = line.trim();
+            if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"') {
+                serde_json::from_str(trimmed).ok()
+            } else {
+                serde_json::from_str(&format!("\"{line}\"")).ok()
+            }
+        }
+    }
+}
+
+pub(crate) fn base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in encoded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    // A single character left over cannot make a byte.
+    (count < 6).then_some(bytes)
+}
+
+fn percent(encoded: &str) -> Vec<u8> {
+    let encoded = encoded.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[i], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            // A `%` not followed by two hex digits is kept as is.
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_each_encoding() {
+        assert_eq!(
+            decode("dXNlcj1hbGljZSBvaz0x", Encoding::Base64).as_deref(),
+            Some("user=alice ok=1")
+        );
+        assert_eq!(
+            decode("aGk/Pz5+", Encoding::Base64).as_deref(),
+            Some("hi??>~")
+        );
+        assert_eq!(
+            decode("aGk_Pz5-", Encoding::Base64).as_deref(),
+            Some("hi??>~")
+        );
+        assert_eq!(decode("aGk=", Encoding::Base64).as_deref(), Some("hi"));
+        assert_eq!(decode("not base64!", Encoding::Base64), None);
+        assert_eq!(
+            decode("GET /a%20b?q=%E2%9C%93 100%", Encoding::Url).as_deref(),
+            Some("GET /a b?q=✓ 100%")
+        );
+        assert_eq!(decode("%FF", Encoding::Url), None);
+        assert_eq!(
+            decode(r#""line\ttab \"quoted\" \u00e9""#, Encoding::Json).as_deref(),
+            Some("line\ttab \"quoted\" é")
+        );
+        assert_eq!(
+            decode(r"panic\n  at main", Encoding::Json).as_deref(),
+            Some("panic\n  at main")
+        );
+        assert_eq!(decode(r"bad \x escape", Encoding::Json), None);
+    }
+}
@@ -0,0 +1,253 @@
+use std::ops::Range;
+
+use promkit_core::crossterm::style::{Attribute, Attributes, Color, ContentStyle};
+
+use crate::archive::Archive;
+
+/// What a line is in a unified diff, e.g. from `git diff` or a test failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    /// `diff --git`, `index`, `---` and `+++` lines ahead of the hunks of a file.
+    Header,
+    /// `@@ -1,4 +1,5 @@`
+    Hunk,
+    Added,
+    Removed,
+    Context,
+}
+
+impl DiffLine {
+    pub fn style(self) -> ContentStyle {
+        let (color, attributes) = match self {
+            DiffLine::Header => (None, Attributes::from(Attribute::Bold)),
+            DiffLine::Hunk => (Some(Color::Cyan), Attributes::default()),
+            DiffLine::Added => (Some(Color::Green), Attributes::default()),
+            DiffLine::Removed => (Some(Color::Red), Attributes::default()),
+            DiffLine::Context => (None, Attributes::default()),
+        };
+        ContentStyle {
+            foreground_color: color,
+            attributes,
+            ..Default::default()
+        }
+    }
+
+    fn is_body(self) -> bool {
+        matches!(
+            self,
+            DiffLine::Added | DiffLine::Removed | DiffLine::Context
+        )
+    }
+}
+
+/// Numbers of old and new lines of a hunk, from its `@@ -start,count +start,count @@` header.
+fn parse_hunk(line: &str) -> Option<(usize, usize)> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, _) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let count = |range: &str| match range.split_once(',') {
+        Some((start, count)) => start.parse::<usize>().ok().and(count.parse().ok()),
+        None => range.parse::<usize>().ok().map(|_| 1),
+    };
+    Some((count(old)?, count(new)?))
+}
+
+/// Classifier of the lines of a stream as they come, telling the lines of unified diffs
+/// from the others. The lines of a hunk are the ones its header counts,
+/// so that the lines after it are not mistaken for removals or additions.
+#[derive(Default)]
+pub struct DiffTracker {
+    // Old and new lines left in the current hunk
+    remaining: Option<(usize, usize)>,
+    // Whether the previous line was a header of a file diff, or a `---` line that may start one
+    in_header: bool,
+    // Whether the previous line was in a diff, to take in a `\ No newline at end of file`
+    in_diff: bool,
+}
+
+impl DiffTracker {
+    pub fn classify(&mut self, line: &str) -> Option<DiffLine> {
+        let kind = self.classify_inner(line);
+        self.in_diff = kind.is_some();
+        kind
+    }
+
+    fn classify_inner(&mut self, line: &str) -> Option<DiffLine> {
+        if let Some((old, new)) = self.remaining {
+            let kind = match line.chars().next() {
+                Some('-') if old > 0 => Some((DiffLine::Removed, (old - 1, new))),
+                Some('+') if new > 0 => Some((DiffLine::Added, (old, new - 1))),
+                // Trailing spaces may have been stripped from empty context lines.
+                Some(' ') | None if old > 0 && new > 0 => {
+                    Some((DiffLine::Context, (old - 1, new - 1)))
+                }
+                _ => None,
+            };
+            self.remaining = None;
+            if let Some((kind, remaining)) = kind {
+                if remaining != (0, 0) {
+                    self.remaining = Some(remaining);
+                }
+                return Some(kind);
+            }
+        }
+        if self.in_diff && line.starts_with("\\ ") {
+            return Some(DiffLine::Context);
+        }
+        if let Some(remaining) = parse_hunk(line) {
+            self.in_header = false;
+            self.remaining = (remaining != (0, 0)).then_some(remaining);
+            return Some(DiffLine::Hunk);
+        }
+
+        let starts_file = line.starts_with("diff --git ") || line.starts_with("diff -u");
+        let in_file_header = self.in_header
+            && [
+                "index ",
+                "--- ",
+                "+++ ",
+                "new file mode",
+                "deleted file mode",
+                "old mode",
+                "new mode",
+                "similarity index",
+                "rename ",
+            ]
+            .iter()
+            .any(|prefix| line.starts_with(prefix));
+        if starts_file || in_file_header {
+            self.in_header = true;
+            return Some(DiffLine::Header);
+        }
+        // A `---` line starts a plain `diff -u` only if `+++` follows, so it is left as is.
+        self.in_header = line.starts_with("--- ");
+        None
+    }
+}
+
+/// The unified diffs found across the archive, to color and fold their hunks in archived mode.
+#[derive(Default)]
+pub struct Diffs {
+    // What each line of the archive is, left empty if the archive has no diff
+    kinds: Vec<Option<DiffLine>>,
+    // Lines of each hunk, from its header, in ascending order
+    hunks: Vec<Range<usize>>,
+}
+
+impl Diffs {
+    pub fn scan(lines: &Archive) -> anyhow::Result<Self> {
+        let mut tracker = DiffTracker::default();
+        let mut diffs = Self::default();
+        lines.scan(|first, chunk| {
+            for (i, line) in chunk.iter().enumerate() {
+                let kind = tracker.classify(line);
+                diffs.push(first + i, kind);
+            }
+        })?;
+        if diffs.hunks.is_empty() {
+            diffs.kinds = Vec::new();
+        }
+        Ok(diffs)
+    }
+
+    fn push(&mut self, line: usize, kind: Option<DiffLine>) {
+        self.kinds.push(kind);
+        match kind {
+            Some(DiffLine::Hunk) => self.hunks.push(line..line + 1),
+            Some(kind) if kind.is_body() => {
+                if let Some(hunk) = self.hunks.last_mut().filter(|hunk| hunk.end == line) {
+                    hunk.end = line + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn kind(&self, line: usize) -> Option<DiffLine> {
+        self.kinds.get(line).copied().flatten()
+    }
+
+    /// Index of the hunk the line is the header or a line of.
+    pub fn hunk_at(&self, line: usize) -> Option<usize> {
+        let next = self.hunks.partition_point(|hunk| hunk.start <= line);
+        (next > 0 && self.hunks[next - 1].contains(&line)).then(|| next - 1)
+    }
+
+    pub fn hunk(&self, idx: usize) -> Range<usize> {
+        self.hunks[idx].clone()
+    }
+
+    /// Label shown after the header of a folded hunk, e.g. `⋯ 7 lines folded (+3 -1)`.
+    pub fn fold_label(&self, idx: usize) -> String {
+        let hunk = self.hunk(idx);
+        let count = |kind| {
+            self.kinds[hunk.clone()]
+                .iter()
+                .filter(|k| **k == Some(kind))
+                .count()
+        };
+        format!(
+            " ⋯ {} lines folded (+{} -{})",
+            hunk.len() - 1,
+            count(DiffLine::Added),
+            count(DiffLine::Removed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_follows_the_counts_of_the_hunk() {
+        use DiffLine::*;
+
+        let mut tracker = DiffTracker::default();
+        let kinds = [
+            "running 1 test",
+            "diff --git a/src/lib.rs b/src/lib.rs",
+            "index 3b18e51..a4c2f0d 100644",
+            "--- a/src/lib.rs",
+            "+++ b/src/lib.rs",
+            "@@ -1,3 +1,3 @@",
+            " fn main() {",
+            "-    old();",
+            "+    new();",
+            "",
+            "- not a removal, the hunk is over",
+            "--- expected",
+            "+++ actual",
+            "@@ -1 +1 @@",
+            "-1",
+            "+2",
+            "\\ No newline at end of file",
+        ]
+        .iter()
+        .map(|line| tracker.classify(line))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                None,
+                Some(Header),
+                Some(Header),
+                Some(Header),
+                Some(Header),
+                Some(Hunk),
+                Some(Context),
+                Some(Removed),
+                Some(Added),
+                Some(Context),
+                None,
+                None,
+                Some(Header),
+                Some(Hunk),
+                Some(Removed),
+                Some(Added),
+                Some(Context),
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::highlight::match_spans;
+
+/// How the lines leaving sig (see `--output` and the copies to the clipboard) are written.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ExportFormat {
+    /// The lines as they are.
+    #[default]
+    Text,
+    /// One JSON object per line, with the byte offsets and text of each match of the query.
+    Jsonl,
+}
+
+/// Write the line in the given format, along with the matches of `query` if it has room for them.
+pub fn format_line(
+    line: &str,
+    query: &str,
+    case_insensitive: bool,
+    format: ExportFormat,
+) -> String {
+    match format {
+        ExportFormat::Text => line.to_string(),
+        ExportFormat::Jsonl => {
+            let matches = match_spans(query, line, case_insensitive)
+                .into_iter()
+                .map(|span| {
+                    json!({
+                        "start": span.start,
+                        "end": span.end,
+                        "text": &line[span],
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({ "line": line, "matches": matches }).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jsonl_includes_the_matches_of_each_query() {
+        assert_eq!(
+            format_line(
+                "GET /api 503 timeout",
+                "503|time",
+                false,
+                ExportFormat::Jsonl
+            ),
+            r#"{"line":"GET /api 503 timeout","matches":[{"start":9,"end":12,"text":"503"},{"start":13,"end":17,"text":"time"}]}"#
+        );
+        assert_eq!(
+            format_line("GET /api", "", false, ExportFormat::Text),
+            "GET /api"
+        );
+    }
+}
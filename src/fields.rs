@@ -0,0 +1,266 @@
+use serde_json::Value;
+
+use crate::{formats, xml};
+
+/// Extract fields from a structured line,
+/// which is either a JSON object, an XML element (see `xml::parse`),
+/// a line of an access log, logcat or strace (see `formats::parse`)
+/// or a sequence of logfmt `key=value` pairs.
+pub fn parse(line: &str) -> Vec<(String, String)> {
+    let trimmed = line.trim();
+    if trimmed.starts_with('<') {
+        let fields = xml::parse(trimmed);
+        if !fields.is_empty() {
+            return fields;
+        }
+    }
+    if trimmed.starts_with('{') {
+        if let Ok(Value::Object(map)) = serde_json::from_str::<Value>(trimmed) {
+            return map
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(s) => (key, s),
+                    other => (key, other.to_string()),
+                })
+                .collect();
+        }
+    }
+    if let Some(fields) = formats::parse(line) {
+        return fields;
+    }
+    parse_logfmt(line)
+}
+
+/// Extract `key=value` pairs, where values may be double-quoted with `\"` escapes.
+/// Tokens without `=` are ignored.
+pub fn parse_logfmt(line: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+
+        if !key.is_empty() && !key.contains('"') {
+            fields.push((key, value));
+        }
+    }
+    fields
+}
+
+/// Value of the first field whose name is one of the given keys (case-insensitive).
+pub fn find<'a>(fields: &'a [(String, String)], keys: &[&str]) -> Option<&'a str> {
+    // The attributes of an XML element are found by their name too, e.g. `level` for `@level`.
+    fields
+        .iter()
+        .find(|(key, _)| {
+            let attribute = key.strip_prefix('@').unwrap_or(key);
+            keys.iter()
+                .any(|k| key.eq_ignore_ascii_case(k) || attribute.eq_ignore_ascii_case(k))
+        })
+        .map(|(_, value)| value.as_str())
+}
+
+/// Byte range of the value of the field `key` in the line,
+/// found after `key=` (logfmt) or `"key":` (JSON), without the quotes.
+#[cfg(feature = "tui")]
+pub fn value_span(line: &str, key: &str) -> Option<std::ops::Range<usize>> {
+    value_spans(line, key).next()
+}
+
+/// Byte ranges of the values of every occurrence of the field `key` in the line, see `value_span`.
+/// A quoted value runs to its closing quote, past spaces and escaped quotes (`\"`).
+pub fn value_spans<'a>(
+    line: &'a str,
+    key: &'a str,
+) -> impl Iterator<Item = std::ops::Range<usize>> + 'a {
+    line.match_indices(key).filter_map(move |(at, _)| {
+        let before = line[..at].chars().next_back();
+        if !matches!(before, None | Some(' ' | '\t' | '"' | '{' | ',')) {
+            return None;
+        }
+        let rest = &line[at + key.len()..];
+        // A quote before is JSON's, or a logfmt field inside a quoted value (`msg="user=bob"`).
+        let json = (before == Some('"'))
+            .then(|| rest.strip_prefix('"'))
+            .flatten()
+            .and_then(|after| after.trim_start().strip_prefix(':'));
+        let rest = match json {
+            Some(after) => after.trim_start(),
+            None => rest.strip_prefix('=')?,
+        };
+        let (quoted, rest) = match rest.strip_prefix('"') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let start = line.len() - rest.len();
+        let len = if quoted {
+            let mut escaped = false;
+            rest.char_indices()
+                .find(|&(_, c)| {
+                    let end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    end
+                })
+                .map_or(rest.len(), |(i, _)| i)
+        } else {
+            rest.find(|c: char| c.is_whitespace() || matches!(c, '"' | ',' | '}'))
+                .unwrap_or(rest.len())
+        };
+        Some(start..start + len)
+    })
+}
+
+/// The number at the start of a value, ignoring a unit such as `ms`.
+#[cfg(feature = "tui")]
+pub fn leading_number(value: &str) -> Option<f64> {
+    let len = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    value[..len].parse().ok()
+}
+
+/// Severity of a line, normalized from the common spellings of a `level` field.
+#[cfg(feature = "tui")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+#[cfg(feature = "tui")]
+impl Level {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" | "dbug" => Some(Self::Debug),
+            "info" | "information" | "notice" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" | "err" | "eror" => Some(Self::Error),
+            "fatal" | "critical" | "crit" | "panic" | "emerg" | "alert" => Some(Self::Fatal),
+            _ => None,
+        }
+    }
+
+    #[cfg(any(feature = "sinks", test))]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+            Self::Fatal => "FATAL",
+        }
+    }
+}
+
+/// Level of the line taken from its `level`/`severity` field.
+#[cfg(feature = "tui")]
+pub fn level(fields: &[(String, String)]) -> Option<Level> {
+    find(fields, &["level", "lvl", "severity"]).and_then(Level::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(fields: &[(&str, &str)]) -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_logfmt_with_quoted_values() {
+        assert_eq!(
+            parse(r#"level=info msg="hello \"sig\"" dur=12ms bare path="""#),
+            pairs(&[
+                ("level", "info"),
+                ("msg", "hello \"sig\""),
+                ("dur", "12ms"),
+                ("path", ""),
+            ])
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn value_span_in_logfmt_and_json() {
+        let line = r#"path=/api duration=132ms"#;
+        assert_eq!(&line[value_span(line, "duration").unwrap()], "132ms");
+        let line = r#"{"xduration": 1, "duration": 87.5, "msg":"ok"}"#;
+        assert_eq!(&line[value_span(line, "duration").unwrap()], "87.5");
+        assert_eq!(leading_number("132ms"), Some(132.0));
+        assert_eq!(value_span("duration 12", "duration"), None);
+    }
+
+    #[test]
+    fn parse_json_object() {
+        let mut fields = parse(r#"{"level":"warn","status":503,"ok":false}"#);
+        fields.sort();
+        assert_eq!(
+            fields,
+            pairs(&[("level", "warn"), ("ok", "false"), ("status", "503")])
+        );
+    }
+
+    /// The fields and level of each line, as the stages, columns and schema view see them.
+    #[cfg(feature = "tui")]
+    fn described(line: &str) -> String {
+        let fields = parse(line);
+        let mut out = String::new();
+        if fields.is_empty() {
+            out.push_str("  (no fields)\n");
+        }
+        for (key, value) in &fields {
+            out.push_str(&format!("  {key} = {value}\n"));
+        }
+        if let Some(level) = level(&fields) {
+            out.push_str(&format!("  level: {}\n", level.as_str()));
+        }
+        out
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn parse_matches_the_golden_files() {
+        for name in ["logfmt", "json", "xml", "access-log", "logcat", "strace"] {
+            crate::golden::check(name, described);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+/// Split a folded stack line, e.g. "main;parse;alloc 42", into the stack and its sample count.
+pub fn parse(line: &str) -> Option<(&str, u64)> {
+    let (stack, count) = line.trim_end().rsplit_once(' ')?;
+    let stack = stack.trim_end();
+    if stack.is_empty() {
+        return None;
+    }
+    Some((stack, count.parse().ok()?))
+}
+
+/// Sample counts aggregated per stack from folded stack output,
+/// e.g. `perf script | stackcollapse-perf.pl`.
+#[derive(Default)]
+pub struct FoldedStacks {
+    counts: HashMap<String, u64>,
+    total: u64,
+}
+
+impl FoldedStacks {
+    /// Add the samples of the line, ignoring lines that are not folded stacks.
+    pub fn add(&mut self, line: &str) {
+        if let Some((stack, count)) = parse(line) {
+            *self.counts.entry(stack.to_string()).or_default() += count;
+            self.total += count;
+        }
+    }
+
+    /// Total number of samples.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of distinct stacks.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// The `n` stacks with the most samples among those accepted by `filter`.
+    pub fn top(&self, n: usize, filter: impl Fn(&str) -> bool) -> Vec<(&str, u64)> {
+        let mut stacks = self
+            .counts
+            .iter()
+            .filter(|(stack, _)| filter(stack))
+            .map(|(stack, count)| (stack.as_str(), *count))
+            .collect::<Vec<(&str, u64)>>();
+        stacks.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        stacks.truncate(n);
+        stacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_aggregates_samples_per_stack() {
+        let mut stacks = FoldedStacks::default();
+        for line in [
+            "main;parse;alloc 3",
+            "main;render 5",
+            "main;parse;alloc 4",
+            "not a folded stack",
+            "main;parse 1",
+        ] {
+            stacks.add(line);
+        }
+
+        assert_eq!(stacks.total(), 13);
+        assert_eq!(stacks.len(), 3);
+        assert_eq!(
+            stacks.top(2, |_| true),
+            vec![("main;parse;alloc", 7), ("main;render", 5)]
+        );
+        assert_eq!(
+            stacks.top(10, |stack| stack.contains("parse")),
+            vec![("main;parse;alloc", 7), ("main;parse", 1)]
+        );
+    }
+
+    #[test]
+    fn parse_matches_the_golden_file() {
+        crate::golden::check("folded", |line| match parse(line) {
+            Some((stack, count)) => format!("  stack = {stack}\n  samples = {count}\n"),
+            None => String::from("  (not a folded stack)\n"),
+        });
+    }
+}
@@ -0,0 +1,129 @@
+//! Parsers of the common log formats without `key=value` pairs,
+//! tried by `fields::parse` before falling back to logfmt.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+type Fields = Vec<(String, String)>;
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid format pattern"))
+}
+
+/// The fields of a line in one of the known formats, if it is in one.
+pub fn parse(line: &str) -> Option<Fields> {
+    access_log(line)
+        .or_else(|| logcat(line))
+        .or_else(|| strace(line))
+}
+
+/// Collect the named groups of `re` matched in the line,
+/// leaving out the unmatched ones and the `-` of missing values.
+fn captures(re: &Regex, line: &str) -> Option<Fields> {
+    let captures = re.captures(line)?;
+    Some(
+        re.capture_names()
+            .flatten()
+            .filter_map(|name| {
+                let value = captures.name(name)?.as_str();
+                (value != "-").then(|| (name.to_string(), value.to_string()))
+            })
+            .collect(),
+    )
+}
+
+/// The Common and Combined Log Formats of Apache and nginx, e.g.
+/// `127.0.0.1 - alice [01/May/2024:12:00:00 +0000] "GET / HTTP/1.1" 200 512 "-" "curl/8.5.0"`.
+fn access_log(line: &str) -> Option<Fields> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = regex(
+        &RE,
+        r#"^(?P<remote_addr>\S+) \S+ (?P<user>\S+) \[(?P<time>[^\]]+)\] "(?:(?P<method>[A-Z]+) (?P<path>\S+)(?: (?P<protocol>[^"\s]+))?|[^"]*)" (?P<status>\d{3}) (?P<bytes>\d+|-)(?: "(?P<referer>(?:[^"\\]|\\.)*)" "(?P<user_agent>(?:[^"\\]|\\.)*)")?"#,
+    );
+    let mut fields = captures(re, line)?;
+    for (key, value) in &mut fields {
+        if key == "referer" || key == "user_agent" {
+            *value = value.replace("\\\"", "\"");
+        }
+    }
+    Some(fields)
+}
+
+/// The `threadtime` format of `adb logcat`, e.g.
+/// `05-01 12:00:00.123  1234  1250 I ActivityManager: Start proc`,
+/// where the priority letter is given as a `level`.
+fn logcat(line: &str) -> Option<Fields> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = regex(
+        &RE,
+        r"^(?P<time>\d\d-\d\d \d\d:\d\d:\d\d\.\d{3})\s+(?P<pid>\d+)\s+(?P<tid>\d+) (?P<priority>[VDIWEFA]) (?P<tag>.*?)\s*: (?P<msg>.*)$",
+    );
+    let mut fields = captures(re, line)?;
+    let (_, priority) = fields.iter().find(|(key, _)| key == "priority")?;
+    let level = match priority.as_str() {
+        "V" => "trace",
+        "D" => "debug",
+        "I" => "info",
+        "W" => "warn",
+        "E" => "error",
+        _ => "fatal",
+    };
+    fields.retain(|(key, _)| key != "priority");
+    fields.insert(3, (String::from("level"), level.to_string()));
+    Some(fields)
+}
+
+/// The lines of `strace`, optionally prefixed by the PID (`-f`): a system call such as
+/// `connect(5, {...}, 16) = -1 EINPROGRESS (Operation now in progress)`,
+/// a signal (`--- SIGCHLD {...} ---`) or the exit (`+++ exited with 0 +++`).
+fn strace(line: &str) -> Option<Fields> {
+    static CALL: OnceLock<Regex> = OnceLock::new();
+    static EVENT: OnceLock<Regex> = OnceLock::new();
+    let call = regex(
+        &CALL,
+        r"^(?:\[pid +(?P<pid>\d+)\] |(?P<pid_>\d+) +)?(?P<syscall>[a-z_][a-z0-9_]*)\((?P<args>.*)\) += (?P<result>-?\d+|0x[0-9a-f]+|\?)(?: (?P<errno>E[A-Z0-9]+) \((?P<error>[^)]*)\))?",
+    );
+    let event = regex(
+        &EVENT,
+        r"^(?:\[pid +(?P<pid>\d+)\] |(?P<pid_>\d+) +)?(?:--- (?P<signal>SIG[A-Z0-9]+) .*---|\+\+\+ (?:exited with (?P<exit_status>\d+)|killed by (?P<killed_by>SIG[A-Z0-9]+)).*\+\+\+)$",
+    );
+    let mut fields = captures(call, line).or_else(|| captures(event, line))?;
+    // Both spellings of the PID prefix are the `pid`.
+    for (key, _) in &mut fields {
+        if key == "pid_" {
+            *key = String::from("pid");
+        }
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(fields: Option<Fields>) -> Vec<String> {
+        fields
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    #[test]
+    fn lines_of_other_formats_are_left_to_logfmt() {
+        for line in [
+            "level=info msg=ok",
+            r#"{"level":"info"}"#,
+            "GET /index.html 200",
+            "12:00:00 I tag: message",
+            "exit(0)",
+        ] {
+            assert_eq!(parse(line), None, "{line}");
+        }
+        assert_eq!(
+            keys(parse("[pid  42] write(1, \"x\", 1) = 1")),
+            ["pid", "syscall", "args", "result"]
+        );
+    }
+}
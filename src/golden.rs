@@ -0,0 +1,30 @@
+//! Golden-file checks of the parsers, run over the sample streams in `testdata/parsers`.
+//!
+//! Each `<name>.log` is parsed line by line and compared with `<name>.golden`.
+//! After an intended change of a parser, regenerate the golden files with
+//! `SIG_UPDATE_GOLDEN=1 cargo test golden` and review their diff.
+
+use std::{fmt::Write, fs, path::PathBuf};
+
+/// Parse each line of `<name>.log` with `parse`, which describes the result
+/// in indented lines, and compare the output with `<name>.golden`.
+pub fn check(name: &str, parse: impl Fn(&str) -> String) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/parsers");
+    let input = fs::read_to_string(dir.join(format!("{name}.log"))).unwrap();
+    let mut output = String::new();
+    for line in input.lines() {
+        writeln!(output, "> {line}").unwrap();
+        output.push_str(&parse(line));
+    }
+
+    let golden = dir.join(format!("{name}.golden"));
+    if std::env::var_os("SIG_UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &output).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap();
+    assert!(
+        output == expected,
+        "{name}.log no longer parses as in {name}.golden, got:\n{output}"
+    );
+}
@@ -0,0 +1,57 @@
+use promkit_core::{
+    crossterm::style::{Color, ContentStyle},
+    grapheme::{StyledGrapheme, StyledGraphemes},
+};
+
+/// Columns taken by the gutter, including the space before the content.
+pub const WIDTH: usize = 4;
+
+/// Flags of a line, shown in the gutter ahead of its first row (see `--gutter`).
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineFlags {
+    /// Color of the source the line was read from, shown as a chip.
+    pub source: Option<Color>,
+    pub bookmark: bool,
+    /// Whether the line matched the query, rather than being passed through.
+    pub matched: bool,
+    /// Whether the line matched the pattern of a firing alert.
+    pub alert: bool,
+}
+
+impl LineFlags {
+    /// The gutter of the first row of the line, in `WIDTH` columns.
+    pub fn render(&self) -> StyledGraphemes {
+        let chip = match self.source {
+            Some(color) => flag('▌', color),
+            None => StyledGrapheme::from(' '),
+        };
+        let bookmark = if self.bookmark {
+            flag('*', Color::Yellow)
+        } else {
+            StyledGrapheme::from(' ')
+        };
+        let state = match (self.alert, self.matched) {
+            (true, _) => flag('!', Color::Red),
+            (false, true) => flag('•', Color::Green),
+            (false, false) => StyledGrapheme::from(' '),
+        };
+        [chip, bookmark, state, StyledGrapheme::from(' ')]
+            .into_iter()
+            .collect()
+    }
+
+    /// The gutter of the rows continuing the line.
+    pub fn blank() -> StyledGraphemes {
+        StyledGraphemes::from(" ".repeat(WIDTH))
+    }
+}
+
+fn flag(ch: char, color: Color) -> StyledGrapheme {
+    StyledGrapheme::new(
+        ch,
+        ContentStyle {
+            foreground_color: Some(color),
+            ..Default::default()
+        },
+    )
+}
@@ -1,9 +1,145 @@
+use std::sync::{Arc, RwLock};
+
 use grep::{
     matcher::{Match, Matcher},
     regex::RegexMatcherBuilder,
 };
 
-use promkit_core::{crossterm::style::ContentStyle, grapheme::StyledGraphemes};
+use promkit_core::{
+    crossterm::style::{Color, ContentStyle},
+    grapheme::StyledGraphemes,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use termcfg::crossterm_config::content_style_serde;
+
+use crate::{caps, fields};
+
+/// Which part of a line a highlight rule styles.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Only the matched parts.
+    #[default]
+    Match,
+    /// The whole line.
+    Line,
+}
+
+/// A style for the lines matching a pattern, configured as `[[highlights]]`.
+/// Rules are applied in addition to the highlighting of the query, which is drawn on top.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighlightConfig {
+    pub pattern: String,
+    #[serde(with = "content_style_serde")]
+    pub style: ContentStyle,
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+pub struct HighlightRule {
+    pattern: Regex,
+    config: HighlightConfig,
+}
+
+impl HighlightRule {
+    pub fn try_new(config: &HighlightConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(&config.pattern).map_err(|e| {
+                anyhow::anyhow!("Invalid pattern of highlight {:?}: {e}", config.pattern)
+            })?,
+            config: config.clone(),
+        })
+    }
+
+    pub fn config(&self) -> &HighlightConfig {
+        &self.config
+    }
+}
+
+/// The highlight rules, in the order of the configuration,
+/// so that a later rule takes precedence over an earlier one.
+#[derive(Default)]
+pub struct Highlights {
+    pub rules: Vec<HighlightRule>,
+    latency: Option<LatencyConfig>,
+}
+
+impl Highlights {
+    pub fn try_new(
+        configs: &[HighlightConfig],
+        latency: Option<LatencyConfig>,
+    ) -> anyhow::Result<Self> {
+        let rules = configs
+            .iter()
+            .map(HighlightRule::try_new)
+            .collect::<anyhow::Result<Vec<HighlightRule>>>()?;
+        Ok(Self { rules, latency })
+    }
+
+    /// Render the line with `base_style`, styled by the matching rules.
+    pub fn styled(&self, line: &str, base_style: ContentStyle) -> StyledGraphemes {
+        let mut styled = StyledGraphemes::from_str(line, base_style);
+        for rule in &self.rules {
+            let style = caps::fit_style(rule.config.style);
+            match rule.config.scope {
+                Scope::Match => {
+                    for m in rule.pattern.find_iter(line) {
+                        for i in m.start()..m.end() {
+                            styled = styled.apply_style_at(i, style);
+                        }
+                    }
+                }
+                Scope::Line => {
+                    if rule.pattern.is_match(line) {
+                        styled = styled.apply_style(style);
+                    }
+                }
+            }
+        }
+        if let Some(latency) = &self.latency {
+            let span = fields::value_span(line, &latency.field);
+            if let Some(value) = span
+                .clone()
+                .and_then(|span| fields::leading_number(&line[span]))
+            {
+                let style = ContentStyle {
+                    foreground_color: Some(latency.color(value)),
+                    ..Default::default()
+                };
+                for i in span.unwrap_or_default() {
+                    styled = styled.apply_style_at(i, style);
+                }
+            }
+        }
+        styled
+    }
+}
+
+/// Coloring of a latency field of JSON/logfmt lines, configured as `[latency]`:
+/// green below `warn`, yellow below `critical`, and red from there on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LatencyConfig {
+    pub field: String,
+    pub warn: f64,
+    pub critical: f64,
+}
+
+impl LatencyConfig {
+    fn color(&self, value: f64) -> Color {
+        if value >= self.critical {
+            Color::Red
+        } else if value >= self.warn {
+            Color::Yellow
+        } else {
+            Color::Green
+        }
+    }
+}
+
+/// Highlight rules shared with the rendering, replaced when the configuration is reloaded.
+/// This is a blocking lock because the archived mode renders synchronously.
+pub type SharedHighlights = Arc<RwLock<Highlights>>;
 
 /// Apply style to matched parts in the line.
 pub fn highlight(
@@ -11,6 +147,40 @@ pub fn highlight(
     line: &str,
     highlight_style: ContentStyle,
     case_insensitive: bool,
+) -> Option<StyledGraphemes> {
+    highlight_with_base(
+        query,
+        line,
+        ContentStyle::default(),
+        highlight_style,
+        case_insensitive,
+    )
+}
+
+/// Same as `highlight`, but the unmatched parts are rendered with `base_style`.
+pub fn highlight_with_base(
+    query: &str,
+    line: &str,
+    base_style: ContentStyle,
+    highlight_style: ContentStyle,
+    case_insensitive: bool,
+) -> Option<StyledGraphemes> {
+    highlight_over(
+        query,
+        line,
+        StyledGraphemes::from_str(line, base_style),
+        highlight_style,
+        case_insensitive,
+    )
+}
+
+/// Same as `highlight`, but the matches are drawn over `styled`, the already styled line.
+pub fn highlight_over(
+    query: &str,
+    line: &str,
+    mut styled: StyledGraphemes,
+    highlight_style: ContentStyle,
+    case_insensitive: bool,
 ) -> Option<StyledGraphemes> {
     let piped = &query
         .split('|')
@@ -18,8 +188,6 @@ pub fn highlight(
         .filter(|s| !s.is_empty())
         .collect::<Vec<&str>>();
 
-    let mut styled = StyledGraphemes::from(line);
-
     if query.is_empty() {
         Some(styled)
     } else {
@@ -41,6 +209,55 @@ pub fn highlight(
     }
 }
 
+/// Whether the line matches the query, as `highlight` tells without styling the line.
+pub fn is_match(query: &str, line: &str, case_insensitive: bool) -> bool {
+    query.is_empty() || !match_spans(query, line, case_insensitive).is_empty()
+}
+
+/// The line with the parts matching the query between `open` and `close`,
+/// for output that cannot be styled, or None if it does not match.
+pub fn mark(
+    query: &str,
+    line: &str,
+    case_insensitive: bool,
+    (open, close): (&str, &str),
+) -> Option<String> {
+    if query.trim().is_empty() {
+        return Some(line.to_string());
+    }
+    // In order and not overlapping, as the matcher finds them
+    let spans = match_spans(query, line, case_insensitive);
+    if spans.is_empty() {
+        return None;
+    }
+    let mut marked = String::with_capacity(line.len() + spans.len() * (open.len() + close.len()));
+    let mut end = 0;
+    for span in spans.into_iter().filter(|span| !span.is_empty()) {
+        marked.push_str(&line[end..span.start]);
+        marked.push_str(open);
+        marked.push_str(&line[span.clone()]);
+        marked.push_str(close);
+        end = span.end;
+    }
+    marked.push_str(&line[end..]);
+    Some(marked)
+}
+
+/// Byte ranges of the parts of the line matching the query, as they are highlighted.
+pub fn match_spans(query: &str, line: &str, case_insensitive: bool) -> Vec<std::ops::Range<usize>> {
+    let piped = query
+        .split('|')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<&str>>();
+    if piped.is_empty() {
+        return Vec::new();
+    }
+    matched(&piped, line, case_insensitive)
+        .map(|matches| matches.into_iter().map(|m| m.start()..m.end()).collect())
+        .unwrap_or_default()
+}
+
 fn matched(queries: &[&str], line: &str, case_insensitive: bool) -> anyhow::Result<Vec<Match>> {
     let mut matched = Vec::new();
     RegexMatcherBuilder::new()
@@ -55,3 +272,70 @@ fn matched(queries: &[&str], line: &str, case_insensitive: bool) -> anyhow::Resu
         })?;
     Ok(matched)
 }
+
+#[cfg(test)]
+mod tests {
+    use promkit_core::grapheme::StyledGrapheme;
+
+    use super::*;
+
+    fn fg(color: Color) -> ContentStyle {
+        ContentStyle {
+            foreground_color: Some(color),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn query_is_drawn_over_the_rules() {
+        let highlights = Highlights::try_new(
+            &[
+                HighlightConfig {
+                    pattern: String::from("WARN"),
+                    style: fg(Color::Yellow),
+                    scope: Scope::Line,
+                },
+                HighlightConfig {
+                    pattern: String::from("disk"),
+                    style: fg(Color::Blue),
+                    scope: Scope::Match,
+                },
+            ],
+            Some(LatencyConfig {
+                field: String::from("took"),
+                warn: 100.0,
+                critical: 500.0,
+            }),
+        )
+        .unwrap();
+        let line = "WARN disk full took=120ms";
+        let styled = highlight_over(
+            "full",
+            line,
+            highlights.styled(line, ContentStyle::default()),
+            fg(Color::Red),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(styled.0[0], StyledGrapheme::new('W', fg(Color::Yellow)));
+        assert_eq!(styled.0[5], StyledGrapheme::new('d', fg(Color::Blue)));
+        assert_eq!(styled.0[10], StyledGrapheme::new('f', fg(Color::Red)));
+        assert_eq!(styled.0[20], StyledGrapheme::new('1', fg(Color::Yellow)));
+        assert_eq!(styled.0[23], StyledGrapheme::new('m', fg(Color::Yellow)));
+    }
+
+    #[test]
+    fn mark_brackets_the_matches() {
+        let marks = ("[[", "]]");
+        assert_eq!(
+            mark("disk | full", "WARN disk full", false, marks).as_deref(),
+            Some("WARN [[disk]] [[full]]")
+        );
+        assert_eq!(
+            mark("warn", "WARN disk", true, marks).as_deref(),
+            Some("[[WARN]] disk")
+        );
+        assert_eq!(mark("ok", "WARN disk", false, marks), None);
+    }
+}
@@ -0,0 +1,150 @@
+use promkit_widgets::text_editor::TextEditor;
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::Pipeline;
+
+/// What to switch to when an alert starts firing, configured as `[incident]`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IncidentConfig {
+    /// Query to replace the current one with.
+    pub query: Option<String>,
+    /// Names of the pipeline stages to enable.
+    #[serde(default)]
+    pub enable: Vec<String>,
+    /// Names of the pipeline stages to disable.
+    #[serde(default)]
+    pub disable: Vec<String>,
+    /// Whether to show the unmatched lines too.
+    pub passthrough: Option<bool>,
+}
+
+/// The state from before the incident profile was applied.
+struct Saved {
+    query: String,
+    // The stages switched by the profile with whether they were enabled
+    stages: Vec<(String, bool)>,
+    passthrough: bool,
+}
+
+/// The incident profile, applied while an alert fires until it is dismissed.
+pub struct Incident {
+    config: IncidentConfig,
+    saved: Option<Saved>,
+}
+
+impl Incident {
+    pub fn new(config: IncidentConfig) -> Self {
+        Self {
+            config,
+            saved: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Apply the profile, saving what it changes.
+    /// Returns false if it is already applied.
+    pub fn apply(
+        &mut self,
+        editor: &mut TextEditor,
+        pipeline: &mut Pipeline,
+        passthrough: &mut bool,
+    ) -> bool {
+        if self.is_active() {
+            return false;
+        }
+
+        let mut saved = Saved {
+            query: editor.text_without_cursor().to_string(),
+            stages: Vec::new(),
+            passthrough: *passthrough,
+        };
+        if let Some(query) = &self.config.query {
+            *editor = TextEditor::new(query.clone());
+        }
+        let switches = self
+            .config
+            .enable
+            .iter()
+            .map(|name| (name, true))
+            .chain(self.config.disable.iter().map(|name| (name, false)));
+        for (name, enabled) in switches {
+            if let Some(stage) = pipeline.stages.iter_mut().find(|stage| &stage.name == name) {
+                saved.stages.push((stage.name.clone(), stage.enabled));
+                stage.enabled = enabled;
+            }
+        }
+        if let Some(value) = self.config.passthrough {
+            *passthrough = value;
+        }
+        self.saved = Some(saved);
+        true
+    }
+
+    /// Restore the state from before the profile was applied.
+    /// Returns false if it is not applied.
+    pub fn dismiss(
+        &mut self,
+        editor: &mut TextEditor,
+        pipeline: &mut Pipeline,
+        passthrough: &mut bool,
+    ) -> bool {
+        let Some(saved) = self.saved.take() else {
+            return false;
+        };
+
+        if self.config.query.is_some() {
+            *editor = TextEditor::new(saved.query);
+        }
+        for (name, enabled) in saved.stages.into_iter().rev() {
+            if let Some(stage) = pipeline.stages.iter_mut().find(|stage| stage.name == name) {
+                stage.enabled = enabled;
+            }
+        }
+        *passthrough = saved.passthrough;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{StageConfig, StageKind};
+
+    #[test]
+    fn dismiss_restores_the_state_from_before_apply() {
+        let mut pipeline = Pipeline::try_new(&[StageConfig {
+            name: String::from("errors"),
+            kind: StageKind::Include {
+                pattern: String::from("ERROR"),
+                field: None,
+            },
+            enabled: false,
+            toggle: Default::default(),
+        }])
+        .unwrap();
+        let mut editor = TextEditor::new("GET /api");
+        let mut passthrough = true;
+        let mut incident = Incident::new(IncidentConfig {
+            query: Some(String::from("timeout")),
+            enable: vec![String::from("errors")],
+            disable: vec![],
+            passthrough: Some(false),
+        });
+
+        assert!(incident.apply(&mut editor, &mut pipeline, &mut passthrough));
+        assert_eq!(editor.text_without_cursor().to_string(), "timeout");
+        assert!(pipeline.stages[0].enabled);
+        assert!(!passthrough);
+        // Firing again keeps the state to restore.
+        assert!(!incident.apply(&mut editor, &mut pipeline, &mut passthrough));
+
+        assert!(incident.dismiss(&mut editor, &mut pipeline, &mut passthrough));
+        assert_eq!(editor.text_without_cursor().to_string(), "GET /api");
+        assert!(!pipeline.stages[0].enabled);
+        assert!(passthrough);
+        assert!(!incident.is_active());
+    }
+}
@@ -0,0 +1,301 @@
+use std::ops::Range;
+
+use promkit_core::{
+    crossterm::style::{Color, ContentStyle},
+    grapheme::{StyledGrapheme, StyledGraphemes},
+};
+use serde::{Deserialize, Serialize};
+
+/// Columns to scroll horizontally at a time in truncate mode.
+const SCROLL_COLUMNS: usize = 8;
+
+/// Marks the rows continuing a line wrapped at the width.
+const WRAP_MARKER: &str = "↪";
+
+/// How lines longer than the width are laid out.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wrap lines into as many rows as needed.
+    #[default]
+    Wrap,
+    /// Cut lines into a single row, scrolled right by the given columns.
+    Truncate(usize),
+}
+
+/// Where the query editor and the status bar are placed on the screen.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditorPosition {
+    /// Above the stream area.
+    #[default]
+    Top,
+    /// Below the stream area, with the query editor on the last row.
+    Bottom,
+}
+
+impl Overflow {
+    /// Switch between wrapping and truncation.
+    pub fn toggle(self) -> Self {
+        match self {
+            Overflow::Wrap => Overflow::Truncate(0),
+            Overflow::Truncate(_) => Overflow::Wrap,
+        }
+    }
+
+    pub fn scroll_left(self) -> Self {
+        match self {
+            Overflow::Truncate(scroll) => Overflow::Truncate(scroll.saturating_sub(SCROLL_COLUMNS)),
+            Overflow::Wrap => Overflow::Wrap,
+        }
+    }
+
+    pub fn scroll_right(self) -> Self {
+        match self {
+            Overflow::Truncate(scroll) => Overflow::Truncate(scroll + SCROLL_COLUMNS),
+            Overflow::Wrap => Overflow::Wrap,
+        }
+    }
+
+    /// Lay out a (possibly multi-line) item into terminal rows.
+    pub fn rows(self, item: &StyledGraphemes, width: usize, height: usize) -> Vec<StyledGraphemes> {
+        match self {
+            Overflow::Wrap => rows(item, width, height),
+            Overflow::Truncate(scroll) => lines(item)
+                .iter()
+                .map(|line| truncate(line, width, scroll))
+                .collect(),
+        }
+    }
+}
+
+/// Lay out a (possibly multi-line) item into terminal rows,
+/// breaking it at newlines and wrapping at the given width.
+pub fn rows(item: &StyledGraphemes, width: usize, height: usize) -> Vec<StyledGraphemes> {
+    if !item.chars().contains(&'\n') {
+        return item.matrixify(width, height, 0).0;
+    }
+
+    lines(item)
+        .iter()
+        .flat_map(|line| wrap(line, width, height))
+        .collect()
+}
+
+/// Cut a line into a single row of the width, skipping the first `scroll` columns.
+/// An ellipsis marks the parts cut off on either side.
+pub fn truncate(line: &StyledGraphemes, width: usize, scroll: usize) -> StyledGraphemes {
+    let mut skipped = 0;
+    let rest = line
+        .iter()
+        .skip_while(|g| {
+            let skip = skipped < scroll;
+            if skip {
+                skipped += g.width();
+            }
+            skip
+        })
+        .collect::<Vec<&StyledGrapheme>>();
+    let rest_width = rest.iter().map(|g| g.width()).sum::<usize>();
+
+    let left = usize::from(skipped > 0);
+    let right = usize::from(left + rest_width > width);
+    let available = width.saturating_sub(left + right);
+
+    let mut truncated = StyledGraphemes::default();
+    if left > 0 && width > 0 {
+        truncated.push_back(StyledGrapheme::from('…'));
+    }
+    let mut used = 0;
+    for g in rest {
+        if used + g.width() > available {
+            break;
+        }
+        used += g.width();
+        truncated.push_back(g.clone());
+    }
+    if right > 0 && width > left {
+        truncated.push_back(StyledGrapheme::from('…'));
+    }
+    truncated
+}
+
+/// Cut each line of a (possibly multi-line) item as `truncate` does, keeping the newlines.
+pub fn truncate_lines(item: &StyledGraphemes, width: usize, scroll: usize) -> StyledGraphemes {
+    let newline = StyledGraphemes::from("\n");
+    let mut truncated = StyledGraphemes::default();
+    for (i, line) in lines(item).iter().enumerate() {
+        if i > 0 {
+            truncated.extend(newline.iter().cloned());
+        }
+        truncated.extend(truncate(line, width, scroll).iter().cloned());
+    }
+    truncated
+}
+
+/// Indent the continuation lines of a multi-line item by the given number of columns.
+pub fn indent(item: StyledGraphemes, columns: usize) -> StyledGraphemes {
+    let chars = item.chars();
+    if columns == 0 || !chars.contains(&'\n') {
+        return item;
+    }
+
+    let padding = StyledGraphemes::from(" ".repeat(columns));
+    let mut indented = StyledGraphemes::default();
+    for (ch, g) in chars.iter().zip(item.iter()) {
+        indented.push_back(g.clone());
+        if *ch == '\n' {
+            indented.extend(padding.iter().cloned());
+        }
+    }
+    indented
+}
+
+/// Wrap each line of a (possibly multi-line) item at the width, joining the rows with newlines.
+/// The rows after the first one are indented by the given number of columns,
+/// with the rows continuing a wrapped line marked in the indentation.
+pub fn wrap_marked(item: &StyledGraphemes, width: usize, columns: usize) -> StyledGraphemes {
+    if width == 0 || columns == 0 {
+        return indent(item.clone(), columns);
+    }
+
+    let padding = StyledGraphemes::from(" ".repeat(columns));
+    let marker = StyledGraphemes::from_iter([
+        &StyledGraphemes::from_str(
+            WRAP_MARKER,
+            ContentStyle {
+                foreground_color: Some(Color::DarkGrey),
+                ..Default::default()
+            },
+        ),
+        &StyledGraphemes::from(" ".repeat(columns.saturating_sub(1))),
+    ]);
+    let newline = StyledGraphemes::from("\n");
+    let mut wrapped = StyledGraphemes::default();
+    for (i, line) in lines(item).iter().enumerate() {
+        for (j, row) in wrap(line, width, usize::MAX).iter().enumerate() {
+            if i > 0 || j > 0 {
+                wrapped.extend(newline.iter().cloned());
+                let prefix = if j > 0 { &marker } else { &padding };
+                wrapped.extend(prefix.iter().cloned());
+            }
+            wrapped.extend(row.iter().cloned());
+        }
+    }
+    wrapped
+}
+
+/// Lay out a vertical scrollbar of the height for a list of `len` items,
+/// with the thumb over the items in view and the marked items (e.g. matches) as ticks,
+/// denser where more of the items a row stands for are marked.
+pub fn scrollbar(
+    height: usize,
+    len: usize,
+    viewport: Range<usize>,
+    marks: &[usize],
+) -> Vec<StyledGrapheme> {
+    (0..height)
+        .map(|row| {
+            let start = row * len / height;
+            let end = ((row + 1) * len / height).max(start + 1);
+            let thumb = start < viewport.end && viewport.start < end;
+            let marked =
+                marks.partition_point(|&m| m < end) - marks.partition_point(|&m| m < start);
+            let tick = match marked * 3 / (end - start) {
+                _ if marked == 0 => ' ',
+                0 => '·',
+                1 => '•',
+                _ => '●',
+            };
+            StyledGrapheme::new(
+                tick,
+                ContentStyle {
+                    foreground_color: Some(Color::Red),
+                    background_color: Some(if thumb { Color::Grey } else { Color::DarkGrey }),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
+}
+
+/// Cut the row to the width, padding it with spaces to overwrite what was drawn before.
+pub fn fit(row: &StyledGraphemes, width: usize) -> StyledGraphemes {
+    let mut used = 0;
+    let mut fitted = row
+        .iter()
+        .take_while(|g| {
+            used += g.width();
+            used <= width
+        })
+        .cloned()
+        .collect::<StyledGraphemes>();
+    let rest = width.saturating_sub(fitted.widths());
+    fitted.extend(std::iter::repeat_n(StyledGrapheme::from(' '), rest));
+    fitted
+}
+
+/// Break an item at newlines.
+fn lines(item: &StyledGraphemes) -> Vec<StyledGraphemes> {
+    let mut lines = Vec::new();
+    let mut line = StyledGraphemes::default();
+    for (ch, g) in item.chars().iter().zip(item.iter()) {
+        if *ch == '\n' {
+            lines.push(line);
+            line = StyledGraphemes::default();
+        } else {
+            line.push_back(g.clone());
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+fn wrap(line: &StyledGraphemes, width: usize, height: usize) -> Vec<StyledGraphemes> {
+    if line.is_empty() {
+        // Keep blank lines inside a record visible.
+        return vec![StyledGraphemes::default()];
+    }
+    line.matrixify(width, height, 0).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_marks_cut_off_parts() {
+        let line = StyledGraphemes::from("0123456789");
+        assert_eq!(truncate(&line, 20, 0).to_string(), "0123456789");
+        assert_eq!(truncate(&line, 6, 0).to_string(), "01234…");
+        assert_eq!(truncate(&line, 6, 3).to_string(), "…3456…");
+        assert_eq!(truncate(&line, 6, 6).to_string(), "…6789");
+
+        let record = StyledGraphemes::from("first line\nsecond line");
+        let rows = Overflow::Truncate(0).rows(&record, 6, 10);
+        assert_eq!(
+            rows.iter().map(|row| row.to_string()).collect::<Vec<_>>(),
+            vec!["first…", "secon…"]
+        );
+    }
+
+    #[test]
+    fn scrollbar_shows_viewport_and_match_density() {
+        let bar = scrollbar(4, 40, 10..20, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 35]);
+        assert_eq!(
+            bar.iter().cloned().collect::<StyledGraphemes>().to_string(),
+            "●  ·"
+        );
+        // Only the second row is in view, which is drawn differently from the third.
+        assert_ne!(bar[1], bar[2]);
+    }
+
+    #[test]
+    fn wrap_marked_marks_only_soft_wrapped_rows() {
+        let record = StyledGraphemes::from("0123456\nab");
+        let rows = rows(&wrap_marked(&record, 4, 2), 6, 10);
+        assert_eq!(
+            rows.iter().map(|row| row.to_string()).collect::<Vec<_>>(),
+            vec!["0123", "↪ 456", "  ab"]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Interactive grep (for streaming).
+//!
+//! Besides the `sig` binary, the streaming filter can be embedded into other tools:
+//! a [`Session`] reads lines from a [`Source`] and runs them through the processing stages,
+//! and [`styled`] highlights the parts of a line matching a query.
+//!
+//! ```no_run
+//! use promkit_core::crossterm::style::{Attribute, Attributes, ContentStyle};
+//! use sig::{source, Session, SessionOptions};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut session = Session::start(
+//!     &source::Cmd(String::from("kubectl logs -f deploy/app")),
+//!     SessionOptions::default(),
+//! )?;
+//! let style = ContentStyle {
+//!     attributes: Attributes::from(Attribute::Bold),
+//!     ..Default::default()
+//! };
+//! while let Some((line, _styled)) = session.next_match("ERROR", style, false).await {
+//!     println!("{line}");
+//! }
+//! session.stop().await;
+//! # Ok(())
+//! # }
+//! ```
+
+/// Declare the items of the terminal UI, which are left out without the `tui` feature
+/// so that the pipeline builds without crossterm (e.g. for a wasm frontend).
+macro_rules! cfg_tui {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "tui")]
+            $item
+        )*
+    };
+}
+
+mod decode;
+mod fields;
+mod formats;
+#[cfg(all(test, feature = "tui"))]
+mod golden;
+mod mask;
+pub use mask::Mask;
+pub mod pipeline;
+pub use pipeline::{Pipeline, SharedPipeline};
+mod session;
+pub use session::{Session, SessionOptions};
+pub mod source;
+pub use source::Source;
+mod spawn;
+pub use spawn::{DropPolicy, FilterMode, InputTask, Records};
+mod xml;
+
+cfg_tui! {
+    use std::{
+        io,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use promkit_core::crossterm::{
+        self, cursor, execute,
+        terminal::{disable_raw_mode, enable_raw_mode},
+    };
+
+    mod alert;
+    mod archive;
+    mod archived;
+    mod audit;
+    mod backend;
+    mod caps;
+    #[doc(hidden)]
+    pub mod cli;
+    mod clipboard;
+    mod compare;
+    mod config;
+    mod crypt;
+    mod dashboard;
+    mod diff;
+    mod export;
+    mod folded;
+    mod gutter;
+    mod highlight;
+    pub use highlight::highlight as styled;
+    mod incident;
+    mod layout;
+    mod minimap;
+    mod mouse;
+    use mouse::{DisableAlternateScrollCapture, EnableAlternateScrollCapture};
+    mod rules;
+    mod schema;
+    mod sequence;
+    #[cfg(all(unix, feature = "server"))]
+    mod server;
+    mod sink;
+    mod slots;
+    mod stats;
+    mod streaming;
+    mod terminal;
+    mod timestamp;
+    mod words;
+
+    /// Whether the terminal is currently set up by `enter_terminal`.
+    static ENTERED: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Eq, PartialEq)]
+    pub(crate) enum Signal {
+        GotoArchived,
+        GotoStreaming,
+        /// Quit with the last query, printing the lines if requested (see `--output`).
+        Exit {
+            query: String,
+            print: bool,
+        },
+        /// Quit, printing the lines picked with Enter (see `--pick`).
+        Pick(Vec<String>),
+        /// Quit without picking a line, exiting with status 1 (see `--pick`).
+        Abort,
+    }
+
+    /// Enter the alternate screen and enable alternate scroll capture mode,
+    /// as far as the terminal is able to (see `caps`).
+    pub(crate) fn enter_terminal() -> anyhow::Result<()> {
+        let caps = caps::get();
+        enable_raw_mode()?;
+        if caps.alt_screen {
+            execute!(io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+        } else {
+            execute!(
+                io::stdout(),
+                crossterm::terminal::Clear(crossterm::terminal::ClearType::All)
+            )?;
+        }
+        if caps.mouse {
+            execute!(io::stdout(), EnableAlternateScrollCapture)?;
+        }
+        execute!(io::stdout(), cursor::Hide)?;
+        ENTERED.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Leave the alternate screen and disable alternate scroll capture mode.
+    /// This does nothing unless the terminal was entered, e.g. in headless mode.
+    pub(crate) fn leave_terminal() -> anyhow::Result<()> {
+        if !ENTERED.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let caps = caps::get();
+        disable_raw_mode()?;
+        if caps.mouse {
+            execute!(io::stdout(), DisableAlternateScrollCapture)?;
+        }
+        if caps.alt_screen {
+            execute!(io::stdout(), crossterm::terminal::LeaveAlternateScreen)?;
+        }
+        execute!(io::stdout(), cursor::Show)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::hash_map::RandomState, hash::BuildHasher, net::Ipv4Addr};
+
+use crate::fields;
+
+/// Fake values for the fields given to `--demo-mask`, so that real streams can be shown
+/// in public demos and screen shares without leaking IPs or user IDs.
+///
+/// A value is always faked the same way for the session, so that the lines of the same user
+/// can still be followed, but differently from a session to the next.
+#[derive(Clone)]
+pub struct Mask {
+    fields: Vec<String>,
+    // Random per session, so that fake values cannot be matched across sessions
+    salt: RandomState,
+}
+
+impl Mask {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            salt: RandomState::new(),
+        }
+    }
+
+    /// Replace the values of every occurrence of the masked fields in the line.
+    pub fn apply(&self, line: String) -> String {
+        let mut spans = self
+            .fields
+            .iter()
+            .flat_map(|field| fields::value_spans(&line, field))
+            .filter(|span| !span.is_empty())
+            .collect::<Vec<_>>();
+        if spans.is_empty() {
+            return line;
+        }
+        // Outer spans first, so that a value nested in one already masked is skipped.
+        spans.sort_unstable_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+        let mut masked = String::with_capacity(line.len());
+        let mut end = 0;
+        for span in spans {
+            if span.start < end {
+                continue;
+            }
+            masked.push_str(&line[end..span.start]);
+            masked.push_str(&self.fake(&line[span.clone()]));
+            end = span.end;
+        }
+        masked.push_str(&line[end..]);
+        masked
+    }
+
+    /// A fake value of the same shape: an IPv4 address in 10.0.0.0/8 for an IPv4 address,
+    /// otherwise digits for digits and letters for letters, keeping the punctuation.
+    pub fn fake(&self, value: &str) -> String {
+        let mut state = self.salt.hash_one(value);
+
+        if value.parse::<Ipv4Addr>().is_ok() {
+            let [_, b, c, d, ..] = state.to_le_bytes();
+            return Ipv4Addr::new(10, b, c, d).to_string();
+        }
+        value
+            .chars()
+            .map(|c| {
+                // splitmix64, to draw a new number per character
+                state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                let n = z ^ (z >> 31);
+                if c.is_ascii_digit() {
+                    char::from(b'0' + (n % 10) as u8)
+                } else if c.is_ascii_lowercase() {
+                    char::from(b'a' + (n % 26) as u8)
+                } else if c.is_ascii_uppercase() {
+                    char::from(b'A' + (n % 26) as u8)
+                } else if c.is_alphanumeric() {
+                    'x'
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_fakes_fields_consistently() {
+        let mask = Mask::new(vec![String::from("ip"), String::from("user")]);
+        let first = mask.apply(String::from("ip=192.168.1.20 user=Alice42 msg=login"));
+        let second = mask.apply(String::from(
+            r#"{"msg":"logout","user":"Alice42","ip":"192.168.1.20"}"#,
+        ));
+
+        let parsed = fields::parse(&first);
+        let (ip, user) = (
+            fields::find(&parsed, &["ip"]).unwrap(),
+            fields::find(&parsed, &["user"]).unwrap(),
+        );
+        assert!(ip.starts_with("10.") && ip.parse::<Ipv4Addr>().is_ok());
+        assert_ne!(user, "Alice42");
+        assert!(user.starts_with(|c: char| c.is_ascii_uppercase()) && user.len() == 7);
+        assert!(first.ends_with(" msg=login"));
+        assert_eq!(
+            second,
+            format!(r#"{{"msg":"logout","user":"{user}","ip":"{ip}"}}"#)
+        );
+        assert_ne!(mask.fake("Alice42"), mask.fake("Alice43"));
+    }
+
+    #[test]
+    fn apply_fakes_quoted_values_whole() {
+        let mask = Mask::new(vec![String::from("user")]);
+        for line in [
+            r#"user="Alice Smith" msg=x"#,
+            r#"{"user": "Alice Smith", "msg": "x"}"#,
+            r#"user="Alice \"Al\" Smith" msg=x"#,
+            r#"{"user":"Alice \"Al\" Smith","msg":"x"}"#,
+        ] {
+            let masked = mask.apply(line.to_string());
+            assert!(
+                !masked.contains("Alice") && !masked.contains("Smith"),
+                "{masked}"
+            );
+            assert!(!masked.contains("Al\\"), "{masked}");
+            assert!(masked.contains("msg"), "{masked}");
+            assert_eq!(masked.len(), line.len(), "{masked}");
+        }
+        let line = r#"user="a \"b\" c" x"#;
+        let spans = fields::value_spans(line, "user").collect::<Vec<_>>();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(&line[spans[0].clone()], r#"a \"b\" c"#);
+    }
+
+    #[test]
+    fn apply_fakes_every_occurrence() {
+        let mask = Mask::new(vec![String::from("user")]);
+        let masked = mask.apply(String::from(
+            r#"user=alice from=bob user=carol msg="login user=dave""#,
+        ));
+        for name in ["alice", "carol", "dave"] {
+            assert!(!masked.contains(name), "{masked}");
+        }
+        assert!(masked.contains("from=bob"));
+        let json = mask.apply(String::from(r#"{"user":"alice","peer":{"user":"carol"}}"#));
+        assert!(!json.contains("alice") && !json.contains("carol"), "{json}");
+        // The same value is faked the same way wherever it is.
+        let twice = mask.apply(String::from("user=alice user=alice"));
+        let (first, second) = twice.split_once(' ').unwrap();
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,105 @@
+use promkit_core::{
+    crossterm::style::{Color, ContentStyle},
+    grapheme::{StyledGrapheme, StyledGraphemes},
+};
+use rayon::prelude::*;
+
+use crate::{
+    archive::Archive,
+    fields::{self, Level},
+};
+
+/// Columns taken by the minimap, including the edge between it and the lines.
+pub const WIDTH: usize = 9;
+
+/// Compressed overview of the archive shown beside the lines in archived mode,
+/// each row standing for as many lines as it takes to fit the whole view in the height.
+pub struct Minimap {
+    // Severity of each line of the archive, parsed once as the archive does not change
+    levels: Vec<Option<Level>>,
+}
+
+impl Minimap {
+    pub fn try_new(lines: &Archive) -> anyhow::Result<Self> {
+        let mut levels = Vec::with_capacity(lines.len());
+        lines.scan(|_, chunk| {
+            levels.par_extend(
+                chunk
+                    .par_iter()
+                    .map(|line| fields::level(&fields::parse(line))),
+            );
+        })?;
+        Ok(Self { levels })
+    }
+
+    pub fn level(&self, line: usize) -> Option<Level> {
+        self.levels.get(line).copied().flatten()
+    }
+}
+
+/// Number of lines of the view a row of the minimap stands for.
+pub fn lines_per_row(len: usize, height: usize) -> usize {
+    len.div_ceil(height.max(1)).max(1)
+}
+
+/// Lay out a row of the minimap for `size` lines, `matched` of which match the query:
+/// a bar as long as the share of the matched lines, colored by the highest level of the lines.
+/// The edge is highlighted for the rows in view.
+pub fn row(matched: usize, size: usize, level: Option<Level>, in_view: bool) -> StyledGraphemes {
+    let edge = if in_view {
+        style('┃', Color::White)
+    } else {
+        style('│', Color::DarkGrey)
+    };
+    let color = match level {
+        Some(Level::Fatal | Level::Error) => Color::Red,
+        Some(Level::Warn) => Color::Yellow,
+        Some(Level::Info) => Color::Green,
+        Some(Level::Debug | Level::Trace) => Color::Blue,
+        None => Color::DarkGrey,
+    };
+    let bar_width = WIDTH - 1;
+    let filled = if size == 0 {
+        0
+    } else {
+        (matched * bar_width).div_ceil(size)
+    };
+    std::iter::once(edge)
+        .chain((0..bar_width).map(|i| {
+            if size == 0 {
+                StyledGrapheme::from(' ')
+            } else if i < filled {
+                style('█', color)
+            } else {
+                style('░', color)
+            }
+        }))
+        .collect()
+}
+
+fn style(ch: char, color: Color) -> StyledGrapheme {
+    StyledGrapheme::new(
+        ch,
+        ContentStyle {
+            foreground_color: Some(color),
+            ..Default::default()
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_fills_the_share_of_matched_lines() {
+        assert_eq!(row(0, 100, None, false).to_string(), "│░░░░░░░░");
+        assert_eq!(row(1, 100, None, false).to_string(), "│█░░░░░░░");
+        assert_eq!(
+            row(50, 100, Some(Level::Error), true).to_string(),
+            "┃████░░░░"
+        );
+        assert_eq!(row(0, 0, None, false).to_string(), "│        ");
+        assert_eq!(lines_per_row(1000, 12), 84);
+    }
+}
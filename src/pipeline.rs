@@ -0,0 +1,424 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "tui")]
+use promkit_core::crossterm::event::Event;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
+use termcfg::crossterm_config::event_set_serde;
+use tokio::sync::RwLock;
+
+#[cfg(feature = "tui")]
+use crate::config::matches_keybind;
+pub use crate::decode::Encoding;
+use crate::{decode, fields};
+
+/// A named processing step of the pipeline, configured as `[[pipeline]]`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StageConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: StageKind,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Keys to enable/disable this stage while streaming.
+    #[cfg(feature = "tui")]
+    #[serde(default, with = "event_set_serde")]
+    pub toggle: HashSet<Event>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum StageKind {
+    /// Keep only the lines matching the pattern,
+    /// or whose field matches it if a field is given (see `fields::parse`).
+    Include {
+        pattern: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        field: Option<String>,
+    },
+    /// Drop the lines matching the pattern, or whose field matches it.
+    Exclude {
+        pattern: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        field: Option<String>,
+    },
+    /// Rewrite the matched parts, where `$1`, `$name` refer to capture groups.
+    Replace {
+        pattern: String,
+        replacement: String,
+    },
+    /// Decode the lines, keeping the ones that are not validly encoded as they are.
+    Decode { encoding: Encoding },
+    /// Project the named fields of JSON/logfmt lines into `key=value` pairs.
+    Fields { fields: Vec<String> },
+    /// Keep only the first occurrence of each line over the session,
+    /// comparing the lines with their numbers masked (see `fingerprint`).
+    Unique,
+    /// Append how many times the line was seen before, comparing as `unique` does.
+    Seen,
+}
+
+enum Op {
+    // The field the pattern is matched against, if not the whole line
+    Include(Regex, Option<String>),
+    Exclude(Regex, Option<String>),
+    Replace(Regex, String),
+    Decode(Encoding),
+    Fields(Vec<String>),
+    Unique(HashSet<u64>),
+    Seen(HashMap<u64, u64>),
+}
+
+/// Hash of the line where runs of digits and of whitespace are collapsed,
+/// so that lines differing only by ids, counts or durations are the same.
+fn fingerprint(line: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut prev = None;
+    for c in line.trim().chars() {
+        let c = if c.is_ascii_digit() {
+            '0'
+        } else if c.is_whitespace() {
+            ' '
+        } else {
+            c
+        };
+        if (c == '0' || c == ' ') && prev == Some(c) {
+            continue;
+        }
+        c.hash(&mut hasher);
+        prev = Some(c);
+    }
+    hasher.finish()
+}
+
+/// Whether the line, or the value of its field if given, matches the pattern.
+/// A line without the field does not match.
+fn is_match(re: &Regex, field: Option<&str>, line: &str) -> bool {
+    match field {
+        Some(field) => {
+            fields::find(&fields::parse(line), &[field]).is_some_and(|value| re.is_match(value))
+        }
+        None => re.is_match(line),
+    }
+}
+
+/// What a stage has done so far, shown in the pipeline inspector.
+#[derive(Clone, Copy, Default)]
+pub struct StageStats {
+    /// Lines that entered the stage.
+    pub lines: u64,
+    /// Lines the stage dropped.
+    pub dropped: u64,
+    /// Total time spent in the stage.
+    pub elapsed: Duration,
+}
+
+impl StageStats {
+    /// Average time spent per line.
+    pub fn latency(&self) -> Option<Duration> {
+        (self.lines > 0)
+            .then(|| Duration::from_nanos((self.elapsed.as_nanos() / self.lines as u128) as u64))
+    }
+}
+
+pub struct Stage {
+    pub name: String,
+    pub enabled: bool,
+    pub stats: StageStats,
+    op: Op,
+    #[cfg(feature = "tui")]
+    toggle: HashSet<Event>,
+    config: StageConfig,
+}
+
+impl Stage {
+    pub fn try_new(config: &StageConfig) -> anyhow::Result<Self> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid pattern in stage {}: {e}", config.name))
+        };
+        let op = match &config.kind {
+            StageKind::Include { pattern, field } => Op::Include(compile(pattern)?, field.clone()),
+            StageKind::Exclude { pattern, field } => Op::Exclude(compile(pattern)?, field.clone()),
+            StageKind::Replace {
+                pattern,
+                replacement,
+            } => Op::Replace(compile(pattern)?, replacement.clone()),
+            StageKind::Decode { encoding } => Op::Decode(*encoding),
+            StageKind::Fields { fields } => Op::Fields(fields.clone()),
+            StageKind::Unique => Op::Unique(HashSet::new()),
+            StageKind::Seen => Op::Seen(HashMap::new()),
+        };
+        Ok(Stage {
+            name: config.name.clone(),
+            enabled: config.enabled,
+            stats: StageStats::default(),
+            op,
+            #[cfg(feature = "tui")]
+            toggle: config.toggle.clone(),
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the stage was created from.
+    pub fn config(&self) -> &StageConfig {
+        &self.config
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self.op {
+            Op::Include(..) => "include",
+            Op::Exclude(..) => "exclude",
+            Op::Replace(..) => "replace",
+            Op::Decode(_) => "decode",
+            Op::Fields(_) => "fields",
+            Op::Unique(_) => "unique",
+            Op::Seen(_) => "seen",
+        }
+    }
+
+    fn apply(&mut self, line: String) -> Option<String> {
+        match &mut self.op {
+            Op::Include(re, field) => is_match(re, field.as_deref(), &line).then_some(line),
+            Op::Exclude(re, field) => (!is_match(re, field.as_deref(), &line)).then_some(line),
+            Op::Replace(re, replacement) => {
+                Some(re.replace_all(&line, replacement.as_str()).into_owned())
+            }
+            Op::Decode(encoding) => Some(decode::decode(&line, *encoding).unwrap_or(line)),
+            Op::Fields(names) => {
+                let parsed = fields::parse(&line);
+                let projected = names
+                    .iter()
+                    .filter_map(|name| {
+                        fields::find(&parsed, &[name]).map(|value| format!("{name}={value}"))
+                    })
+                    .collect::<Vec<String>>();
+                if projected.is_empty() {
+                    Some(line)
+                } else {
+                    Some(projected.join(" "))
+                }
+            }
+            Op::Unique(seen) => seen.insert(fingerprint(&line)).then_some(line),
+            Op::Seen(counts) => {
+                let count = counts.entry(fingerprint(&line)).or_default();
+                *count += 1;
+                match *count - 1 {
+                    0 => Some(line),
+                    1 => Some(format!("{line} (seen once before)")),
+                    n => Some(format!("{line} (seen {n} times before)")),
+                }
+            }
+        }
+    }
+}
+
+/// Ordered stages applied to every line before it is archived, matched and sent to sinks.
+#[derive(Default)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+}
+
+pub type SharedPipeline = Arc<RwLock<Pipeline>>;
+
+impl Pipeline {
+    pub fn try_new(configs: &[StageConfig]) -> anyhow::Result<Self> {
+        let stages = configs
+            .iter()
+            .map(Stage::try_new)
+            .collect::<anyhow::Result<Vec<Stage>>>()?;
+        Ok(Self { stages })
+    }
+
+    /// Run the line through the enabled stages in order, recording their stats.
+    /// Returns None if a stage dropped the line.
+    pub fn apply(&mut self, line: String) -> Option<String> {
+        self.stages
+            .iter_mut()
+            .filter(|stage| stage.enabled)
+            .try_fold(line, |line, stage| {
+                let started = Instant::now();
+                let output = stage.apply(line);
+                stage.stats.elapsed += started.elapsed();
+                stage.stats.lines += 1;
+                if output.is_none() {
+                    stage.stats.dropped += 1;
+                }
+                output
+            })
+    }
+
+    /// Whether the event toggles a stage, see `toggle_by_event`.
+    #[cfg(feature = "tui")]
+    pub fn binds(&self, event: &Event) -> bool {
+        self.stages
+            .iter()
+            .any(|stage| matches_keybind(event, &stage.toggle))
+    }
+
+    /// Toggle the stages bound to the event, returning whether any stage was toggled.
+    #[cfg(feature = "tui")]
+    pub fn toggle_by_event(&mut self, event: &Event) -> bool {
+        let mut toggled = false;
+        for stage in &mut self.stages {
+            if matches_keybind(event, &stage.toggle) {
+                stage.enabled = !stage.enabled;
+                toggled = true;
+            }
+        }
+        toggled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(name: &str, kind: StageKind) -> StageConfig {
+        StageConfig {
+            name: name.to_string(),
+            kind,
+            enabled: true,
+            #[cfg(feature = "tui")]
+            toggle: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn apply_runs_enabled_stages_in_order() {
+        let mut pipeline = Pipeline::try_new(&[
+            stage(
+                "drop-health",
+                StageKind::Exclude {
+                    pattern: String::from("/health"),
+                    field: None,
+                },
+            ),
+            stage(
+                "project",
+                StageKind::Fields {
+                    fields: vec![String::from("level"), String::from("msg")],
+                },
+            ),
+            stage(
+                "mask",
+                StageKind::Replace {
+                    pattern: String::from(r"id=(\d+)"),
+                    replacement: String::from("id=***"),
+                },
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(pipeline.apply(String::from("GET /health")), None);
+        assert_eq!(pipeline.stages[0].stats.dropped, 1);
+        assert_eq!(pipeline.stages[1].stats.lines, 0);
+        assert_eq!(
+            pipeline.apply(String::from(r#"ts=1 level=info msg="user id=42" x=1"#)),
+            Some(String::from("level=info msg=user id=***"))
+        );
+
+        pipeline.stages[1].enabled = false;
+        assert_eq!(
+            pipeline.apply(String::from("level=info id=42")),
+            Some(String::from("level=info id=***"))
+        );
+    }
+
+    #[test]
+    fn decoded_lines_go_through_the_next_stages() {
+        let decode = toml::from_str::<StageConfig>(
+            "name = \"unwrap\"\nkind = \"decode\"\nencoding = \"json\"\n",
+        )
+        .unwrap();
+        let mut pipeline = Pipeline::try_new(&[
+            decode,
+            stage(
+                "errors",
+                StageKind::Include {
+                    pattern: String::from("^panic"),
+                    field: None,
+                },
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(pipeline.stages[0].kind(), "decode");
+        assert_eq!(
+            pipeline.apply(String::from(r#""panic: boom\n\tat main""#)),
+            Some(String::from("panic: boom\n\tat main"))
+        );
+        // Lines that do not decode are kept as they are.
+        assert_eq!(
+            pipeline.apply(String::from(r"panic \q")),
+            Some(String::from(r"panic \q"))
+        );
+        assert_eq!(pipeline.stages[1].stats.lines, 2);
+    }
+
+    #[test]
+    fn include_matches_the_field_of_xml_records() {
+        let mut pipeline = Pipeline::try_new(&[
+            stage(
+                "errors",
+                StageKind::Include {
+                    pattern: String::from("^error$"),
+                    field: Some(String::from("level")),
+                },
+            ),
+            stage(
+                "columns",
+                StageKind::Fields {
+                    fields: vec![String::from("@id"), String::from("user/name")],
+                },
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            pipeline.apply(String::from(
+                r#"<event id="7" level="error"><user><name>alice</name></user></event>"#
+            )),
+            Some(String::from("@id=7 user/name=alice"))
+        );
+        assert_eq!(
+            pipeline.apply(String::from(r#"<event id="8" level="info"/>"#)),
+            None
+        );
+        assert_eq!(pipeline.apply(String::from("level is error")), None);
+    }
+
+    #[test]
+    fn unique_and_seen_compare_lines_with_numbers_masked() {
+        let mut pipeline = Pipeline::try_new(&[
+            stage("unique", StageKind::Unique),
+            stage("seen", StageKind::Seen),
+        ])
+        .unwrap();
+        assert_eq!(
+            pipeline.apply(String::from("timeout after 3s id=71")),
+            Some(String::from("timeout after 3s id=71"))
+        );
+        pipeline.stages[0].enabled = false;
+        assert_eq!(
+            pipeline.apply(String::from("timeout after  12s id=9")),
+            Some(String::from("timeout after  12s id=9 (seen once before)"))
+        );
+        pipeline.stages[0].enabled = true;
+        assert_eq!(pipeline.apply(String::from("timeout after 5s id=2")), None);
+        assert_eq!(
+            pipeline.apply(String::from("refused")),
+            Some(String::from("refused"))
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use std::{fs::OpenOptions, io::Write, path::Path, time::Instant};
+
+use promkit_core::{
+    crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
+        style::{Color, ContentStyle},
+    },
+    grapheme::StyledGraphemes,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    alert::{Alert, AlertConfig, Alerts},
+    audit,
+    config::{matches_keybind, EditorKeybinds, RulesKeybinds},
+    highlight::{HighlightConfig, HighlightRule, Highlights},
+    pipeline::{Pipeline, Stage, StageConfig, StageKind},
+};
+
+/// The kinds of rules that can be edited while streaming.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RuleKind {
+    Highlight,
+    Exclude,
+    Alert,
+}
+
+const KINDS: [RuleKind; 3] = [RuleKind::Highlight, RuleKind::Exclude, RuleKind::Alert];
+
+/// How the text of a field is written to the configuration.
+#[derive(Clone, Copy)]
+enum FieldType {
+    Text,
+    Integer,
+    Number,
+    /// One of the options, cycled through rather than typed.
+    Choice(&'static [&'static str]),
+}
+
+struct FieldDef {
+    key: &'static str,
+    ty: FieldType,
+}
+
+const fn field(key: &'static str, ty: FieldType) -> FieldDef {
+    FieldDef { key, ty }
+}
+
+impl RuleKind {
+    fn label(self) -> &'static str {
+        match self {
+            RuleKind::Highlight => "highlight",
+            RuleKind::Exclude => "exclude",
+            RuleKind::Alert => "alert",
+        }
+    }
+
+    /// Name of the array of tables in the configuration.
+    fn section(self) -> &'static str {
+        match self {
+            RuleKind::Highlight => "highlights",
+            RuleKind::Exclude => "pipeline",
+            RuleKind::Alert => "alerts",
+        }
+    }
+
+    fn fields(self) -> &'static [FieldDef] {
+        match self {
+            RuleKind::Highlight => HIGHLIGHT_FIELDS,
+            RuleKind::Exclude => EXCLUDE_FIELDS,
+            RuleKind::Alert => ALERT_FIELDS,
+        }
+    }
+}
+
+const HIGHLIGHT_FIELDS: &[FieldDef] = &[
+    field("pattern", FieldType::Text),
+    field("style", FieldType::Text),
+    field("scope", FieldType::Choice(&["match", "line"])),
+];
+const EXCLUDE_FIELDS: &[FieldDef] = &[
+    field("name", FieldType::Text),
+    field("pattern", FieldType::Text),
+];
+const ALERT_FIELDS: &[FieldDef] = &[
+    field("name", FieldType::Text),
+    field("pattern", FieldType::Text),
+    field("field", FieldType::Text),
+    field("window", FieldType::Integer),
+    field("above", FieldType::Number),
+    field("increase", FieldType::Number),
+];
+
+/// A rule in effect, with the fields of its configuration.
+struct Entry {
+    kind: RuleKind,
+    // Position among the rules of its kind (the highlights, the pipeline stages or the alerts)
+    index: usize,
+    table: toml::Table,
+}
+
+/// The rule being created (`index` is None) or edited.
+struct Form {
+    kind: RuleKind,
+    index: Option<usize>,
+    values: Vec<String>,
+    // 0 is the kind, then the fields of the kind
+    focus: usize,
+}
+
+impl Form {
+    fn new(kind: RuleKind, index: Option<usize>, table: &toml::Table) -> Self {
+        let values = kind
+            .fields()
+            .iter()
+            .map(|def| match (table.get(def.key), def.ty) {
+                (Some(toml::Value::String(s)), _) => s.clone(),
+                (Some(toml::Value::Integer(i)), _) => i.to_string(),
+                (Some(toml::Value::Float(f)), _) => f.to_string(),
+                (_, FieldType::Choice(options)) => options[0].to_string(),
+                _ => String::new(),
+            })
+            .collect();
+        Self {
+            kind,
+            index,
+            values,
+            focus: if index.is_some() { 1 } else { 0 },
+        }
+    }
+
+    /// The fields as they are written to the configuration, leaving out the empty ones.
+    fn table(&self) -> anyhow::Result<toml::Table> {
+        let mut table = toml::Table::new();
+        if self.kind == RuleKind::Exclude {
+            table.insert("kind".into(), toml::Value::String("exclude".into()));
+        }
+        for (def, value) in self.kind.fields().iter().zip(&self.values) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let invalid = |e: &dyn std::fmt::Display| anyhow::anyhow!("Invalid {}: {e}", def.key);
+            let value = match def.ty {
+                FieldType::Text | FieldType::Choice(_) => toml::Value::String(value.to_string()),
+                FieldType::Integer => toml::Value::Integer(value.parse().map_err(|e| invalid(&e))?),
+                FieldType::Number => toml::Value::Float(value.parse().map_err(|e| invalid(&e))?),
+            };
+            table.insert(def.key.into(), value);
+        }
+        Ok(table)
+    }
+
+    fn cycle(&mut self) {
+        if self.focus == 0 {
+            // The kind of an existing rule cannot be changed.
+            if self.index.is_none() {
+                let next = KINDS
+                    [(KINDS.iter().position(|k| *k == self.kind).unwrap_or(0) + 1) % KINDS.len()];
+                let pattern = self.value("pattern").unwrap_or_default().to_string();
+                let mut table = toml::Table::new();
+                table.insert("pattern".into(), toml::Value::String(pattern));
+                *self = Form::new(next, None, &table);
+            }
+            return;
+        }
+        if let FieldType::Choice(options) = self.kind.fields()[self.focus - 1].ty {
+            let value = &mut self.values[self.focus - 1];
+            let next = options.iter().position(|o| o == value).map_or(0, |i| i + 1);
+            *value = options[next % options.len()].to_string();
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&str> {
+        self.kind
+            .fields()
+            .iter()
+            .position(|def| def.key == key)
+            .map(|i| self.values[i].as_str())
+    }
+
+    /// The text of the focused field, unless it is the kind or a choice.
+    fn focused_text(&mut self) -> Option<&mut String> {
+        let i = self.focus.checked_sub(1)?;
+        match self.kind.fields()[i].ty {
+            FieldType::Choice(_) => None,
+            _ => Some(&mut self.values[i]),
+        }
+    }
+}
+
+enum View {
+    /// The rules, after the first row to add a new one.
+    List {
+        cursor: usize,
+    },
+    Form(Form),
+}
+
+/// What the streaming view has to do after a key event in the rule editor.
+pub enum RuleAction {
+    Continue,
+    Close,
+    /// Apply the edited rule, appending it to the configuration file if `save`.
+    Apply {
+        save: bool,
+    },
+}
+
+/// A popup to create and edit the highlight, exclude and alert rules while streaming.
+pub struct RuleEditor {
+    entries: Vec<Entry>,
+    view: View,
+    // The outcome of the last apply, e.g. an invalid pattern
+    message: Option<(String, bool)>,
+}
+
+fn to_table<T: Serialize>(config: &T) -> toml::Table {
+    toml::Table::try_from(config).unwrap_or_default()
+}
+
+fn from_table<T: DeserializeOwned>(table: toml::Table) -> anyhow::Result<T> {
+    toml::Value::Table(table).try_into().map_err(Into::into)
+}
+
+impl RuleEditor {
+    pub fn open(pipeline: &Pipeline, alerts: &Alerts, highlights: &Highlights) -> Self {
+        let mut editor = Self {
+            entries: Vec::new(),
+            view: View::List { cursor: 0 },
+            message: None,
+        };
+        editor.refresh(pipeline, alerts, highlights);
+        editor
+    }
+
+    fn refresh(&mut self, pipeline: &Pipeline, alerts: &Alerts, highlights: &Highlights) {
+        let highlights = highlights.rules.iter().map(|rule| to_table(rule.config()));
+        let excludes = pipeline.stages.iter().map(|stage| {
+            matches!(stage.config().kind, StageKind::Exclude { .. })
+                .then(|| to_table(stage.config()))
+        });
+        let alerts = alerts.alerts.iter().map(|alert| to_table(alert.config()));
+
+        self.entries = highlights
+            .enumerate()
+            .map(|(index, table)| Entry {
+                kind: RuleKind::Highlight,
+                index,
+                table,
+            })
+            .chain(excludes.enumerate().filter_map(|(index, table)| {
+                table.map(|table| Entry {
+                    kind: RuleKind::Exclude,
+                    index,
+                    table,
+                })
+            }))
+            .chain(alerts.enumerate().map(|(index, table)| Entry {
+                kind: RuleKind::Alert,
+                index,
+                table,
+            }))
+            .collect();
+    }
+
+    pub fn evaluate_event(
+        &mut self,
+        event: &Event,
+        keybinds: &RulesKeybinds,
+        editor_keybinds: &EditorKeybinds,
+    ) -> RuleAction {
+        match &mut self.view {
+            View::List { cursor } => {
+                if matches_keybind(event, &keybinds.cancel) {
+                    return RuleAction::Close;
+                }
+                if matches_keybind(event, &keybinds.up) {
+                    *cursor = cursor.saturating_sub(1);
+                } else if matches_keybind(event, &keybinds.down) {
+                    *cursor = (*cursor + 1).min(self.entries.len());
+                } else if matches_keybind(event, &keybinds.apply) {
+                    self.message = None;
+                    self.view = View::Form(match cursor.checked_sub(1) {
+                        Some(i) => {
+                            let entry = &self.entries[i];
+                            Form::new(entry.kind, Some(entry.index), &entry.table)
+                        }
+                        None => Form::new(RuleKind::Highlight, None, &toml::Table::new()),
+                    });
+                }
+                RuleAction::Continue
+            }
+            View::Form(form) => {
+                if matches_keybind(event, &keybinds.cancel) {
+                    self.message = None;
+                    self.view = View::List { cursor: 0 };
+                } else if matches_keybind(event, &keybinds.apply) {
+                    return RuleAction::Apply { save: false };
+                } else if matches_keybind(event, &keybinds.save) {
+                    return RuleAction::Apply { save: true };
+                } else if matches_keybind(event, &keybinds.up) {
+                    form.focus = form.focus.saturating_sub(1);
+                } else if matches_keybind(event, &keybinds.down) {
+                    form.focus = (form.focus + 1).min(form.kind.fields().len());
+                } else if matches_keybind(event, &keybinds.cycle) {
+                    form.cycle();
+                } else if let Some(text) = form.focused_text() {
+                    if matches_keybind(event, &editor_keybinds.erase) {
+                        text.pop();
+                    } else if matches_keybind(event, &editor_keybinds.erase_all) {
+                        text.clear();
+                    } else if let Event::Key(KeyEvent {
+                        code: KeyCode::Char(ch),
+                        modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                        kind: KeyEventKind::Press,
+                        state: KeyEventState::NONE,
+                    }) = event
+                    {
+                        text.push(*ch);
+                    }
+                }
+                RuleAction::Continue
+            }
+        }
+    }
+
+    /// Put the edited rule into effect, and append it to `config_file` if `save`.
+    /// On failure, the form stays open with the error shown.
+    pub fn apply(
+        &mut self,
+        pipeline: &mut Pipeline,
+        alerts: &mut Alerts,
+        highlights: &mut Highlights,
+        config_file: Option<&Path>,
+        save: bool,
+    ) {
+        let View::Form(form) = &self.view else {
+            return;
+        };
+        match Self::apply_form(form, pipeline, alerts, highlights, config_file, save) {
+            Ok(message) => {
+                self.refresh(pipeline, alerts, highlights);
+                self.message = Some((message, false));
+                self.view = View::List { cursor: 0 };
+            }
+            Err(e) => self.message = Some((e.to_string(), true)),
+        }
+    }
+
+    fn apply_form(
+        form: &Form,
+        pipeline: &mut Pipeline,
+        alerts: &mut Alerts,
+        highlights: &mut Highlights,
+        config_file: Option<&Path>,
+        save: bool,
+    ) -> anyhow::Result<String> {
+        fn put<T>(items: &mut Vec<T>, index: Option<usize>, item: T) {
+            match index.and_then(|i| items.get_mut(i)) {
+                Some(slot) => *slot = item,
+                None => items.push(item),
+            }
+        }
+
+        if save && form.index.is_some() {
+            return Err(anyhow::anyhow!(
+                "Only new rules can be saved, edit the configuration file to change this one"
+            ));
+        }
+        let table = form.table()?;
+        match form.kind {
+            RuleKind::Highlight => {
+                let rule = HighlightRule::try_new(&from_table::<HighlightConfig>(table.clone())?)?;
+                put(&mut highlights.rules, form.index, rule);
+            }
+            RuleKind::Exclude => {
+                let stage = Stage::try_new(&from_table::<StageConfig>(table.clone())?)?;
+                put(&mut pipeline.stages, form.index, stage);
+            }
+            RuleKind::Alert => {
+                let alert =
+                    Alert::try_new(&from_table::<AlertConfig>(table.clone())?, Instant::now())?;
+                put(&mut alerts.alerts, form.index, alert);
+            }
+        }
+
+        let rule = format!("{} {}", form.kind.label(), summarize(&table));
+        audit::record(
+            "streaming",
+            if form.index.is_some() {
+                "edit_rule"
+            } else {
+                "add_rule"
+            },
+            &rule,
+        );
+        if !save {
+            return Ok(format!("Applied {rule}"));
+        }
+        let path = config_file
+            .ok_or_else(|| anyhow::anyhow!("No configuration file to save the rule to"))?;
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        write!(
+            file,
+            "\n[[{}]]\n{}",
+            form.kind.section(),
+            toml::to_string(&table)?
+        )?;
+        Ok(format!("Applied and saved {rule} to {}", path.display()))
+    }
+
+    /// Rows of the popup.
+    pub fn rows(&self) -> Vec<StyledGraphemes> {
+        let dim = ContentStyle {
+            foreground_color: Some(Color::DarkGrey),
+            ..Default::default()
+        };
+        let marker = |selected: bool| if selected { "❯" } else { " " };
+        let mut rows = Vec::new();
+        match &self.view {
+            View::List { cursor } => {
+                rows.push(
+                    StyledGraphemes::from("rules | Enter: edit | Esc: close").apply_style(dim),
+                );
+                rows.push(StyledGraphemes::from(format!(
+                    "{} + new rule",
+                    marker(*cursor == 0)
+                )));
+                rows.extend(self.entries.iter().enumerate().map(|(i, entry)| {
+                    StyledGraphemes::from(format!(
+                        "{} {:9}  {}",
+                        marker(i + 1 == *cursor),
+                        entry.kind.label(),
+                        summarize(&entry.table),
+                    ))
+                }));
+            }
+            View::Form(form) => {
+                rows.push(
+                    StyledGraphemes::from(format!(
+                        "{} rule | Enter: apply | Ctrl+S: save | Tab: option | Esc: back",
+                        if form.index.is_some() { "edit" } else { "new" },
+                    ))
+                    .apply_style(dim),
+                );
+                rows.push(StyledGraphemes::from(format!(
+                    "{} {:9} {}",
+                    marker(form.focus == 0),
+                    "kind",
+                    form.kind.label(),
+                )));
+                rows.extend(form.kind.fields().iter().zip(&form.values).enumerate().map(
+                    |(i, (def, value))| {
+                        StyledGraphemes::from(format!(
+                            "{} {:9} {value}",
+                            marker(form.focus == i + 1),
+                            def.key,
+                        ))
+                    },
+                ));
+            }
+        }
+        if let Some((message, error)) = &self.message {
+            rows.push(StyledGraphemes::from(message).apply_style(ContentStyle {
+                foreground_color: Some(if *error { Color::Red } else { Color::Green }),
+                ..Default::default()
+            }));
+        }
+        rows
+    }
+}
+
+/// The fields of a rule on a single line, e.g. `pattern=ERROR scope=line`.
+fn summarize(table: &toml::Table) -> String {
+    table
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "kind" | "enabled" | "toggle"))
+        .map(|(key, value)| match value {
+            toml::Value::String(s) => format!("{key}={s}"),
+            other => format!("{key}={other}"),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, StreamingKeybinds, DEFAULT_CONFIG};
+
+    use super::*;
+
+    fn press(editor: &mut RuleEditor, keybinds: &StreamingKeybinds, code: KeyCode) -> RuleAction {
+        let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        editor.evaluate_event(&event, &keybinds.rules, &keybinds.editor)
+    }
+
+    fn type_text(editor: &mut RuleEditor, keybinds: &StreamingKeybinds, text: &str) {
+        for ch in text.chars() {
+            press(editor, keybinds, KeyCode::Char(ch));
+        }
+    }
+
+    #[test]
+    fn new_exclude_rule_is_added_to_the_pipeline() {
+        let keybinds = Config::load_from(DEFAULT_CONFIG)
+            .unwrap()
+            .streaming
+            .keybinds;
+        let mut pipeline = Pipeline::default();
+        let mut alerts = Alerts::default();
+        let mut highlights = Highlights::default();
+        let mut editor = RuleEditor::open(&pipeline, &alerts, &highlights);
+
+        // Open the form for a new rule, and switch from highlight to exclude.
+        press(&mut editor, &keybinds, KeyCode::Enter);
+        press(&mut editor, &keybinds, KeyCode::Tab);
+        press(&mut editor, &keybinds, KeyCode::Down);
+        type_text(&mut editor, &keybinds, "health");
+        press(&mut editor, &keybinds, KeyCode::Down);
+        type_text(&mut editor, &keybinds, "GET /health");
+        assert!(matches!(
+            press(&mut editor, &keybinds, KeyCode::Enter),
+            RuleAction::Apply { save: false }
+        ));
+        editor.apply(&mut pipeline, &mut alerts, &mut highlights, None, false);
+
+        assert_eq!(pipeline.apply(String::from("GET /health")), None);
+        assert_eq!(
+            summarize(&editor.entries[0].table),
+            "name=health pattern=GET /health"
+        );
+    }
+}
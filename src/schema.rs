@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, HashMap};
+
+use promkit_core::{
+    crossterm::style::{Color, ContentStyle},
+    grapheme::StyledGraphemes,
+};
+
+use crate::{archive::Archive, fields};
+
+/// Distinct values kept per field to show as samples.
+const MAX_SAMPLES: usize = 3;
+/// Characters a sample value is cut to.
+const SAMPLE_CHARS: usize = 24;
+/// Most frequent values shown per aggregated field.
+const TOP_VALUES: usize = 5;
+/// Colors given to the values of the colored fields, in the order the fields were checked.
+const PALETTE: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Type of a value, guessed from its text since logfmt values are untyped.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    Number,
+    Bool,
+    Time,
+    Object,
+    Null,
+    String,
+}
+
+impl Kind {
+    pub fn guess(value: &str) -> Self {
+        let bytes = value.as_bytes();
+        // Rule out the words f64 parses, e.g. "inf" and "NaN".
+        let numeric =
+            value.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+        if numeric && value.parse::<f64>().is_ok() {
+            Kind::Number
+        } else if value == "true" || value == "false" {
+            Kind::Bool
+        } else if value == "null" {
+            Kind::Null
+        } else if value.starts_with('{') || value.starts_with('[') {
+            Kind::Object
+        } else if bytes.len() >= 19
+            && bytes[..4].iter().all(u8::is_ascii_digit)
+            && bytes[4] == b'-'
+            && matches!(bytes[10], b'T' | b' ')
+        {
+            Kind::Time
+        } else {
+            Kind::String
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "number",
+            Kind::Bool => "bool",
+            Kind::Time => "time",
+            Kind::Object => "object",
+            Kind::Null => "null",
+            Kind::String => "string",
+        }
+    }
+}
+
+/// What was observed of a field across the lines.
+pub struct FieldSummary {
+    pub name: String,
+    /// Number of lines having the field.
+    pub count: usize,
+    kinds: BTreeMap<Kind, usize>,
+    pub samples: Vec<String>,
+}
+
+impl FieldSummary {
+    /// The guessed types, the most frequent first, e.g. `number|null`.
+    pub fn kind(&self) -> String {
+        let mut kinds = self.kinds.iter().collect::<Vec<_>>();
+        kinds.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        kinds
+            .iter()
+            .map(|(kind, _)| kind.name())
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+/// Fields observed across the lines of a JSON/logfmt stream,
+/// to discover what can be filtered on in an unfamiliar format.
+#[derive(Default)]
+pub struct Schema {
+    pub lines: usize,
+    /// Number of lines having any field.
+    pub structured: usize,
+    /// In the order they were first seen.
+    pub fields: Vec<FieldSummary>,
+    index: HashMap<String, usize>,
+}
+
+impl Schema {
+    pub fn infer(lines: &Archive) -> anyhow::Result<Self> {
+        let mut schema = Self::default();
+        lines.scan(|_, chunk| {
+            for line in chunk {
+                schema.add(line);
+            }
+        })?;
+        Ok(schema)
+    }
+
+    pub fn add(&mut self, line: &str) {
+        self.lines += 1;
+        let parsed = fields::parse(line);
+        if parsed.is_empty() {
+            return;
+        }
+        self.structured += 1;
+
+        for (name, value) in parsed {
+            let idx = match self.index.get(&name) {
+                Some(&idx) => idx,
+                None => {
+                    self.index.insert(name.clone(), self.fields.len());
+                    self.fields.push(FieldSummary {
+                        name,
+                        count: 0,
+                        kinds: BTreeMap::new(),
+                        samples: Vec::new(),
+                    });
+                    self.fields.len() - 1
+                }
+            };
+            let field = &mut self.fields[idx];
+            field.count += 1;
+            *field.kinds.entry(Kind::guess(&value)).or_default() += 1;
+            let sample = value.chars().take(SAMPLE_CHARS).collect::<String>();
+            if field.samples.len() < MAX_SAMPLES && !field.samples.contains(&sample) {
+                field.samples.push(sample);
+            }
+        }
+    }
+
+    /// Share of the lines having the field, in percent.
+    pub fn fill_rate(&self, field: &FieldSummary) -> f64 {
+        field.count as f64 * 100.0 / self.lines.max(1) as f64
+    }
+
+    /// Lay out the summary as a table with a header row, a row per field.
+    pub fn rows(&self) -> Vec<StyledGraphemes> {
+        let name_width = self
+            .fields
+            .iter()
+            .map(|field| field.name.chars().count())
+            .max()
+            .unwrap_or_default()
+            .clamp(5, 24);
+        let header = format!(
+            "{:<name_width$}  {:<13} {:>6}  samples ({} fields in {} of {} lines)",
+            "field",
+            "type",
+            "fill",
+            self.fields.len(),
+            self.structured,
+            self.lines,
+        );
+        let mut rows = vec![StyledGraphemes::from(header).apply_style(ContentStyle {
+            foreground_color: Some(Color::DarkGrey),
+            ..Default::default()
+        })];
+        rows.extend(self.fields.iter().map(|field| {
+            StyledGraphemes::from(format!(
+                "{:<name_width$}  {:<13} {:>5.1}%  {}",
+                field.name,
+                field.kind(),
+                self.fill_rate(field),
+                field.samples.join(", "),
+            ))
+        }));
+        rows
+    }
+}
+
+/// What a field is picked for in the schema popup.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    /// Show only the picked fields of the lines, as `name=value` pairs.
+    Project,
+    /// Color the values of the field in the lines.
+    Color,
+    /// Show the most frequent values of the field under its row.
+    Aggregate,
+}
+
+/// Popup over the schema to pick fields by checkbox rather than typing their names.
+/// The picked fields are kept in the order they were checked.
+pub struct FieldPicker {
+    pub schema: Schema,
+    cursor: usize,
+    projected: Vec<String>,
+    colored: Vec<String>,
+    aggregated: HashMap<String, Vec<(String, usize)>>,
+}
+
+impl FieldPicker {
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            schema,
+            cursor: 0,
+            projected: Vec::new(),
+            colored: Vec::new(),
+            aggregated: HashMap::new(),
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.schema.fields.len().saturating_sub(1));
+    }
+
+    /// Check or uncheck the field under the cursor,
+    /// counting its values across the lines when aggregated.
+    pub fn toggle(&mut self, pick: Pick, lines: &Archive) -> anyhow::Result<()> {
+        let Some(field) = self.schema.fields.get(self.cursor) else {
+            return Ok(());
+        };
+        let name = field.name.clone();
+        match pick {
+            Pick::Project => toggle(&mut self.projected, name),
+            Pick::Color => toggle(&mut self.colored, name),
+            Pick::Aggregate => {
+                if self.aggregated.remove(&name).is_none() {
+                    let top = top_values(lines, &name, TOP_VALUES)?;
+                    self.aggregated.insert(name, top);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The line cut down to the projected fields, if any is picked and found in the line.
+    pub fn project(&self, line: &str) -> Option<String> {
+        if self.projected.is_empty() {
+            return None;
+        }
+        let parsed = fields::parse(line);
+        let projected = self
+            .projected
+            .iter()
+            .filter_map(|name| {
+                fields::find(&parsed, &[name]).map(|value| format!("{name}={value}"))
+            })
+            .collect::<Vec<String>>();
+        (!projected.is_empty()).then(|| projected.join(" "))
+    }
+
+    /// Color the values of the colored fields in the line.
+    pub fn color(&self, line: &str, mut styled: StyledGraphemes) -> StyledGraphemes {
+        for (name, color) in self.colored.iter().zip(PALETTE.iter().cycle()) {
+            let style = ContentStyle {
+                foreground_color: Some(*color),
+                ..Default::default()
+            };
+            for i in fields::value_span(line, name).unwrap_or_default() {
+                styled = styled.apply_style_at(i, style);
+            }
+        }
+        styled
+    }
+
+    /// Lay out the schema with the checkboxes of each field, scrolled to keep the cursor
+    /// in the `height` rows, and the top values of the aggregated fields under their row.
+    pub fn rows(&self, height: usize) -> Vec<StyledGraphemes> {
+        let mut table = self.schema.rows().into_iter();
+        let header = table.next().unwrap_or_default();
+        let checkbox = |checked: bool, label: char| {
+            if checked {
+                format!("[{label}]")
+            } else {
+                String::from("[ ]")
+            }
+        };
+
+        let mut rows = Vec::new();
+        let mut cursor_row = 0;
+        for (i, (field, row)) in self.schema.fields.iter().zip(table).enumerate() {
+            if i == self.cursor {
+                cursor_row = rows.len();
+            }
+            let marker = if i == self.cursor { "❯ " } else { "  " };
+            let boxes = format!(
+                "{marker}{}{}{} ",
+                checkbox(self.projected.contains(&field.name), 'P'),
+                checkbox(self.colored.contains(&field.name), 'C'),
+                checkbox(self.aggregated.contains_key(&field.name), 'A'),
+            );
+            rows.push(StyledGraphemes::from_iter([
+                &StyledGraphemes::from(boxes),
+                &row,
+            ]));
+            if let Some(top) = self.aggregated.get(&field.name) {
+                let values = top
+                    .iter()
+                    .map(|(value, count)| format!("{value} {count}"))
+                    .collect::<Vec<_>>()
+                    .join(" · ");
+                rows.push(
+                    StyledGraphemes::from(format!("             ↳ {values}")).apply_style(
+                        ContentStyle {
+                            foreground_color: Some(Color::DarkGrey),
+                            ..Default::default()
+                        },
+                    ),
+                );
+            }
+        }
+
+        let body = height.saturating_sub(1);
+        let offset = (cursor_row + 1).saturating_sub(body);
+        std::iter::once(StyledGraphemes::from_iter([
+            &StyledGraphemes::from("  P  C  A  "),
+            &header,
+        ]))
+        .chain(rows.into_iter().skip(offset).take(body))
+        .collect()
+    }
+}
+
+fn toggle(picked: &mut Vec<String>, name: String) {
+    match picked.iter().position(|picked| *picked == name) {
+        Some(at) => {
+            picked.remove(at);
+        }
+        None => picked.push(name),
+    }
+}
+
+/// The `n` most frequent values of the field across the lines, with their counts.
+pub fn top_values(lines: &Archive, field: &str, n: usize) -> anyhow::Result<Vec<(String, usize)>> {
+    let mut counts = HashMap::<String, usize>::new();
+    lines.scan(|_, chunk| {
+        for line in chunk {
+            if let Some(value) = fields::find(&fields::parse(line), &[field]) {
+                *counts.entry(value.to_string()).or_default() += 1;
+            }
+        }
+    })?;
+    let mut top = counts.into_iter().collect::<Vec<_>>();
+    top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(n);
+    Ok(top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_summarizes_fields_across_formats() {
+        let mut schema = Schema::default();
+        for line in [
+            r#"{"level":"info","latency":12,"ts":"2024-05-01T10:00:00Z"}"#,
+            "level=error latency=null user=alice",
+            "level=info latency=8.5",
+            "plain text line",
+        ] {
+            schema.add(line);
+        }
+
+        assert_eq!((schema.lines, schema.structured), (4, 3));
+        let summary = schema
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.kind(), field.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("level", String::from("string"), 3),
+                ("latency", String::from("number|null"), 3),
+                ("ts", String::from("time"), 1),
+                ("user", String::from("string"), 1),
+            ]
+        );
+        assert_eq!(schema.fields[0].samples, vec!["info", "error"]);
+        assert_eq!(schema.fill_rate(&schema.fields[0]), 75.0);
+    }
+
+    #[test]
+    fn picker_projects_and_aggregates_checked_fields() {
+        let mut lines = Archive::new(10);
+        for line in [
+            "level=info user=alice msg=ok",
+            "level=error user=bob msg=failed",
+            "level=info msg=ok",
+        ] {
+            lines.push(line.to_string()).unwrap();
+        }
+        let mut picker = FieldPicker::new(Schema::infer(&lines).unwrap());
+        assert_eq!(picker.project("level=info msg=ok"), None);
+
+        // Check `user` then `level`, keeping the order they were checked in.
+        picker.down();
+        picker.toggle(Pick::Project, &lines).unwrap();
+        picker.up();
+        picker.toggle(Pick::Project, &lines).unwrap();
+        picker.toggle(Pick::Aggregate, &lines).unwrap();
+
+        assert_eq!(
+            picker.project("level=error user=bob msg=failed").as_deref(),
+            Some("user=bob level=error")
+        );
+        assert_eq!(picker.project("plain text"), None);
+        assert_eq!(
+            picker.aggregated["level"],
+            vec![(String::from("info"), 2), (String::from("error"), 1)]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use crate::fields;
+
+/// What a sequence number tells about the messages before it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// Messages were skipped between the previous and this sequence number.
+    Gap { previous: u64, current: u64 },
+    /// The sequence number is not above the previous one, e.g. a redelivered message.
+    Repeat { previous: u64, current: u64 },
+}
+
+impl Anomaly {
+    /// Marker drawn in the stream before the line.
+    pub fn marker(&self, field: &str) -> String {
+        match self {
+            Anomaly::Gap { previous, current } => format!(
+                "⋯ {} missing ({field} {previous} → {current})",
+                current - previous - 1
+            ),
+            Anomaly::Repeat { previous, current } => {
+                format!("⋯ repeated ({field} {current} after {previous})")
+            }
+        }
+    }
+}
+
+/// Check of a field carrying a monotonically increasing sequence number (`--sequence-field`),
+/// counting the messages dropped or repeated along the stream.
+pub struct SequenceCheck {
+    field: String,
+    last: Option<u64>,
+    /// Number of sequence numbers skipped.
+    pub missing: u64,
+    /// Number of lines whose sequence number was not above the previous one.
+    pub repeated: u64,
+}
+
+impl SequenceCheck {
+    pub fn new(field: String) -> Self {
+        Self {
+            field,
+            last: None,
+            missing: 0,
+            repeated: 0,
+        }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Check the sequence number of the line, ignoring lines without one.
+    pub fn observe(&mut self, line: &str) -> Option<Anomaly> {
+        let parsed = fields::parse(line);
+        let current = fields::find(&parsed, &[&self.field])?.parse::<u64>().ok()?;
+        let previous = self.last.replace(current)?;
+
+        if current <= previous {
+            // Keep expecting the numbers after the highest one seen.
+            self.last = Some(previous);
+            self.repeated += 1;
+            Some(Anomaly::Repeat { previous, current })
+        } else if current > previous + 1 {
+            self.missing += current - previous - 1;
+            Some(Anomaly::Gap { previous, current })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observe_counts_gaps_and_repeats() {
+        let mut check = SequenceCheck::new(String::from("seq"));
+        let anomalies = [
+            "seq=1 msg=a",
+            "seq=2 msg=b",
+            "no sequence number",
+            "seq=5 msg=c",
+            "seq=4 msg=d",
+            "seq=6 msg=e",
+        ]
+        .iter()
+        .map(|line| check.observe(line))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            anomalies,
+            vec![
+                None,
+                None,
+                None,
+                Some(Anomaly::Gap {
+                    previous: 2,
+                    current: 5
+                }),
+                Some(Anomaly::Repeat {
+                    previous: 5,
+                    current: 4
+                }),
+                None,
+            ]
+        );
+        assert_eq!((check.missing, check.repeated), (2, 1));
+        assert_eq!(
+            anomalies[3].as_ref().unwrap().marker("seq"),
+            "⋯ 2 missing (seq 2 → 5)"
+        );
+    }
+}
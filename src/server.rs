@@ -0,0 +1,302 @@
+use std::{collections::VecDeque, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{broadcast, Mutex},
+};
+
+use crate::{
+    pipeline::StageConfig,
+    session::{Session, SessionOptions},
+    source::Source,
+};
+
+/// Capacity of the channel relaying new lines to the clients,
+/// beyond which a slow client misses lines rather than slowing down the others.
+const RELAY_CAPACITY: usize = 4096;
+
+/// What the collector sends to every client ahead of the lines,
+/// so that read-only clients view them as the owner of the collector does.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Published {
+    /// The query of the collector, see `--query`.
+    pub query: String,
+    /// The pipeline stages of the collector, which the clients run rather than the collector.
+    pub stages: Vec<StageConfig>,
+}
+
+/// A message of the collector to a client after what is published.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Relayed {
+    Line(String),
+    /// Number of lines the client missed since it did not keep up with the others.
+    Dropped {
+        dropped: usize,
+    },
+}
+
+/// Lines kept by the collector, to be replayed to newly attached clients.
+struct Buffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+/// Collect lines from the source without a terminal,
+/// serving them to the clients attached to the Unix socket (`sig attach`)
+/// until interrupted.
+///
+/// Each client receives what is published as a JSON object, then the buffered lines
+/// followed by the new ones, one JSON string per line so that multi-line records survive the trip.
+/// The lines are relayed before the pipeline stages of `options`, which are left to the clients.
+pub async fn serve(
+    source: &dyn Source,
+    options: SessionOptions,
+    published: &Published,
+    socket: &Path,
+    capacity: usize,
+) -> anyhow::Result<()> {
+    let published = Arc::new(serde_json::to_string(published)?);
+    // A socket left behind by a collector that did not exit cleanly.
+    if socket.exists() && UnixStream::connect(socket).await.is_err() {
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)
+        .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {e}", socket.display()))?;
+
+    let buffer = Arc::new(Mutex::new(Buffer {
+        lines: VecDeque::new(),
+        capacity,
+    }));
+    let (relay, _) = broadcast::channel::<Arc<String>>(RELAY_CAPACITY);
+
+    let mut session = Session::start(
+        source,
+        SessionOptions {
+            pipeline: Default::default(),
+            ..options
+        },
+    )?;
+    let collecting_buffer = Arc::clone(&buffer);
+    let collecting_relay = relay.clone();
+    let collecting = tokio::spawn(async move {
+        while let Some(line) = session.recv().await {
+            let line = Arc::new(serde_json::to_string(&line).expect("strings are serializable"));
+            let mut buffer = collecting_buffer.lock().await;
+            if buffer.lines.len() >= buffer.capacity {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back(line.to_string());
+            // Send while holding the lock, so that a client attaching now
+            // gets the line either from the buffer or from the relay.
+            let _ = collecting_relay.send(line);
+        }
+        session.stop().await;
+    });
+
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => break Err(e.into()),
+                };
+                let (replay, mut rx) = {
+                    let buffer = buffer.lock().await;
+                    (buffer.lines.iter().cloned().collect::<Vec<String>>(), relay.subscribe())
+                };
+                let published = Arc::clone(&published);
+                tokio::spawn(async move {
+                    let mut stream = stream;
+                    stream.write_all(published.as_bytes()).await?;
+                    stream.write_all(b"\n").await?;
+                    for line in replay {
+                        stream.write_all(line.as_bytes()).await?;
+                        stream.write_all(b"\n").await?;
+                    }
+                    loop {
+                        match rx.recv().await {
+                            Ok(line) => {
+                                stream.write_all(line.as_bytes()).await?;
+                                stream.write_all(b"\n").await?;
+                            }
+                            Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                                // Tell the client, which shows them as dropped lines.
+                                let notice = serde_json::to_string(&Relayed::Dropped {
+                                    dropped: dropped as usize,
+                                })?;
+                                stream.write_all(notice.as_bytes()).await?;
+                                stream.write_all(b"\n").await?;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        }
+                    }
+                    Ok::<(), anyhow::Error>(())
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    collecting.abort();
+    let _ = std::fs::remove_file(socket);
+    result
+}
+
+/// Read what the collector listening on the socket publishes, see `sig attach --read-only`.
+pub async fn published(socket: &Path) -> anyhow::Result<Published> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to attach to {}: {e}", socket.display()))?;
+    let line = BufReader::new(stream)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("{} closed before publishing", socket.display()))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Write};
+
+    use tokio::{sync::RwLock, time::Duration};
+
+    use super::*;
+    use crate::{pipeline::Pipeline, source};
+
+    #[tokio::test]
+    async fn attached_clients_receive_buffered_records() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("sig-server-{}.log", std::process::id()));
+        let socket = dir.join(format!("sig-server-{}.sock", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"first\nsecond\n")
+            .unwrap();
+
+        let serving = {
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                serve(
+                    &source::File(path),
+                    SessionOptions::default(),
+                    &Published::default(),
+                    &socket,
+                    1,
+                )
+                .await
+            })
+        };
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Let the collector read the whole file, so that only the last line is buffered.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for _ in 0..2 {
+            let mut session =
+                Session::start(&source::Socket(socket.clone()), SessionOptions::default()).unwrap();
+            assert_eq!(session.recv().await.as_deref(), Some("second"));
+            session.stop().await;
+        }
+        serving.abort();
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[tokio::test]
+    async fn clients_read_the_query_and_stages_of_the_collector() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("sig-published-{}.log", std::process::id()));
+        let socket = dir.join(format!("sig-published-{}.sock", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"INFO ready\nERROR failed\n")
+            .unwrap();
+        let stages = toml::from_str::<HashMap<String, Vec<StageConfig>>>(
+            "[[pipeline]]\nname = \"errors\"\nkind = \"include\"\npattern = \"ERROR\"\n",
+        )
+        .unwrap()
+        .remove("pipeline")
+        .unwrap();
+        let options = SessionOptions {
+            pipeline: Arc::new(RwLock::new(Pipeline::try_new(&stages).unwrap())),
+            ..Default::default()
+        };
+        let published = Published {
+            query: String::from("failed"),
+            stages,
+        };
+
+        let serving = {
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                serve(&source::File(path), options, &published, &socket, 10).await
+            })
+        };
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let published = super::published(&socket).await.unwrap();
+        assert_eq!(published.query, "failed");
+        assert_eq!(published.stages.len(), 1);
+        assert_eq!(published.stages[0].name, "errors");
+        // The stages are run by the clients, which get every line.
+        let mut session =
+            Session::start(&source::Socket(socket.clone()), SessionOptions::default()).unwrap();
+        assert_eq!(session.recv().await.as_deref(), Some("INFO ready"));
+        assert_eq!(session.recv().await.as_deref(), Some("ERROR failed"));
+        session.stop().await;
+        serving.abort();
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[tokio::test]
+    async fn clients_falling_behind_count_the_missed_lines() {
+        const LINES: usize = 200_000;
+        let dir = std::env::temp_dir();
+        let script = dir.join(format!("sig-lagging-{}.sh", std::process::id()));
+        let socket = dir.join(format!("sig-lagging-{}.sock", std::process::id()));
+        // Wait for the client to attach, then outrun it.
+        std::fs::write(&script, format!("sleep 0.5\nseq 1 {LINES}\n")).unwrap();
+
+        let serving = {
+            let socket = socket.clone();
+            let cmd = source::Cmd(format!("sh {}", script.display()));
+            tokio::spawn(async move {
+                serve(
+                    &cmd,
+                    SessionOptions::default(),
+                    &Published::default(),
+                    &socket,
+                    1,
+                )
+                .await
+            })
+        };
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut session =
+            Session::start(&source::Socket(socket.clone()), SessionOptions::default()).unwrap();
+        // Read nothing until the lines overflow the relay.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        let mut received = 0;
+        while received + session.dropped() < LINES {
+            tokio::time::timeout(Duration::from_secs(10), session.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            received += 1;
+        }
+        assert!(session.dropped() > 0);
+        session.stop().await;
+        serving.abort();
+        let _ = std::fs::remove_file(&socket);
+        let _ = std::fs::remove_file(&script);
+    }
+}
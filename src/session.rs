@@ -0,0 +1,333 @@
+#[cfg(feature = "tui")]
+use promkit_core::{crossterm::style::ContentStyle, grapheme::StyledGraphemes};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::{sync::mpsc, time::Duration};
+
+#[cfg(feature = "tui")]
+use crate::highlight::highlight;
+use crate::{
+    mask::Mask,
+    pipeline::SharedPipeline,
+    source::Source,
+    spawn::{self, DropPolicy, FilterMode, InputTask, Records},
+};
+
+/// How the lines of a session are processed.
+pub struct SessionOptions {
+    /// Timeout to read a next line from the source.
+    pub retrieval_timeout: Duration,
+    /// Number of lines buffered from the source ahead of a slow consumer.
+    pub channel_capacity: usize,
+    /// What to do with the lines arriving while the buffer is full.
+    pub drop_policy: DropPolicy,
+    /// Command to stream every line through, see `--filter-cmd`.
+    pub filter: Option<(String, FilterMode)>,
+    /// Grouping and flush timeout of multi-line records,
+    /// see `--multiline-start` and `--xml-record`.
+    pub multiline: Option<(Records, Duration)>,
+    /// Lines per second read from the source at most, see `--rate`.
+    pub rate: Option<f64>,
+    /// Fields whose values are faked before anything else sees the lines, see `--demo-mask`.
+    pub mask: Option<Mask>,
+    pub pipeline: SharedPipeline,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            retrieval_timeout: Duration::from_millis(10),
+            channel_capacity: 1,
+            drop_policy: DropPolicy::Block,
+            filter: None,
+            multiline: None,
+            rate: None,
+            mask: None,
+            pipeline: Default::default(),
+        }
+    }
+}
+
+/// Lines read from a source, masked as they are read (see `--demo-mask`), throttled to `--rate`
+/// and run through the filter command, multi-line grouping and pipeline stages, in this order.
+///
+/// This is what the streaming mode displays, without any terminal involved,
+/// so that it can be consumed headlessly or rendered by another TUI app.
+pub struct Session {
+    rx: mpsc::Receiver<String>,
+    tasks: Vec<InputTask>,
+    pipeline: SharedPipeline,
+    // Lines dropped by the drop policy or by the collector
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Session {
+    pub fn start(source: &dyn Source, options: SessionOptions) -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::channel(1);
+
+        let mut tasks = Vec::new();
+        let tx = match options.multiline {
+            Some((records, flush_timeout)) => {
+                let (lines_tx, lines_rx) = mpsc::channel(1);
+                tasks.push(match records {
+                    Records::Start(start) => {
+                        spawn::spawn_record_grouper(start, flush_timeout, lines_rx, tx)
+                    }
+                    Records::Xml(tag) => spawn::spawn_xml_grouper(tag, flush_timeout, lines_rx, tx),
+                });
+                lines_tx
+            }
+            None => tx,
+        };
+        let source_tx = match &options.filter {
+            Some((filter_cmd, mode)) => {
+                let (source_tx, source_rx) = mpsc::channel(1);
+                tasks.push(spawn::spawn_filter(filter_cmd, *mode, source_rx, tx)?);
+                source_tx
+            }
+            None => tx,
+        };
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (buffer_tx, buffer_rx) = mpsc::channel(1);
+        tasks.push(spawn::spawn_buffer(
+            options.channel_capacity,
+            options.drop_policy,
+            buffer_rx,
+            source_tx,
+            Arc::clone(&dropped),
+        ));
+        let buffer_tx = match options.rate {
+            Some(rate) => {
+                let (throttle_tx, throttle_rx) = mpsc::channel(1);
+                tasks.push(spawn::spawn_throttle(rate, throttle_rx, buffer_tx));
+                throttle_tx
+            }
+            None => buffer_tx,
+        };
+        let buffer_tx = match options.mask {
+            Some(mask) => {
+                let (mask_tx, mask_rx) = mpsc::channel(1);
+                tasks.push(spawn::spawn_mask(mask, mask_rx, buffer_tx));
+                mask_tx
+            }
+            None => buffer_tx,
+        };
+        tasks.push(source.spawn_with_drops(
+            buffer_tx,
+            options.retrieval_timeout,
+            Arc::clone(&dropped),
+        )?);
+
+        Ok(Self {
+            rx,
+            tasks,
+            pipeline: options.pipeline,
+            dropped,
+        })
+    }
+
+    pub fn pipeline(&self) -> &SharedPipeline {
+        &self.pipeline
+    }
+
+    /// Number of lines dropped so far since the consumer did not keep up with the source,
+    /// or the client with the collector it is attached to.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Counter behind `dropped`, to be read where the session is not at hand.
+    pub fn dropped_counter(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.dropped)
+    }
+
+    /// Receive the next line that passed the pipeline.
+    /// Returns None once the source is exhausted.
+    pub async fn recv(&mut self) -> Option<String> {
+        loop {
+            let line = self.rx.recv().await?;
+            if let Some(line) = self.pipeline.write().await.apply(line) {
+                return Some(line);
+            }
+        }
+    }
+
+    /// Receive the next line matching the query, along with its highlighted form.
+    /// Returns None once the source is exhausted.
+    #[cfg(feature = "tui")]
+    pub async fn next_match(
+        &mut self,
+        query: &str,
+        highlight_style: ContentStyle,
+        case_insensitive: bool,
+    ) -> Option<(String, StyledGraphemes)> {
+        loop {
+            let line = self.recv().await?;
+            if let Some(styled) = highlight(query, &line, highlight_style, case_insensitive) {
+                return Some((line, styled));
+            }
+        }
+    }
+
+    /// Kill the commands and tasks feeding the session.
+    pub async fn stop(self) {
+        for task in self.tasks {
+            if let Some(mut child) = task.child {
+                let _ = child.kill().await;
+            }
+            task.handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, sync::Arc};
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        pipeline::{Pipeline, StageConfig, StageKind},
+        source,
+    };
+
+    #[tokio::test]
+    async fn next_match_reads_file_through_pipeline() {
+        let path = std::env::temp_dir().join(format!("sig-session-{}.log", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"GET /health\nGET /users\nPOST /users\n")
+            .unwrap();
+
+        let pipeline = Pipeline::try_new(&[StageConfig {
+            name: String::from("drop-health"),
+            kind: StageKind::Exclude {
+                pattern: String::from("/health"),
+                field: None,
+            },
+            enabled: true,
+            #[cfg(feature = "tui")]
+            toggle: Default::default(),
+        }])
+        .unwrap();
+        let mut session = Session::start(
+            &source::File(path.clone()),
+            SessionOptions {
+                pipeline: Arc::new(RwLock::new(pipeline)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(session.recv().await.as_deref(), Some("GET /users"));
+        #[cfg(feature = "tui")]
+        {
+            let (line, _) = session
+                .next_match("POST", ContentStyle::default(), false)
+                .await
+                .unwrap();
+            assert_eq!(line, "POST /users");
+        }
+        #[cfg(not(feature = "tui"))]
+        assert_eq!(session.recv().await.as_deref(), Some("POST /users"));
+        assert_eq!(session.recv().await, None);
+
+        session.stop().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mask_applies_before_the_filter_command() {
+        let path =
+            std::env::temp_dir().join(format!("sig-session-mask-{}.log", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"user=alice action=login\n")
+            .unwrap();
+
+        // The field is renamed by the filter, so it could not be masked afterwards.
+        let mut session = Session::start(
+            &source::File(path.clone()),
+            SessionOptions {
+                filter: Some((String::from("sed -u s/user=/who=/"), FilterMode::Replace)),
+                mask: Some(Mask::new(vec![String::from("user")])),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let line = session.recv().await.unwrap();
+        assert!(line.starts_with("who="), "{line}");
+        assert!(!line.contains("alice"), "{line}");
+        assert!(line.ends_with(" action=login"), "{line}");
+
+        session.stop().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn drop_newest_accounts_for_every_line() {
+        let path =
+            std::env::temp_dir().join(format!("sig-session-drop-{}.log", std::process::id()));
+        let content = (1..=100).map(|i| format!("{i}\n")).collect::<String>();
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+
+        let mut session = Session::start(
+            &source::File(path.clone()),
+            SessionOptions {
+                channel_capacity: 5,
+                drop_policy: DropPolicy::DropNewest,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // Let the source run ahead of the consumer.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut received = Vec::new();
+        while let Some(line) = session.recv().await {
+            received.push(line);
+        }
+        assert!(session.dropped() > 0);
+        assert_eq!(received.len() + session.dropped(), 100);
+        assert_eq!(received[0], "1");
+        session.stop().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_slows_down_the_source() {
+        let path =
+            std::env::temp_dir().join(format!("sig-session-rate-{}.log", std::process::id()));
+        let content = (1..=11).map(|i| format!("{i}\n")).collect::<String>();
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+
+        let start = std::time::Instant::now();
+        let mut session = Session::start(
+            &source::File(path.clone()),
+            SessionOptions {
+                rate: Some(100.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut received = 0;
+        while session.recv().await.is_some() {
+            received += 1;
+        }
+        assert_eq!(received, 11);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        session.stop().await;
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,395 @@
+#[cfg(feature = "sinks")]
+use std::future::Future;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{self, error::TrySendError, Sender, UnboundedSender},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use crate::crypt::Cipher;
+
+#[cfg(feature = "sinks")]
+pub mod email;
+#[cfg(feature = "sinks")]
+pub mod otlp;
+pub mod route;
+#[cfg(feature = "sinks")]
+pub mod syslog;
+
+/// Destinations for matched lines, configured under `[sinks]`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SinksConfig {
+    #[cfg(feature = "sinks")]
+    pub email: Option<email::EmailConfig>,
+    #[cfg(feature = "sinks")]
+    pub otlp: Option<otlp::OtlpConfig>,
+    #[cfg(feature = "sinks")]
+    pub syslog: Option<syslog::SyslogConfig>,
+    #[serde(default)]
+    pub routes: Vec<route::RouteConfig>,
+    /// The sinks this build leaves out, to be reported rather than ignored.
+    #[cfg(not(feature = "sinks"))]
+    #[serde(flatten, skip_serializing)]
+    pub unsupported: std::collections::BTreeMap<String, toml::Value>,
+}
+
+/// Number of lines sent to a sink that it has not delivered yet,
+/// counting down as the sink writes, sends or gives up on them.
+#[derive(Clone, Default)]
+pub struct Pending(Arc<AtomicUsize>);
+
+impl Pending {
+    fn add(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account for lines the sink is done with.
+    pub fn done(&self, count: usize) {
+        self.0.fetch_sub(count, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Lines queued ahead of a sink of matched lines, beyond which the arriving ones are dropped
+/// so that a destination that is down neither slows the stream nor grows the queue without bound.
+#[cfg(feature = "sinks")]
+const CAPACITY: usize = 1024;
+
+/// Delay before trying a destination again after it failed,
+/// doubling on every failure in a row up to `max`.
+#[cfg(feature = "sinks")]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    delay: Duration,
+    retry_at: Option<Instant>,
+}
+
+#[cfg(feature = "sinks")]
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            delay: initial,
+            retry_at: None,
+        }
+    }
+
+    /// Whether the destination is to be tried now.
+    pub fn ready(&self) -> bool {
+        self.retry_at.is_none_or(|at| Instant::now() >= at)
+    }
+
+    pub fn failed(&mut self) {
+        self.retry_at = Some(Instant::now() + self.delay);
+        self.delay = (self.delay * 2).min(self.max);
+    }
+
+    pub fn succeeded(&mut self) {
+        self.delay = self.initial;
+        self.retry_at = None;
+    }
+}
+
+/// Send a line to the routes, counting it as pending until they are done with it.
+fn send_to(tx: &UnboundedSender<String>, pending: &Pending, line: &str) {
+    // Counted before sending, so that the sink never counts it down first.
+    pending.add();
+    // A closed channel means the sink has already stopped,
+    // and its error is reported on close.
+    if tx.send(line.to_string()).is_err() {
+        pending.done(1);
+    }
+}
+
+/// The channel to a sink of matched lines.
+#[derive(Clone)]
+struct Matched {
+    tx: Sender<String>,
+    pending: Pending,
+    // Lines dropped while the channel was full
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Matched {
+    fn send(&self, line: &str) {
+        self.pending.add();
+        match self.tx.try_send(line.to_string()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.pending.done(1);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(_)) => self.pending.done(1),
+        }
+    }
+}
+
+/// Sends matched lines to every running sink, and every line to the routes.
+#[derive(Clone, Default)]
+pub struct SinkSender {
+    matched: Vec<Matched>,
+    routes: Option<(UnboundedSender<String>, Pending)>,
+}
+
+impl SinkSender {
+    pub fn send(&self, line: &str) {
+        for matched in &self.matched {
+            matched.send(line);
+        }
+    }
+
+    /// Send a line that passed the pipeline, matched or not, to the routes.
+    pub fn route(&self, line: &str) {
+        if let Some((tx, pending)) = &self.routes {
+            send_to(tx, pending, line);
+        }
+    }
+}
+
+struct Task {
+    name: &'static str,
+    handle: JoinHandle<anyhow::Result<()>>,
+    pending: Pending,
+    dropped: Arc<AtomicUsize>,
+}
+
+/// Spawn a sink of matched lines, fed through a channel of `CAPACITY` lines.
+#[cfg(feature = "sinks")]
+fn spawn_matched<F>(
+    name: &'static str,
+    senders: &mut Vec<Matched>,
+    tasks: &mut Vec<Task>,
+    run: impl FnOnce(mpsc::Receiver<String>, Pending) -> F,
+) where
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(CAPACITY);
+    let matched = Matched {
+        tx,
+        pending: Pending::default(),
+        dropped: Arc::default(),
+    };
+    tasks.push(Task {
+        name,
+        handle: tokio::spawn(run(rx, matched.pending.clone())),
+        pending: matched.pending.clone(),
+        dropped: Arc::clone(&matched.dropped),
+    });
+    senders.push(matched);
+}
+
+/// Background tasks consuming matched lines, kept across retries of the stream.
+pub struct Sinks {
+    sender: SinkSender,
+    tasks: Vec<Task>,
+}
+
+impl Sinks {
+    /// Spawn a task for each configured sink, the routes writing lines encrypted with `cipher`.
+    pub fn spawn(config: &SinksConfig, cipher: Option<Cipher>) -> Self {
+        #[cfg_attr(not(feature = "sinks"), allow(unused_mut))]
+        let mut senders = Vec::new();
+        let mut tasks = Vec::new();
+
+        #[cfg(feature = "sinks")]
+        if let Some(email) = &config.email {
+            spawn_matched("email", &mut senders, &mut tasks, |rx, pending| {
+                email::run(email.clone(), rx, pending)
+            });
+        }
+
+        #[cfg(feature = "sinks")]
+        if let Some(otlp) = &config.otlp {
+            spawn_matched("otlp", &mut senders, &mut tasks, |rx, pending| {
+                otlp::run(otlp.clone(), rx, pending)
+            });
+        }
+
+        #[cfg(feature = "sinks")]
+        if let Some(syslog) = &config.syslog {
+            spawn_matched("syslog", &mut senders, &mut tasks, |rx, pending| {
+                syslog::run(syslog.clone(), rx, pending)
+            });
+        }
+
+        let routes = (!config.routes.is_empty()).then(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let pending = Pending::default();
+            tasks.push(Task {
+                name: "routes",
+                handle: tokio::spawn(route::run(
+                    config.routes.clone(),
+                    rx,
+                    pending.clone(),
+                    cipher,
+                )),
+                pending: pending.clone(),
+                dropped: Arc::default(),
+            });
+            (tx, pending)
+        });
+
+        Self {
+            sender: SinkSender {
+                matched: senders,
+                routes,
+            },
+            tasks,
+        }
+    }
+
+    pub fn sender(&self) -> SinkSender {
+        self.sender.clone()
+    }
+
+    /// Close the channels and wait for every sink to finish its pending work,
+    /// aborting the sinks still busy after `timeout` and reporting their undelivered lines,
+    /// as well as the lines dropped while a sink fell behind.
+    pub async fn close(self, timeout: Duration) -> anyhow::Result<()> {
+        drop(self.sender);
+        let deadline = Instant::now() + timeout;
+        let mut errors = Vec::new();
+        for mut task in self.tasks {
+            let dropped = task.dropped.load(Ordering::Relaxed);
+            if dropped > 0 {
+                errors.push(format!(
+                    "{} sink: {dropped} lines dropped while it fell behind",
+                    task.name
+                ));
+            }
+            match time::timeout_at(deadline, &mut task.handle).await {
+                Ok(joined) => {
+                    if let Err(e) = joined? {
+                        errors.push(format!("{} sink: {e}", task.name));
+                    }
+                }
+                Err(_) => {
+                    task.handle.abort();
+                    errors.push(format!(
+                        "{} sink: {} lines undelivered after {:?}",
+                        task.name,
+                        task.pending.get(),
+                        timeout
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn close_reports_the_lines_of_sinks_past_the_deadline() {
+        let dir = std::env::temp_dir().join(format!("sig-sinks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = SinksConfig::default();
+        config.routes.push(route::RouteConfig {
+            pattern: String::from("ERROR"),
+            path: dir.join("errors.log"),
+        });
+        let mut sinks = Sinks::spawn(&config, None);
+        // A sink stuck on delivering the first line it receives
+        let (tx, mut rx) = mpsc::channel(16);
+        let matched = Matched {
+            tx,
+            pending: Pending::default(),
+            dropped: Arc::default(),
+        };
+        sinks.tasks.push(Task {
+            name: "stuck",
+            handle: tokio::spawn(async move {
+                rx.recv().await;
+                std::future::pending().await
+            }),
+            pending: matched.pending.clone(),
+            dropped: Arc::clone(&matched.dropped),
+        });
+        sinks.sender.matched.push(matched);
+
+        let sender = sinks.sender();
+        for line in ["ERROR disk", "INFO ok", "ERROR full"] {
+            sender.route(line);
+            sender.send(line);
+        }
+        drop(sender);
+        let e = sinks.close(Duration::from_millis(50)).await.unwrap_err();
+        assert_eq!(e.to_string(), "stuck sink: 3 lines undelivered after 50ms");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("errors.log")).unwrap(),
+            "ERROR disk\nERROR full\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lines_beyond_a_full_channel_are_dropped_and_reported() {
+        let mut sinks = Sinks::spawn(&SinksConfig::default(), None);
+        let (tx, mut rx) = mpsc::channel(1);
+        let matched = Matched {
+            tx,
+            pending: Pending::default(),
+            dropped: Arc::default(),
+        };
+        sinks.tasks.push(Task {
+            name: "slow",
+            handle: tokio::spawn(async move {
+                while rx.recv().await.is_some() {}
+                Ok(())
+            }),
+            pending: Pending::default(),
+            dropped: Arc::clone(&matched.dropped),
+        });
+        sinks.sender.matched.push(matched.clone());
+
+        // The sink does not run before the test yields, so only the first line fits.
+        for line in ["a", "b", "c"] {
+            sinks.sender.send(line);
+        }
+        assert_eq!(matched.pending.get(), 1);
+        drop(matched);
+        let e = sinks.close(Duration::from_secs(1)).await.unwrap_err();
+        assert_eq!(e.to_string(), "slow sink: 2 lines dropped while it fell behind");
+    }
+
+    #[cfg(feature = "sinks")]
+    #[tokio::test(start_paused = true)]
+    async fn backoff_doubles_up_to_the_max_until_a_success() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(4));
+        assert!(backoff.ready());
+        for secs in [1, 2, 4, 4] {
+            backoff.failed();
+            time::advance(Duration::from_secs(secs) - Duration::from_millis(1)).await;
+            assert!(!backoff.ready());
+            time::advance(Duration::from_millis(1)).await;
+            assert!(backoff.ready());
+        }
+        backoff.failed();
+        backoff.succeeded();
+        assert!(backoff.ready());
+        backoff.failed();
+        time::advance(Duration::from_secs(1)).await;
+        assert!(backoff.ready());
+    }
+}
@@ -0,0 +1,274 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc::Receiver,
+    time::{self, Duration},
+};
+
+use super::Pending;
+use crate::timestamp;
+
+/// Settings for sending matched lines as a digest email.
+///
+/// The digest is sent over plain SMTP without authentication,
+/// so `server` is expected to be a trusted relay such as a local MTA.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// Address of the SMTP server, e.g. "localhost:25".
+    pub server: String,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    /// Interval to send a digest in seconds.
+    /// If not set, a digest is sent only at the end of the session.
+    pub interval: Option<u64>,
+    /// Number of lines kept for the digest while it cannot be sent,
+    /// beyond which the oldest ones are dropped and counted in the next digest.
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+}
+
+fn default_subject() -> String {
+    String::from("sig digest")
+}
+
+fn default_max_lines() -> usize {
+    10000
+}
+
+/// Time to wait for the SMTP server to accept the connection, a command or the message,
+/// so that a hung server does not hold the digest until the sinks are stopped.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn within<T>(
+    what: &str,
+    future: impl std::future::Future<Output = std::io::Result<T>>,
+) -> anyhow::Result<T> {
+    time::timeout(SMTP_TIMEOUT, future)
+        .await
+        .map_err(|_| anyhow::anyhow!("SMTP server timed out on {what}"))?
+        .map_err(Into::into)
+}
+
+/// Accumulate the received lines and send them as a digest
+/// on every interval and when the channel is closed.
+pub async fn run(
+    config: EmailConfig,
+    mut rx: Receiver<String>,
+    pending: Pending,
+) -> anyhow::Result<()> {
+    let mut digest = Digest::default();
+    let mut maybe_interval = config.interval.map(|secs| {
+        let period = Duration::from_secs(secs.max(1));
+        time::interval_at(time::Instant::now() + period, period)
+    });
+
+    loop {
+        tokio::select! {
+            maybe_line = rx.recv() => match maybe_line {
+                Some(line) => {
+                    if digest.push(line, config.max_lines) {
+                        pending.done(1);
+                    }
+                }
+                None => break,
+            },
+            _ = async {
+                match &mut maybe_interval {
+                    Some(interval) => interval.tick().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                // Keep the lines to retry with the next digest if sending fails.
+                if send_digest(&config, &digest).await.is_ok() {
+                    pending.done(digest.lines.len());
+                    digest = Digest::default();
+                }
+            }
+        }
+    }
+
+    send_digest(&config, &digest).await?;
+    pending.done(digest.lines.len());
+    Ok(())
+}
+
+/// Lines waiting to be sent, and the number of older ones dropped since the last digest.
+#[derive(Default)]
+struct Digest {
+    lines: VecDeque<String>,
+    dropped: usize,
+}
+
+impl Digest {
+    /// Add a line, dropping the oldest one if the digest holds `max_lines` already.
+    /// Returns whether a line was dropped.
+    fn push(&mut self, line: String, max_lines: usize) -> bool {
+        self.lines.push_back(line);
+        if self.lines.len() > max_lines.max(1) {
+            self.lines.pop_front();
+            self.dropped += 1;
+            return true;
+        }
+        false
+    }
+}
+
+async fn send_digest(config: &EmailConfig, digest: &Digest) -> anyhow::Result<()> {
+    if digest.lines.is_empty() {
+        return Ok(());
+    }
+
+    let stream = within("connect", TcpStream::connect(&config.server)).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    expect_reply(&mut reader, 220).await?;
+    let domain = config.from.rsplit('@').next().unwrap_or("localhost");
+    command(&mut writer, &mut reader, &format!("EHLO {domain}"), 250).await?;
+    command(
+        &mut writer,
+        &mut reader,
+        &format!("MAIL FROM:<{}>", config.from),
+        250,
+    )
+    .await?;
+    for to in &config.to {
+        command(&mut writer, &mut reader, &format!("RCPT TO:<{to}>"), 250).await?;
+    }
+    command(&mut writer, &mut reader, "DATA", 354).await?;
+    within(
+        "message",
+        writer.write_all(message(config, digest, SystemTime::now()).as_bytes()),
+    )
+    .await?;
+    command(&mut writer, &mut reader, ".", 250).await?;
+    command(&mut writer, &mut reader, "QUIT", 221).await?;
+    Ok(())
+}
+
+async fn command<W, R>(writer: &mut W, reader: &mut R, line: &str, code: u16) -> anyhow::Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    R: AsyncBufReadExt + Unpin,
+{
+    within(
+        "command",
+        writer.write_all(format!("{line}\r\n").as_bytes()),
+    )
+    .await?;
+    within("command", writer.flush()).await?;
+    expect_reply(reader, code).await
+}
+
+/// Read a (possibly multi-line) reply and check its status code.
+async fn expect_reply<R: AsyncBufReadExt + Unpin>(reader: &mut R, code: u16) -> anyhow::Result<()> {
+    loop {
+        let mut line = String::new();
+        if within("reply", reader.read_line(&mut line)).await? == 0 {
+            return Err(anyhow::anyhow!("SMTP connection closed unexpectedly"));
+        }
+        // The last line of a reply has a space after the status code.
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        return match line.get(..3).and_then(|s| s.parse::<u16>().ok()) {
+            Some(received) if received == code => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "Unexpected SMTP reply: {}",
+                line.trim_end()
+            )),
+        };
+    }
+}
+
+/// Build the message sent after DATA, with the lines of multi-line records ended by CRLF
+/// and dot-stuffed one by one, so that none of them can end the message early.
+fn message(config: &EmailConfig, digest: &Digest, now: SystemTime) -> String {
+    let mut message = format!(
+        "Date: {}\r\nFrom: <{}>\r\nTo: {}\r\nSubject: {} ({} lines)\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        timestamp::rfc5322(now),
+        config.from,
+        config
+            .to
+            .iter()
+            .map(|to| format!("<{to}>"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        config.subject,
+        digest.lines.len(),
+    );
+    if digest.dropped > 0 {
+        message.push_str(&format!(
+            "({} earlier lines were dropped while the digest could not be sent)\r\n\r\n",
+            digest.dropped
+        ));
+    }
+    for line in digest.lines.iter().flat_map(|record| record.split('\n')) {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_escapes_leading_dots() {
+        let config = EmailConfig {
+            server: String::from("localhost:25"),
+            from: String::from("sig@localhost"),
+            to: vec![String::from("a@example.com"), String::from("b@example.com")],
+            subject: default_subject(),
+            interval: None,
+            max_lines: default_max_lines(),
+        };
+        let digest = Digest {
+            lines: VecDeque::from([
+                String::from("ok"),
+                String::from(".hidden"),
+                String::from("record\n.\n.inner\r\nlast"),
+            ]),
+            dropped: 0,
+        };
+        let message = message(&config, &digest, std::time::UNIX_EPOCH);
+        assert!(message.starts_with("Date: Thu, 01 Jan 1970 00:00:00 +0000\r\n"));
+        assert!(message.contains("To: <a@example.com>, <b@example.com>\r\n"));
+        assert!(message.contains("Subject: sig digest (3 lines)\r\n"));
+        assert!(message.ends_with("\r\n\r\nok\r\n..hidden\r\nrecord\r\n..\r\n..inner\r\nlast\r\n"));
+        assert!(!message.replace("\r\n", "").contains('\n'));
+    }
+    #[test]
+    fn digest_keeps_the_latest_lines_and_counts_the_dropped() {
+        let mut digest = Digest::default();
+        let dropped = (0..5)
+            .filter(|i| digest.push(format!("line {i}"), 3))
+            .count();
+        assert_eq!(dropped, 2);
+        assert_eq!(digest.lines, ["line 2", "line 3", "line 4"]);
+
+        let config = EmailConfig {
+            server: String::from("localhost:25"),
+            from: String::from("sig@localhost"),
+            to: vec![String::from("a@example.com")],
+            subject: default_subject(),
+            interval: None,
+            max_lines: 3,
+        };
+        let message = message(&config, &digest, std::time::UNIX_EPOCH);
+        assert!(message.contains("Subject: sig digest (3 lines)\r\n"));
+        assert!(message.ends_with(
+            "\r\n\r\n(2 earlier lines were dropped while the digest could not be sent)\r\n\r\nline 2\r\nline 3\r\nline 4\r\n"
+        ));
+    }
+}
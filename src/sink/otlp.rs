@@ -0,0 +1,338 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc::Receiver,
+    time::{self, Duration},
+};
+
+use super::{Backoff, Pending};
+use crate::fields::{self, Level};
+
+/// Settings for exporting matched lines to an OpenTelemetry collector
+/// with OTLP/HTTP and JSON encoding.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OtlpConfig {
+    /// URL of the logs endpoint, e.g. "http://localhost:4318/v1/logs".
+    /// Only plain HTTP is supported.
+    pub endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Interval to export the buffered records in milliseconds.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Number of buffered records that triggers an export before the interval elapses.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Number of records kept for the next attempt while the collector is unavailable,
+    /// beyond which the oldest ones are dropped.
+    /// Failed exports are retried after the interval, doubling up to `MAX_BACKOFF`.
+    #[serde(default = "default_max_buffered")]
+    pub max_buffered: usize,
+}
+
+fn default_service_name() -> String {
+    String::from("sig")
+}
+
+fn default_interval() -> u64 {
+    1000
+}
+
+fn default_batch_size() -> usize {
+    512
+}
+
+fn default_max_buffered() -> usize {
+    10000
+}
+
+/// Time to wait for the collector to accept the connection and answer an export.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest delay between the attempts to export to a collector that keeps failing,
+/// starting from the interval.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Buffer a record, dropping the oldest one if `max` records are already buffered.
+/// Returns whether a record was dropped.
+fn buffer(batch: &mut VecDeque<Value>, record: Value, max: usize) -> bool {
+    let dropped = batch.len() >= max.max(1);
+    if dropped {
+        batch.pop_front();
+    }
+    batch.push_back(record);
+    dropped
+}
+
+/// Export the received lines in batches
+/// on every interval, when the batch is full, and when the channel is closed.
+/// After a failed export the next attempt waits for the backoff instead.
+pub async fn run(
+    config: OtlpConfig,
+    mut rx: Receiver<String>,
+    pending: Pending,
+) -> anyhow::Result<()> {
+    let mut batch = VecDeque::new();
+    let mut dropped = 0usize;
+    let period = Duration::from_millis(config.interval.max(1));
+    let mut interval = time::interval_at(time::Instant::now() + period, period);
+    let mut backoff = Backoff::new(period, MAX_BACKOFF);
+
+    loop {
+        tokio::select! {
+            maybe_line = rx.recv() => match maybe_line {
+                Some(line) => {
+                    let record = log_record(&line, SystemTime::now());
+                    if buffer(&mut batch, record, config.max_buffered) {
+                        dropped += 1;
+                        pending.done(1);
+                    }
+                    if batch.len() >= config.batch_size && backoff.ready() {
+                        export(&config, &mut batch, &pending, &mut backoff).await;
+                    }
+                }
+                None => break,
+            },
+            _ = interval.tick() => {
+                if backoff.ready() {
+                    export(&config, &mut batch, &pending, &mut backoff).await;
+                }
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        post(
+            &config.endpoint,
+            &request_body(&config, batch.make_contiguous()),
+        )
+        .await?;
+        pending.done(batch.len());
+    }
+    if dropped > 0 {
+        return Err(anyhow::anyhow!(
+            "{dropped} lines were dropped while the OTLP collector was unavailable"
+        ));
+    }
+    Ok(())
+}
+
+/// Export the batch, keeping the records for the next attempt if the collector is unavailable.
+async fn export(
+    config: &OtlpConfig,
+    batch: &mut VecDeque<Value>,
+    pending: &Pending,
+    backoff: &mut Backoff,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    match post(
+        &config.endpoint,
+        &request_body(config, batch.make_contiguous()),
+    )
+    .await
+    {
+        Ok(()) => {
+            backoff.succeeded();
+            pending.done(batch.len());
+            batch.clear();
+        }
+        Err(_) => backoff.failed(),
+    }
+}
+
+fn severity_number(level: Level) -> u8 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+        Level::Fatal => 21,
+    }
+}
+
+/// Convert a line into an OTLP LogRecord, with its parsed fields as attributes.
+fn log_record(line: &str, observed: SystemTime) -> Value {
+    let nanos = observed
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string();
+    let fields = fields::parse(line);
+    let mut record = json!({
+        "timeUnixNano": nanos,
+        "observedTimeUnixNano": nanos,
+        "body": { "stringValue": line },
+        "attributes": fields
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+            .collect::<Vec<Value>>(),
+    });
+    if let Some(level) = fields::level(&fields) {
+        record["severityText"] = json!(level.as_str());
+        record["severityNumber"] = json!(severity_number(level));
+    }
+    record
+}
+
+fn request_body(config: &OtlpConfig, records: &[Value]) -> String {
+    json!({
+        "resourceLogs": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": config.service_name } },
+                ],
+            },
+            "scopeLogs": [{
+                "scope": { "name": "sig", "version": env!("CARGO_PKG_VERSION") },
+                "logRecords": records,
+            }],
+        }],
+    })
+    .to_string()
+}
+
+/// Send a JSON body with HTTP/1.1 POST and check for a 2xx status,
+/// giving up after `EXPORT_TIMEOUT`.
+async fn post(endpoint: &str, body: &str) -> anyhow::Result<()> {
+    let rest = endpoint
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("Unsupported OTLP endpoint: {endpoint}"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/v1/logs"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let response = time::timeout(EXPORT_TIMEOUT, async {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("OTLP collector timed out"))??;
+    let response = String::from_utf8_lossy(&response);
+    let status = response
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or_default();
+    if !status.starts_with('2') {
+        return Err(anyhow::anyhow!("OTLP export failed with status {status:?}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OtlpConfig {
+        OtlpConfig {
+            endpoint: String::from("http://localhost:4318/v1/logs"),
+            service_name: default_service_name(),
+            interval: default_interval(),
+            batch_size: 2,
+            max_buffered: 3,
+        }
+    }
+
+    #[test]
+    fn log_record_carries_the_fields_and_level() {
+        let observed = UNIX_EPOCH + Duration::from_millis(1500);
+        let record = log_record("level=warn msg=slow dur=12ms", observed);
+        assert_eq!(record["timeUnixNano"], "1500000000");
+        assert_eq!(
+            record["body"]["stringValue"],
+            "level=warn msg=slow dur=12ms"
+        );
+        assert_eq!(record["severityText"], "WARN");
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(
+            record["attributes"][1],
+            json!({ "key": "msg", "value": { "stringValue": "slow" } })
+        );
+
+        let record = log_record("plain text", observed);
+        assert!(record.get("severityText").is_none());
+        assert_eq!(record["attributes"], json!([]));
+    }
+
+    #[test]
+    fn request_body_wraps_the_records_and_buffer_is_bounded() {
+        let config = config();
+        let mut batch = VecDeque::new();
+        let dropped = (0..5)
+            .filter(|i| buffer(&mut batch, json!(i), config.max_buffered))
+            .count();
+        assert_eq!(dropped, 2);
+
+        let body: Value =
+            serde_json::from_str(&request_body(&config, batch.make_contiguous())).unwrap();
+        let logs = &body["resourceLogs"][0];
+        assert_eq!(
+            logs["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "sig" } })
+        );
+        assert_eq!(logs["scopeLogs"][0]["scope"]["name"], "sig");
+        assert_eq!(logs["scopeLogs"][0]["logRecords"], json!([2, 3, 4]));
+    }
+
+    #[tokio::test]
+    async fn failed_exports_wait_for_the_backoff() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let collector = tokio::spawn(async move {
+            let mut attempts = 0;
+            while let Ok((mut conn, _)) = listener.accept().await {
+                attempts += 1;
+                let mut request = [0; 65536];
+                let _ = conn.read(&mut request).await;
+                let _ = conn
+                    .write_all(b"HTTP/1.1 503 Service Unavailable\r\n\r\n")
+                    .await;
+                if attempts == 2 {
+                    break;
+                }
+            }
+            attempts
+        });
+        let config = OtlpConfig {
+            endpoint: format!("http://{address}/v1/logs"),
+            interval: 60_000,
+            ..config()
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let pending = Pending::default();
+        for i in 0..6 {
+            pending.add();
+            tx.try_send(format!("line {i}")).unwrap();
+        }
+        drop(tx);
+
+        let e = run(config, rx, pending.clone()).await.unwrap_err();
+        assert_eq!(e.to_string(), "OTLP export failed with status \"503\"");
+        // The first full batch, then the last attempt on close rather than one per line
+        assert_eq!(collector.await.unwrap(), 2);
+        assert_eq!(pending.get(), 3);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{borrow::Cow, path::PathBuf};
+#[cfg(unix)]
+use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncWriteExt, BufWriter},
+    sync::mpsc::UnboundedReceiver,
+};
+
+use super::Pending;
+use crate::crypt::Cipher;
+
+/// Lines matching `pattern` are appended to the file at `path`,
+/// whatever the query is, configured as `[[sinks.routes]]`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RouteConfig {
+    pub pattern: String,
+    pub path: PathBuf,
+}
+
+/// Write each received line to the files of the routes it matches,
+/// encrypted with `cipher` if any.
+pub async fn run(
+    routes: Vec<RouteConfig>,
+    mut rx: UnboundedReceiver<String>,
+    pending: Pending,
+    cipher: Option<Cipher>,
+) -> anyhow::Result<()> {
+    let mut outputs = Vec::new();
+    for route in &routes {
+        let pattern = Regex::new(&route.pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid pattern of route {:?}: {e}", route.pattern))?;
+        // Created readable by the owner only, as the lines may be sensitive.
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options
+            .open(&route.path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open {}: {e}", route.path.display()))?;
+        // The mode only applies to a file created here; an existing one is restricted too.
+        #[cfg(unix)]
+        if file.metadata().await?.is_file() {
+            file.set_permissions(Permissions::from_mode(0o600))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to restrict {}: {e}", route.path.display()))?;
+        }
+        outputs.push((pattern, BufWriter::new(file)));
+    }
+
+    // Lines written since the last flush, which are delivered once flushed
+    let mut unflushed = 0;
+    while let Some(line) = rx.recv().await {
+        unflushed += 1;
+        let written = match &cipher {
+            Some(cipher) => Cow::Owned(cipher.encrypt(&line)),
+            None => Cow::Borrowed(&line),
+        };
+        for (pattern, writer) in &mut outputs {
+            if pattern.is_match(&line) {
+                writer.write_all(written.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+            }
+        }
+        // Keep the files current while the stream is idle.
+        if rx.is_empty() {
+            for (_, writer) in &mut outputs {
+                writer.flush().await?;
+            }
+            pending.done(unflushed);
+            unflushed = 0;
+        }
+    }
+
+    for (_, writer) in &mut outputs {
+        writer.flush().await?;
+    }
+    pending.done(unflushed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn lines_are_appended_to_matching_routes() {
+        let dir = std::env::temp_dir().join(format!("sig-route-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let route = |pattern: &str, name: &str| RouteConfig {
+            pattern: pattern.to_string(),
+            path: dir.join(name),
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        for line in [
+            "ERROR disk",
+            "GET /slow 1200ms",
+            "INFO ok",
+            "ERROR slow 1500ms",
+        ] {
+            tx.send(line.to_string()).unwrap();
+        }
+        drop(tx);
+        run(
+            vec![route("ERROR", "errors.log"), route(r"\d{4}ms", "slow.log")],
+            rx,
+            Pending::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("errors.log"), "ERROR disk\nERROR slow 1500ms\n");
+        assert_eq!(read("slow.log"), "GET /slow 1200ms\nERROR slow 1500ms\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn existing_files_are_made_private() {
+        let path = std::env::temp_dir().join(format!("sig-route-mode-{}", std::process::id()));
+        std::fs::write(&path, "before\n").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        drop(tx);
+        let route = RouteConfig {
+            pattern: ".".to_string(),
+            path: path.clone(),
+        };
+        run(vec![route], rx, Pending::default(), None)
+            .await
+            .unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+use std::time::SystemTime;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpStream, UdpSocket},
+    sync::mpsc::Receiver,
+    time::{self, Duration},
+};
+
+use super::{Backoff, Pending};
+use crate::{
+    fields::{self, Level},
+    timestamp,
+};
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Udp,
+    /// TCP with octet-counting framing (RFC 6587).
+    Tcp,
+}
+
+/// Settings for forwarding matched lines as RFC 5424 syslog messages.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyslogConfig {
+    /// Address of the collector, e.g. "127.0.0.1:514".
+    pub address: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+    /// Facility code, 1 (user-level messages) by default.
+    #[serde(default = "default_facility")]
+    pub facility: u8,
+}
+
+fn default_app_name() -> String {
+    String::from("sig")
+}
+
+fn default_facility() -> u8 {
+    1
+}
+
+/// Time to wait for the collector to accept a TCP connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First and longest delays before connecting again after a failed attempt.
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The connection to the collector, made on the first message and again after a failure,
+/// so that a collector down at launch or restarted later is sent the next messages.
+/// While the collector is down, the messages are dropped until the backoff elapses
+/// rather than each waiting for a connection of its own.
+struct Transport {
+    protocol: Protocol,
+    conn: Option<Conn>,
+    backoff: Backoff,
+}
+
+enum Conn {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Transport {
+    fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            conn: None,
+            backoff: Backoff::new(RECONNECT_BACKOFF, MAX_BACKOFF),
+        }
+    }
+
+    async fn connect(protocol: Protocol, address: &str) -> anyhow::Result<Conn> {
+        Ok(match protocol {
+            Protocol::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(address).await?;
+                Conn::Udp(socket)
+            }
+            Protocol::Tcp => Conn::Tcp(
+                time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+                    .await
+                    .map_err(|_| anyhow::anyhow!("Timed out connecting to {address}"))??,
+            ),
+        })
+    }
+
+    async fn send(&mut self, address: &str, message: &str) -> anyhow::Result<()> {
+        let conn = match &mut self.conn {
+            Some(conn) => conn,
+            None if !self.backoff.ready() => {
+                return Err(anyhow::anyhow!("Waiting to reconnect to {address}"));
+            }
+            None => match Self::connect(self.protocol, address).await {
+                Ok(conn) => {
+                    self.backoff.succeeded();
+                    self.conn.insert(conn)
+                }
+                Err(e) => {
+                    self.backoff.failed();
+                    return Err(e);
+                }
+            },
+        };
+        match conn {
+            Conn::Udp(socket) => {
+                socket.send(message.as_bytes()).await?;
+            }
+            Conn::Tcp(stream) => {
+                let framed = format!("{} {message}", message.len());
+                if let Err(e) = stream.write_all(framed.as_bytes()).await {
+                    self.conn = None;
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Forward each received line as a syslog message.
+pub async fn run(
+    config: SyslogConfig,
+    mut rx: Receiver<String>,
+    pending: Pending,
+) -> anyhow::Result<()> {
+    let mut transport = Transport::new(config.protocol);
+    let formatter = Formatter::new(&config);
+    let mut undelivered = 0usize;
+
+    while let Some(line) = rx.recv().await {
+        let message = formatter.format(&line, SystemTime::now());
+        if transport.send(&config.address, &message).await.is_err() {
+            undelivered += 1;
+        }
+        pending.done(1);
+    }
+
+    if undelivered > 0 {
+        return Err(anyhow::anyhow!(
+            "{undelivered} lines could not be forwarded to syslog"
+        ));
+    }
+    Ok(())
+}
+
+struct Formatter {
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    procid: u32,
+    rfc3339: Regex,
+}
+
+impl Formatter {
+    fn new(config: &SyslogConfig) -> Self {
+        Self {
+            facility: config.facility.min(23),
+            hostname: hostname(),
+            app_name: config.app_name.clone(),
+            procid: std::process::id(),
+            rfc3339: Regex::new(
+                r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d{1,6})?(Z|[+-]\d{2}:\d{2})$",
+            )
+            .expect("valid regex"),
+        }
+    }
+
+    /// Build a `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID SD MSG` message,
+    /// taking the severity and timestamp from the line's fields when available.
+    fn format(&self, line: &str, now: SystemTime) -> String {
+        let fields = fields::parse(line);
+        let severity = match fields::level(&fields) {
+            Some(Level::Fatal) => 2,
+            Some(Level::Error) => 3,
+            Some(Level::Warn) => 4,
+            Some(Level::Info) | None => 6,
+            Some(Level::Debug) | Some(Level::Trace) => 7,
+        };
+        let time = fields::find(&fields, &["time", "ts", "timestamp", "@timestamp"])
+            .filter(|t| self.rfc3339.is_match(t))
+            .map(String::from)
+            .unwrap_or_else(|| timestamp::rfc3339(now));
+
+        format!(
+            "<{}>1 {time} {} {} {} - - {line}",
+            self.facility as u16 * 8 + severity,
+            self.hostname,
+            self.app_name,
+            self.procid,
+        )
+    }
+}
+
+/// The name of the host, or the nil value `-` if it is unknown or not valid in a message.
+fn hostname() -> String {
+    #[cfg(unix)]
+    let name = {
+        let mut buf = [0u8; 256];
+        // SAFETY: the buffer is valid for writes of its length.
+        (unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0).then(|| {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        })
+    };
+    #[cfg(windows)]
+    let name = std::env::var("COMPUTERNAME").ok();
+    #[cfg(not(any(unix, windows)))]
+    let name = None::<String>;
+    name.filter(|h| !h.is_empty() && h.len() <= 255 && h.bytes().all(|b| b.is_ascii_graphic()))
+        .unwrap_or_else(|| String::from("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn tcp_reconnects_once_the_backoff_elapses() {
+        // A port nothing listens on yet
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut transport = Transport::new(Protocol::Tcp);
+        let delay = Duration::from_millis(50);
+        transport.backoff = Backoff::new(delay, delay);
+
+        assert!(transport.send(&address, "lost").await.is_err());
+        let listener = tokio::net::TcpListener::bind(&address).await.unwrap();
+        assert_eq!(
+            transport
+                .send(&address, "skipped")
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!("Waiting to reconnect to {address}")
+        );
+        time::sleep(delay).await;
+        transport.send(&address, "delivered").await.unwrap();
+        let (mut conn, _) = listener.accept().await.unwrap();
+        drop(transport);
+
+        let mut received = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut conn, &mut received)
+            .await
+            .unwrap();
+        assert_eq!(received, "9 delivered");
+    }
+
+    #[test]
+    fn format_keeps_parsed_severity_and_timestamp() {
+        let mut formatter = Formatter::new(&SyslogConfig {
+            address: String::from("127.0.0.1:514"),
+            protocol: Protocol::Udp,
+            app_name: default_app_name(),
+            facility: default_facility(),
+        });
+        formatter.hostname = String::from("host");
+        formatter.procid = 42;
+        assert_eq!(
+            formatter.format(
+                "ts=2024-05-01T10:00:00Z level=error msg=boom",
+                UNIX_EPOCH
+            ),
+            "<11>1 2024-05-01T10:00:00Z host sig 42 - - ts=2024-05-01T10:00:00Z level=error msg=boom"
+        );
+        assert_eq!(
+            formatter.format("plain", UNIX_EPOCH),
+            "<14>1 1970-01-01T00:00:00.000000Z host sig 42 - - plain"
+        );
+    }
+}
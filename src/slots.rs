@@ -0,0 +1,73 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Queries saved to the slots 1-9 while streaming, recalled with Alt+1-9.
+///
+/// They are kept in `<config>.slots.toml` next to the configuration file,
+/// so that each configuration file (e.g. one per team or service) has slots of its own.
+#[derive(Default)]
+pub struct QuerySlots {
+    path: Option<PathBuf>,
+    slots: BTreeMap<String, String>,
+}
+
+impl QuerySlots {
+    /// Load the slots saved for the configuration file, starting empty if there are none.
+    pub fn load(config_file: Option<&Path>) -> Self {
+        let path = config_file.map(|config_file| {
+            let stem = config_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            config_file.with_file_name(format!("{stem}.slots.toml"))
+        });
+        let slots = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, slots }
+    }
+
+    pub fn get(&self, slot: char) -> Option<&str> {
+        self.slots.get(&slot.to_string()).map(String::as_str)
+    }
+
+    /// Save the query to the slot, writing all the slots to the file.
+    /// The slot is kept for the session even if the file cannot be written.
+    pub fn save(&mut self, slot: char, query: &str) -> anyhow::Result<()> {
+        self.slots.insert(slot.to_string(), query.to_string());
+        if let Some(path) = &self.path {
+            std::fs::write(path, toml::to_string(&self.slots)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_slots_are_loaded_for_the_same_config_file() {
+        let dir = std::env::temp_dir().join(format!("sig-slots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_file = dir.join("team.toml");
+
+        let mut slots = QuerySlots::load(Some(&config_file));
+        slots.save('3', "ERROR|WARN").unwrap();
+        assert!(dir.join("team.slots.toml").exists());
+
+        let slots = QuerySlots::load(Some(&config_file));
+        assert_eq!(slots.get('3'), Some("ERROR|WARN"));
+        assert_eq!(slots.get('1'), None);
+        assert_eq!(
+            QuerySlots::load(Some(&dir.join("other.toml"))).get('3'),
+            None
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use tokio::{sync::mpsc, time::Duration};
+
+use crate::spawn::{self, InputTask};
+
+/// Where the lines come from.
+pub trait Source: Send + Sync {
+    /// Start reading lines, sending them to `tx` until the source is exhausted.
+    fn spawn(
+        &self,
+        tx: mpsc::Sender<String>,
+        retrieval_timeout: Duration,
+    ) -> anyhow::Result<InputTask>;
+
+    /// Start reading lines as `spawn` does, also counting in `dropped`
+    /// the lines lost before they reach `tx`, e.g. by a collector this client fell behind.
+    fn spawn_with_drops(
+        &self,
+        tx: mpsc::Sender<String>,
+        retrieval_timeout: Duration,
+        _dropped: Arc<AtomicUsize>,
+    ) -> anyhow::Result<InputTask> {
+        self.spawn(tx, retrieval_timeout)
+    }
+
+    /// Whether spawning again reads the lines afresh, which allows retries.
+    fn restartable(&self) -> bool {
+        false
+    }
+}
+
+/// Lines piped into the process.
+pub struct Stdin;
+
+impl Source for Stdin {
+    fn spawn(
+        &self,
+        tx: mpsc::Sender<String>,
+        retrieval_timeout: Duration,
+    ) -> anyhow::Result<InputTask> {
+        spawn::spawn_stdin_sender(tx, retrieval_timeout)
+    }
+}
+
+/// Lines written to stdout and stderr by a command, e.g. "stern --context kind-kind etcd".
+pub struct Cmd(pub String);
+
+impl Source for Cmd {
+    fn spawn(
+        &self,
+        tx: mpsc::Sender<String>,
+        retrieval_timeout: Duration,
+    ) -> anyhow::Result<InputTask> {
+        spawn::spawn_cmd_result_sender(&self.0, tx, retrieval_timeout)
+    }
+
+    fn restartable(&self) -> bool {
+        true
+    }
+}
+
+/// Lines of a file.
+pub struct File(pub PathBuf);
+
+impl Source for File {
+    fn spawn(
+        &self,
+        tx: mpsc::Sender<String>,
+        _retrieval_timeout: Duration,
+    ) -> anyhow::Result<InputTask> {
+        spawn::spawn_file_sender(&self.0, tx)
+    }
+
+    fn restartable(&self) -> bool {
+        true
+    }
+}
+
+/// Lines served by a headless collector (`sig serve`) on a Unix socket.
+/// Every attach replays the lines buffered by the collector, which allows retries.
+#[cfg(all(unix, feature = "server"))]
+pub struct Socket(pub PathBuf);
+
+#[cfg(all(unix, feature = "server"))]
+impl Source for Socket {
+    fn spawn(
+        &self,
+        tx: mpsc::Sender<String>,
+        retrieval_timeout: Duration,
+    ) -> anyhow::Result<InputTask> {
+        self.spawn_with_drops(tx, retrieval_timeout, Default::default())
+    }
+
+    fn spawn_with_drops(
+        &self,
+        tx: mpsc::Sender<String>,
+        _retrieval_timeout: Duration,
+        dropped: Arc<AtomicUsize>,
+    ) -> anyhow::Result<InputTask> {
+        spawn::spawn_socket_sender(&self.0, tx, dropped)
+    }
+
+    fn restartable(&self) -> bool {
+        true
+    }
+}
@@ -1,14 +1,29 @@
-use std::process::Stdio;
+use std::{
+    collections::VecDeque,
+    path::Path,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
+#[cfg(feature = "tui")]
+use clap::ValueEnum;
+use regex::Regex;
 use tokio::{
-    io::{self, AsyncBufReadExt, BufReader},
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
     process::{Child, Command},
     sync::mpsc,
     task::JoinHandle,
-    time::{timeout, Duration},
+    time::{timeout, Duration, Instant},
 };
 
-/// A task that reads lines from a source (stdin or command output) and sends them through an mpsc channel.
+#[cfg(all(unix, feature = "server"))]
+use crate::server::Relayed;
+use crate::{mask::Mask, xml};
+
+/// A task that reads lines from a source (stdin, command output, file or socket) and sends them through an mpsc channel.
 pub struct InputTask {
     pub handle: JoinHandle<anyhow::Result<()>>,
     // None if the task reads from stdin
@@ -44,6 +59,56 @@ pub fn spawn_stdin_sender(
     })
 }
 
+/// Spawn a task to read a file and send lines to the provided mpsc sender.
+pub fn spawn_file_sender(path: &Path, tx: mpsc::Sender<String>) -> anyhow::Result<InputTask> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {e}", path.display()))?;
+    let mut reader = BufReader::new(tokio::fs::File::from_std(file)).lines();
+
+    Ok(InputTask {
+        handle: tokio::spawn(async move {
+            while let Some(line) = reader.next_line().await? {
+                let escaped = strip_ansi_escapes::strip_str(line.replace(['\n', '\t'], " "));
+                tx.send(escaped).await?;
+            }
+            Ok(())
+        }),
+        child: None,
+    })
+}
+
+/// Spawn a task to read the lines served by `sig serve` on the Unix socket
+/// and send them to the provided mpsc sender,
+/// adding the lines the collector could not relay in time to `dropped`.
+#[cfg(all(unix, feature = "server"))]
+pub fn spawn_socket_sender(
+    path: &Path,
+    tx: mpsc::Sender<String>,
+    dropped: Arc<AtomicUsize>,
+) -> anyhow::Result<InputTask> {
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .map_err(|e| anyhow::anyhow!("Failed to attach to {}: {e}", path.display()))?;
+    stream.set_nonblocking(true)?;
+    let mut reader = BufReader::new(tokio::net::UnixStream::from_std(stream)?).lines();
+
+    Ok(InputTask {
+        handle: tokio::spawn(async move {
+            // What the collector publishes, read by `server::published` if needed
+            reader.next_line().await?;
+            while let Some(line) = reader.next_line().await? {
+                match serde_json::from_str(&line)? {
+                    Relayed::Line(line) => tx.send(line).await?,
+                    Relayed::Dropped { dropped: count } => {
+                        dropped.fetch_add(count, Ordering::Relaxed);
+                    }
+                }
+            }
+            Ok(())
+        }),
+        child: None,
+    })
+}
+
 /// Spawn a command and read its stdout and stderr, sending lines to the provided mpsc sender.
 pub fn spawn_cmd_result_sender(
     cmdstr: &str,
@@ -111,3 +176,527 @@ pub fn spawn_cmd_result_sender(
         child: Some(child),
     })
 }
+
+/// How the output of the filter command is combined with the original line.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "tui", derive(ValueEnum))]
+pub enum FilterMode {
+    /// Show the output of the command instead of the line.
+    #[default]
+    Replace,
+    /// Show the output of the command after the line.
+    /// This assumes that the command writes exactly one line per input line.
+    Annotate,
+}
+
+/// Spawn a long-lived command that lines from `rx` are streamed through,
+/// sending its output to the provided mpsc sender.
+pub fn spawn_filter(
+    cmdstr: &str,
+    mode: FilterMode,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+) -> anyhow::Result<InputTask> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmdstr)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow::anyhow!("stdin is not available"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow::anyhow!("stdout is not available"))?;
+    let mut stdout_reader = BufReader::new(stdout).lines();
+    // Lines written to the command and waiting for their annotation
+    let (pending_tx, mut pending_rx) = mpsc::unbounded_channel::<String>();
+
+    let writer = async move {
+        while let Some(line) = rx.recv().await {
+            if let FilterMode::Annotate = mode {
+                pending_tx.send(line.clone())?;
+            }
+            let written = async {
+                stdin.write_all(line.as_bytes()).await?;
+                stdin.write_all(b"\n").await?;
+                stdin.flush().await
+            }
+            .await;
+            match written {
+                Ok(()) => {}
+                // The command exited without reading all the lines (e.g. `head`),
+                // which ends the stream once its output is read.
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        // Closing stdin lets the command flush its output and exit.
+        drop(stdin);
+        Ok::<(), anyhow::Error>(())
+    };
+
+    let reader = async move {
+        while let Some(output) = stdout_reader.next_line().await? {
+            let escaped = strip_ansi_escapes::strip_str(output.replace(['\n', '\t'], " "));
+            let line = match mode {
+                FilterMode::Replace => escaped,
+                FilterMode::Annotate => match pending_rx.recv().await {
+                    Some(original) => format!("{original} » {escaped}"),
+                    None => escaped,
+                },
+            };
+            tx.send(line).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+
+    Ok(InputTask {
+        handle: tokio::spawn(async move {
+            tokio::try_join!(writer, reader)?;
+            Ok(())
+        }),
+        child: Some(child),
+    })
+}
+
+/// What to do with a line arriving while the buffer ahead of a slow consumer is full.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "tui", derive(ValueEnum))]
+pub enum DropPolicy {
+    /// Wait for room, slowing down the source.
+    #[default]
+    Block,
+    /// Drop the oldest buffered line to make room.
+    DropOldest,
+    /// Drop the arriving line.
+    DropNewest,
+}
+
+/// Spawn a task that buffers up to `capacity` lines from `rx` until `tx` can take them,
+/// applying `policy` while the buffer is full and counting the dropped lines in `dropped`.
+pub fn spawn_buffer(
+    capacity: usize,
+    policy: DropPolicy,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+    dropped: Arc<AtomicUsize>,
+) -> InputTask {
+    let capacity = capacity.max(1);
+    InputTask {
+        handle: tokio::spawn(async move {
+            let mut queue = VecDeque::with_capacity(capacity);
+            let mut closed = false;
+            while !closed || !queue.is_empty() {
+                let accepting =
+                    !closed && (queue.len() < capacity || !matches!(policy, DropPolicy::Block));
+                tokio::select! {
+                    maybe_line = rx.recv(), if accepting => match maybe_line {
+                        Some(line) => {
+                            if queue.len() >= capacity {
+                                dropped.fetch_add(1, Ordering::Relaxed);
+                                if let DropPolicy::DropNewest = policy {
+                                    continue;
+                                }
+                                queue.pop_front();
+                            }
+                            queue.push_back(line);
+                        }
+                        None => closed = true,
+                    },
+                    permit = tx.reserve(), if !queue.is_empty() => {
+                        permit?.send(queue.pop_front().expect("queue is not empty"));
+                    }
+                }
+            }
+            Ok(())
+        }),
+        child: None,
+    }
+}
+
+/// Spawn a task that passes lines from `rx` to `tx` at `rate` lines per second at most,
+/// slowing down the source as a producer of that speed would.
+pub fn spawn_throttle(
+    rate: f64,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+) -> InputTask {
+    InputTask {
+        handle: tokio::spawn(async move {
+            let start = Instant::now();
+            let mut sent = 0u32;
+            while let Some(line) = rx.recv().await {
+                // Sleeping only when ahead of schedule keeps high rates accurate
+                // despite the resolution of the timer.
+                let due = start + Duration::from_secs_f64(f64::from(sent) / rate);
+                tokio::time::sleep_until(due).await;
+                tx.send(line).await?;
+                sent = sent.saturating_add(1);
+            }
+            Ok(())
+        }),
+        child: None,
+    }
+}
+
+/// Spawn a task that fakes the values of the masked fields of the lines from `rx`,
+/// before sending them to `tx` (see `--demo-mask`).
+pub fn spawn_mask(
+    mask: Mask,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+) -> InputTask {
+    InputTask {
+        handle: tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                tx.send(mask.apply(line)).await?;
+            }
+            Ok(())
+        }),
+        child: None,
+    }
+}
+
+/// Maximum number of lines in a record, to bound memory when the start pattern never matches.
+const MAX_RECORD_LINES: usize = 10000;
+
+/// Spawn a task that groups lines from `rx` into multi-line records,
+/// where a new record starts at each line matching `start`.
+/// A record is sent joined with newlines once the next one starts,
+/// or when no line arrives within `flush_timeout`.
+pub fn spawn_record_grouper(
+    start: Regex,
+    flush_timeout: Duration,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+) -> InputTask {
+    InputTask {
+        handle: tokio::spawn(async move {
+            let mut record: Vec<String> = Vec::new();
+            loop {
+                match timeout(flush_timeout, rx.recv()).await {
+                    Ok(Some(line)) => {
+                        if !record.is_empty()
+                            && (start.is_match(&line) || record.len() >= MAX_RECORD_LINES)
+                        {
+                            tx.send(record.join("\n")).await?;
+                            record.clear();
+                        }
+                        record.push(line);
+                    }
+                    Ok(None) => break,
+                    Err(_) => {
+                        if !record.is_empty() {
+                            tx.send(record.join("\n")).await?;
+                            record.clear();
+                        }
+                    }
+                }
+            }
+            if !record.is_empty() {
+                tx.send(record.join("\n")).await?;
+            }
+            Ok(())
+        }),
+        child: None,
+    }
+}
+
+/// How lines are grouped into records, to which the query is applied as a whole.
+#[derive(Clone)]
+pub enum Records {
+    /// A new record starts at each line matching the pattern, see `--multiline-start`.
+    Start(Regex),
+    /// A record is an XML element with the tag, from its start tag to its end tag,
+    /// see `--xml-record`.
+    Xml(String),
+}
+
+/// Spawn a task that groups lines from `rx` into XML elements with the tag,
+/// passing the lines outside of them through.
+/// An element is sent as a single line once it is closed (see `xml::join`),
+/// or when no line arrives within `flush_timeout`.
+pub fn spawn_xml_grouper(
+    tag: String,
+    flush_timeout: Duration,
+    mut rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<String>,
+) -> InputTask {
+    InputTask {
+        handle: tokio::spawn(async move {
+            let mut record: Vec<String> = Vec::new();
+            // Elements with the tag left open
+            let mut depth = 0;
+            loop {
+                match timeout(flush_timeout, rx.recv()).await {
+                    Ok(Some(line)) => {
+                        let (opened, closed) = xml::balance(&line, &tag);
+                        if record.is_empty() && opened == 0 {
+                            tx.send(line).await?;
+                            continue;
+                        }
+                        depth = (depth + opened).saturating_sub(closed);
+                        record.push(line);
+                        if depth == 0 || record.len() >= MAX_RECORD_LINES {
+                            tx.send(xml::join(&record)).await?;
+                            record.clear();
+                            depth = 0;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(_) => {
+                        if !record.is_empty() {
+                            tx.send(xml::join(&record)).await?;
+                            record.clear();
+                            depth = 0;
+                        }
+                    }
+                }
+            }
+            if !record.is_empty() {
+                tx.send(xml::join(&record)).await?;
+            }
+            Ok(())
+        }),
+        child: None,
+    }
+}
+
+/// Quote a string to be passed as a single word to `sh`.
+#[cfg(feature = "tui")]
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Run the `--preview` command for the selected line, where `{}` is replaced as in `run_on_select`,
+/// returning the lines of its output (stdout, then stderr).
+/// The command is killed if the returned future is dropped, e.g. when the task running it is aborted.
+#[cfg(feature = "tui")]
+pub async fn run_preview(template: &str, line: &str) -> anyhow::Result<Vec<String>> {
+    let cmdstr = template.replace("{}", &shell_quote(line));
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(cmdstr)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    let text = [output.stdout, output.stderr].concat();
+    Ok(String::from_utf8_lossy(&text)
+        .lines()
+        .map(|line| strip_ansi_escapes::strip_str(line.replace('\t', "    ")))
+        .collect())
+}
+
+/// Run the user-defined command for the selected line,
+/// where `{}` in the command is replaced by the shell-quoted line.
+///
+/// The terminal is handed over to the command while it runs,
+/// so that interactive programs such as editors can be used.
+#[cfg(feature = "tui")]
+pub async fn run_on_select(template: &str, line: &str) -> anyhow::Result<()> {
+    let cmdstr = template.replace("{}", &shell_quote(line));
+    // Read from the terminal rather than the stream piped into sig.
+    let stdin = std::fs::File::open("/dev/tty")
+        .map(Stdio::from)
+        .unwrap_or_else(|_| Stdio::null());
+
+    crate::leave_terminal()?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(cmdstr)
+        .stdin(stdin)
+        .status()
+        .await;
+    crate::enter_terminal()?;
+
+    status?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stream the lines through the filter command until it ends,
+    /// returning its output and how its task ended.
+    async fn filter(
+        cmdstr: &str,
+        mode: FilterMode,
+        lines: Vec<String>,
+    ) -> (Vec<String>, anyhow::Result<()>) {
+        let (input_tx, input_rx) = mpsc::channel(1);
+        let (output_tx, mut output_rx) = mpsc::channel(1);
+        let task = spawn_filter(cmdstr, mode, input_rx, output_tx).unwrap();
+        tokio::spawn(async move {
+            for line in lines {
+                if input_tx.send(line).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let mut output = Vec::new();
+        while let Some(line) = output_rx.recv().await {
+            output.push(line);
+        }
+        (output, task.handle.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn replace_passes_on_the_output_of_the_command() {
+        let lines = vec![String::from("one"), String::from("two\ttabs")];
+        let (output, result) = filter("tr a-z A-Z", FilterMode::Replace, lines).await;
+        result.unwrap();
+        assert_eq!(output, ["ONE", "TWO TABS"]);
+    }
+
+    #[tokio::test]
+    async fn annotate_pairs_each_output_with_its_line() {
+        let lines = vec![String::from("abc"), String::from("hello")];
+        let (output, result) = filter(
+            "awk '{ print length($0); fflush() }'",
+            FilterMode::Annotate,
+            lines,
+        )
+        .await;
+        result.unwrap();
+        assert_eq!(output, ["abc » 3", "hello » 5"]);
+    }
+
+    #[tokio::test]
+    async fn a_command_exiting_early_ends_the_stream() {
+        // More than a pipe holds, so that writing to `head` fails once it has exited.
+        let lines = (0..100_000).map(|i| format!("line {i}")).collect();
+        let (output, result) = filter("head -1", FilterMode::Replace, lines).await;
+        result.unwrap();
+        assert_eq!(output, ["line 0"]);
+    }
+    const FLUSH_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn start() -> Regex {
+        Regex::new("^[A-Z]+ ").unwrap()
+    }
+
+    /// Stream the lines through a grouper, returning the records it sends until the lines end.
+    async fn group(
+        spawn: impl FnOnce(mpsc::Receiver<String>, mpsc::Sender<String>) -> InputTask,
+        lines: Vec<String>,
+    ) -> Vec<String> {
+        let (input_tx, input_rx) = mpsc::channel(16);
+        let (output_tx, mut output_rx) = mpsc::channel(16);
+        spawn(input_rx, output_tx);
+        tokio::spawn(async move {
+            for line in lines {
+                input_tx.send(line).await.unwrap();
+            }
+        });
+        let mut records = Vec::new();
+        while let Some(record) = output_rx.recv().await {
+            records.push(record);
+        }
+        records
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn records_start_at_the_start_pattern() {
+        let records = group(
+            |rx, tx| spawn_record_grouper(start(), FLUSH_TIMEOUT, rx, tx),
+            lines(&["ERROR boom", "  at main", "  at run", "INFO ok"]),
+        )
+        .await;
+        assert_eq!(records, ["ERROR boom\n  at main\n  at run", "INFO ok"]);
+
+        let records = group(
+            |rx, tx| spawn_xml_grouper(String::from("event"), FLUSH_TIMEOUT, rx, tx),
+            lines(&[
+                "before",
+                "<event id=\"1\">",
+                "  <msg>hi</msg>",
+                "</event>",
+                "after",
+            ]),
+        )
+        .await;
+        assert_eq!(
+            records,
+            ["before", "<event id=\"1\"><msg>hi</msg></event>", "after"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn records_are_flushed_when_no_line_arrives_in_time() {
+        // The input stays open, so only the timeout sends the record.
+        async fn flushed(
+            spawn: impl FnOnce(mpsc::Receiver<String>, mpsc::Sender<String>) -> InputTask,
+            lines: &[&str],
+        ) -> (String, Duration) {
+            let (input_tx, input_rx) = mpsc::channel(16);
+            let (output_tx, mut output_rx) = mpsc::channel(16);
+            spawn(input_rx, output_tx);
+            let began = Instant::now();
+            for line in lines {
+                input_tx.send(line.to_string()).await.unwrap();
+            }
+            (output_rx.recv().await.unwrap(), began.elapsed())
+        }
+
+        assert_eq!(
+            flushed(
+                |rx, tx| spawn_record_grouper(start(), FLUSH_TIMEOUT, rx, tx),
+                &["ERROR boom", "  at main"],
+            )
+            .await,
+            (String::from("ERROR boom\n  at main"), FLUSH_TIMEOUT)
+        );
+        assert_eq!(
+            flushed(
+                |rx, tx| spawn_xml_grouper(String::from("event"), FLUSH_TIMEOUT, rx, tx),
+                &["<event>", "  <msg>hi</msg>"],
+            )
+            .await,
+            (String::from("<event><msg>hi</msg>"), FLUSH_TIMEOUT)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn records_are_cut_at_max_record_lines() {
+        let mut stack = lines(&["ERROR boom"]);
+        stack.extend(lines(&["  at main"; MAX_RECORD_LINES + 1]));
+        let records = group(
+            |rx, tx| spawn_record_grouper(start(), FLUSH_TIMEOUT, rx, tx),
+            stack,
+        )
+        .await;
+        let line_counts = records
+            .iter()
+            .map(|record| record.lines().count())
+            .collect::<Vec<_>>();
+        assert_eq!(line_counts, [MAX_RECORD_LINES, 2]);
+
+        let mut element = lines(&["<event>"]);
+        element.extend(lines(&["<msg/>"; MAX_RECORD_LINES]));
+        let records = group(
+            |rx, tx| spawn_xml_grouper(String::from("event"), FLUSH_TIMEOUT, rx, tx),
+            element,
+        )
+        .await;
+        // The line after the cut is outside of any element again, so it passes through.
+        assert_eq!(
+            records,
+            [
+                format!("<event>{}", "<msg/>".repeat(MAX_RECORD_LINES - 1)),
+                String::from("<msg/>"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number and sum of the values observed over a period.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Aggregate {
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Aggregate {
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Values observed over a sliding window, compared with the window just before it.
+///
+/// Values are aggregated into one-second buckets, and only the buckets
+/// of the last two windows are kept, so that memory does not grow with the stream.
+/// Windows consist of complete seconds, so that the value does not drop
+/// each time a new second starts.
+pub struct WindowedStats {
+    window: u64,
+    origin: Instant,
+    // (seconds since origin, aggregate of that second), in ascending order
+    buckets: VecDeque<(u64, Aggregate)>,
+}
+
+impl WindowedStats {
+    pub fn new(window: Duration, origin: Instant) -> Self {
+        Self {
+            window: window.as_secs().max(1),
+            origin,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window)
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.origin).as_secs()
+    }
+
+    pub fn record(&mut self, now: Instant, value: f64) {
+        let second = self.second(now);
+        match self.buckets.back_mut() {
+            Some((last, aggregate)) if *last == second => {
+                aggregate.count += 1;
+                aggregate.sum += value;
+            }
+            _ => self.buckets.push_back((
+                second,
+                Aggregate {
+                    count: 1,
+                    sum: value,
+                },
+            )),
+        }
+        self.prune(second);
+    }
+
+    fn prune(&mut self, second: u64) {
+        while let Some((first, _)) = self.buckets.front() {
+            if first + 2 * self.window >= second {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Aggregates of the current window (the last complete seconds) and of the previous one.
+    pub fn windows(&mut self, now: Instant) -> (Aggregate, Aggregate) {
+        let second = self.second(now);
+        self.prune(second);
+        let mut current = Aggregate::default();
+        let mut previous = Aggregate::default();
+        for (bucket, aggregate) in &self.buckets {
+            let target = if *bucket >= second {
+                continue;
+            } else if bucket + self.window >= second {
+                &mut current
+            } else {
+                &mut previous
+            };
+            target.count += aggregate.count;
+            target.sum += aggregate.sum;
+        }
+        (current, previous)
+    }
+
+    /// Whether the previous window has been observed entirely.
+    pub fn has_previous(&self, now: Instant) -> bool {
+        self.second(now) >= 2 * self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_split_current_and_previous() {
+        let origin = Instant::now();
+        let at = |secs: u64| origin + Duration::from_secs(secs);
+        let mut stats = WindowedStats::new(Duration::from_secs(10), origin);
+        stats.record(at(1), 1.0);
+        stats.record(at(12), 2.0);
+        stats.record(at(15), 4.0);
+
+        let (current, previous) = stats.windows(at(19));
+        assert_eq!(current, Aggregate { count: 2, sum: 6.0 });
+        assert_eq!(previous, Aggregate { count: 1, sum: 1.0 });
+        assert!(!stats.has_previous(at(19)));
+
+        // The first bucket has slid out of both windows.
+        let (current, previous) = stats.windows(at(25));
+        assert_eq!(current, Aggregate { count: 1, sum: 4.0 });
+        assert_eq!(previous, Aggregate { count: 1, sum: 2.0 });
+        assert!(stats.has_previous(at(25)));
+    }
+}
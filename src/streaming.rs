@@ -0,0 +1,1519 @@
+use std::{
+    collections::HashSet,
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use tokio::{
+    sync::{watch, RwLock},
+    task::JoinHandle,
+    time::{self, Duration, Instant},
+};
+
+use promkit_core::{
+    crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
+        style::{Color, ContentStyle},
+    },
+    grapheme::StyledGraphemes,
+    pane::Pane,
+    PaneFactory,
+};
+use promkit_widgets::{
+    text,
+    text_editor::{self, TextEditor},
+};
+use termcfg::event::{event_def::EventDef, format::event_to_shortcut};
+
+use crate::{
+    alert::{Alerts, SharedAlerts},
+    archive::Archive,
+    audit,
+    backend::Backend,
+    compare::Comparison,
+    config::{matches_keybind, StreamingKeybinds},
+    diff::{DiffLine, DiffTracker},
+    folded::FoldedStacks,
+    gutter::LineFlags,
+    highlight::{highlight, highlight_over, mark, SharedHighlights},
+    layout::{EditorPosition, Overflow},
+    pipeline::{Pipeline, SharedPipeline},
+    rules::{RuleAction, RuleEditor},
+    sequence::SequenceCheck,
+    session::{Session, SessionOptions},
+    sink::SinkSender,
+    slots::QuerySlots,
+    source::Source,
+    spawn,
+    terminal::Terminal,
+    timestamp,
+    words::WordCounts,
+    Signal,
+};
+
+/// Interval to refresh the pipeline inspector and the top-N table of `--preset`.
+const PANE_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+enum InputAction {
+    Continue,
+    TogglePause,
+    TogglePassthrough,
+    ToggleSplit,
+    ToggleWrap,
+    ScrollLeft,
+    ScrollRight,
+    DismissIncident,
+    PinComparison,
+    Lock,
+    Abort,
+    Exec,
+    GotoArchived,
+    GotoStreaming,
+    Exit { print: bool },
+}
+
+#[derive(Clone)]
+struct HintKeybindLabels {
+    archived: String,
+    pause_resume: String,
+    retry: Option<String>,
+    inspector: String,
+    rules: String,
+    dismiss_incident: String,
+    unlock: String,
+    exit: String,
+    // Whether the query and the pipeline are locked, see `sig attach --read-only`
+    read_only: bool,
+    // Whether the status bar and the alert banners are hidden, see `--quiet`
+    quiet: bool,
+}
+
+pub(crate) fn format_keybinds(events: &HashSet<Event>) -> String {
+    let mut labels = events
+        .iter()
+        .filter_map(|event| EventDef::try_from(event).ok().map(event_to_shortcut))
+        .collect::<Vec<String>>();
+    labels.sort();
+    labels.dedup();
+    labels.join("/")
+}
+
+fn create_hint_keybind_labels(
+    keybinds: &StreamingKeybinds,
+    retryable: bool,
+    read_only: bool,
+    quiet: bool,
+) -> HintKeybindLabels {
+    HintKeybindLabels {
+        archived: format_keybinds(&keybinds.goto_archived),
+        pause_resume: format_keybinds(&keybinds.toggle_pause),
+        retry: retryable.then(|| format_keybinds(&keybinds.retry)),
+        inspector: format_keybinds(&keybinds.toggle_inspector),
+        rules: format_keybinds(&keybinds.toggle_rules),
+        dismiss_incident: format_keybinds(&keybinds.dismiss_incident),
+        unlock: format_keybinds(&keybinds.unlock),
+        exit: format_keybinds(&keybinds.exit),
+        read_only,
+        quiet,
+    }
+}
+
+// Evaluate a key event and return the corresponding InputAction.
+// The query cannot be edited and no command is run if `read_only`.
+fn evaluate_event(
+    event: &Event,
+    state: &mut text_editor::State,
+    retryable: bool,
+    read_only: bool,
+    pick: bool,
+    keybinds: &StreamingKeybinds,
+) -> anyhow::Result<InputAction> {
+    if matches_keybind(event, &keybinds.goto_archived) {
+        return Ok(InputAction::GotoArchived);
+    }
+
+    if retryable && matches_keybind(event, &keybinds.retry) {
+        return Ok(InputAction::GotoStreaming);
+    }
+
+    if matches_keybind(event, &keybinds.toggle_pause) {
+        return Ok(InputAction::TogglePause);
+    }
+
+    if matches_keybind(event, &keybinds.toggle_passthrough) {
+        return Ok(InputAction::TogglePassthrough);
+    }
+
+    if !read_only && matches_keybind(event, &keybinds.exec) {
+        return Ok(InputAction::Exec);
+    }
+
+    if !read_only && matches_keybind(event, &keybinds.dismiss_incident) {
+        return Ok(InputAction::DismissIncident);
+    }
+
+    if !read_only && matches_keybind(event, &keybinds.compare) {
+        return Ok(InputAction::PinComparison);
+    }
+
+    if matches_keybind(event, &keybinds.lock) {
+        return Ok(InputAction::Lock);
+    }
+
+    if matches_keybind(event, &keybinds.toggle_split) {
+        return Ok(InputAction::ToggleSplit);
+    }
+
+    if matches_keybind(event, &keybinds.toggle_wrap) {
+        return Ok(InputAction::ToggleWrap);
+    }
+
+    if matches_keybind(event, &keybinds.scroll_left) {
+        return Ok(InputAction::ScrollLeft);
+    }
+
+    if matches_keybind(event, &keybinds.scroll_right) {
+        return Ok(InputAction::ScrollRight);
+    }
+
+    if matches_keybind(event, &keybinds.exit) {
+        return Ok(InputAction::Exit { print: false });
+    }
+
+    if matches_keybind(event, &keybinds.exit_and_print) {
+        return Ok(InputAction::Exit { print: true });
+    }
+
+    if pick && matches_keybind(event, &keybinds.abort) {
+        return Ok(InputAction::Abort);
+    }
+
+    if read_only {
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.backward) {
+        state.texteditor.backward();
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.forward) {
+        state.texteditor.forward();
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.move_to_head) {
+        state.texteditor.move_to_head();
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.move_to_tail) {
+        state.texteditor.move_to_tail();
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.erase) {
+        state.texteditor.erase();
+        return Ok(InputAction::Continue);
+    }
+
+    if matches_keybind(event, &keybinds.editor.erase_all) {
+        state.texteditor.erase_all();
+        return Ok(InputAction::Continue);
+    }
+
+    match event {
+        // Input char.
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(ch),
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        })
+        | Event::Key(KeyEvent {
+            code: KeyCode::Char(ch),
+            modifiers: KeyModifiers::SHIFT,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => match state.config.edit_mode {
+            text_editor::Mode::Insert => state.texteditor.insert(*ch),
+            text_editor::Mode::Overwrite => state.texteditor.overwrite(*ch),
+        },
+
+        _ => (),
+    }
+    Ok(InputAction::Continue)
+}
+
+// Evaluate a key event for the query slots: the key of `save_query_slot` followed by 1-9
+// saves the query to that slot, and Alt+1-9 recalls a saved one,
+// unless the key toggles a pipeline stage. Returns whether the event was consumed.
+fn evaluate_slot_event(
+    event: &Event,
+    state: &mut text_editor::State,
+    saving: &mut bool,
+    slots: &mut QuerySlots,
+    pipeline: &Pipeline,
+    keybinds: &StreamingKeybinds,
+) -> bool {
+    let digit = match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(ch @ '1'..='9'),
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) => Some((*ch, *modifiers)),
+        _ => None,
+    };
+
+    if *saving {
+        *saving = false;
+        if let Some((slot, _)) = digit {
+            let query = state.texteditor.text_without_cursor().to_string();
+            audit::record("streaming", "save_query_slot", &format!("{slot} {query}"));
+            let _ = slots.save(slot, &query);
+        }
+        return true;
+    }
+
+    if matches_keybind(event, &keybinds.save_query_slot) {
+        *saving = true;
+        return true;
+    }
+
+    match digit {
+        // Leave the keys bound to the pipeline stages to them.
+        Some((slot, KeyModifiers::ALT)) if !pipeline.binds(event) => match slots.get(slot) {
+            Some(query) => {
+                state.texteditor = TextEditor::new(query);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// Evaluate a key event for the pipeline inspector, where `cursor` is the selected stage
+// while the inspector is shown. Returns whether the event was consumed.
+fn evaluate_inspector_event(
+    event: &Event,
+    pipeline: &mut Pipeline,
+    cursor: &mut Option<usize>,
+    keybinds: &StreamingKeybinds,
+) -> bool {
+    if matches_keybind(event, &keybinds.toggle_inspector) {
+        if !pipeline.stages.is_empty() {
+            *cursor = match cursor {
+                Some(_) => None,
+                None => Some(0),
+            };
+        }
+        return true;
+    }
+
+    let Some(cursor) = cursor else {
+        return false;
+    };
+
+    if matches_keybind(event, &keybinds.inspector.up) {
+        *cursor = cursor.saturating_sub(1);
+        return true;
+    }
+
+    if matches_keybind(event, &keybinds.inspector.down) {
+        *cursor = (*cursor + 1).min(pipeline.stages.len() - 1);
+        return true;
+    }
+
+    if matches_keybind(event, &keybinds.inspector.toggle) {
+        let stage = &mut pipeline.stages[*cursor];
+        stage.enabled = !stage.enabled;
+        return true;
+    }
+
+    false
+}
+
+// Evaluate a key event for the rule editor, which takes all the keys while it is shown.
+// Returns whether the event was consumed.
+fn evaluate_rules_event(
+    event: &Event,
+    rule_editor: &mut Option<RuleEditor>,
+    pipeline: &mut Pipeline,
+    alerts: &mut Alerts,
+    highlights: &SharedHighlights,
+    config_file: Option<&Path>,
+    keybinds: &StreamingKeybinds,
+) -> bool {
+    let Some(editor) = rule_editor else {
+        if matches_keybind(event, &keybinds.toggle_rules) {
+            let highlights = highlights.read().unwrap_or_else(|e| e.into_inner());
+            *rule_editor = Some(RuleEditor::open(pipeline, alerts, &highlights));
+            return true;
+        }
+        return false;
+    };
+
+    match editor.evaluate_event(event, &keybinds.rules, &keybinds.editor) {
+        RuleAction::Continue => {}
+        RuleAction::Close => *rule_editor = None,
+        RuleAction::Apply { save } => {
+            let mut highlights = highlights.write().unwrap_or_else(|e| e.into_inner());
+            editor.apply(pipeline, alerts, &mut highlights, config_file, save);
+        }
+    }
+    true
+}
+
+/// Summarize the pipeline stages, naming the disabled ones,
+/// e.g. " | Stages(2/3, off: mask)".
+fn stages_hint(pipeline: &Pipeline) -> String {
+    if pipeline.stages.is_empty() {
+        return String::new();
+    }
+    let disabled = pipeline
+        .stages
+        .iter()
+        .filter(|stage| !stage.enabled)
+        .map(|stage| stage.name.as_str())
+        .collect::<Vec<&str>>();
+    let enabled = pipeline.stages.len() - disabled.len();
+    if disabled.is_empty() {
+        format!(" | Stages({enabled}/{})", pipeline.stages.len())
+    } else {
+        format!(
+            " | Stages({enabled}/{}, off: {})",
+            pipeline.stages.len(),
+            disabled.join(",")
+        )
+    }
+}
+
+fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{:.1}µs", latency.as_secs_f64() * 1_000_000.0),
+        None => String::from("-"),
+    }
+}
+
+/// List the stages with their stats, marking the one under the cursor.
+fn create_inspector_pane(pipeline: &Pipeline, cursor: usize, size: (u16, u16)) -> Pane {
+    let name_width = pipeline
+        .stages
+        .iter()
+        .map(|stage| stage.name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("stage".len());
+    let header = format!(
+        "      {:name_width$}  {:7}  {:>8}  {:>8}  {:>10}",
+        "stage", "kind", "lines", "dropped", "latency"
+    );
+    let mut rows = vec![StyledGraphemes::from(header).apply_style(ContentStyle {
+        foreground_color: Some(Color::DarkGrey),
+        ..Default::default()
+    })];
+    rows.extend(pipeline.stages.iter().enumerate().map(|(i, stage)| {
+        StyledGraphemes::from(format!(
+            "{} [{}] {:name_width$}  {:7}  {:>8}  {:>8}  {:>10}",
+            if i == cursor { "❯" } else { " " },
+            if stage.enabled { "x" } else { " " },
+            stage.name,
+            stage.kind(),
+            stage.stats.lines,
+            stage.stats.dropped,
+            format_latency(stage.stats.latency()),
+        ))
+    }));
+
+    text::State {
+        text: text::Text::from_styled_graphemes(rows),
+        config: text::Config {
+            style: None,
+            lines: Some(pipeline.stages.len() + 1),
+        },
+    }
+    .create_pane(size.0, size.1)
+}
+
+/// What the top-N table of `--preset` ranks.
+pub enum Ranking {
+    /// Folded stacks by their samples, among the stacks matching the query (`--preset folded`).
+    Stacks(FoldedStacks),
+    /// Words by the matched lines they occur in, other than those of the query (`--preset words`).
+    Words(WordCounts),
+}
+
+impl Ranking {
+    fn add(&mut self, line: &str, matched: bool) {
+        match self {
+            Ranking::Stacks(stacks) => stacks.add(line),
+            Ranking::Words(words) if matched => words.add(line),
+            Ranking::Words(_) => (),
+        }
+    }
+}
+
+/// The live top-N table of `--preset`.
+struct TopTable {
+    top: usize,
+    ranking: Ranking,
+    // Rendered on refresh rather than per line, since ranking all entries is costly
+    table: Vec<StyledGraphemes>,
+}
+
+impl TopTable {
+    fn new(ranking: Ranking, top: usize, width: u16) -> Self {
+        let mut view = Self {
+            top,
+            ranking,
+            table: Vec::new(),
+        };
+        view.refresh("", ContentStyle::default(), false, width);
+        view
+    }
+
+    /// Rank the entries for the query, padding the table to keep its height stable.
+    fn refresh(
+        &mut self,
+        query: &str,
+        highlight_style: ContentStyle,
+        case_insensitive: bool,
+        width: u16,
+    ) {
+        let matches =
+            |entry: &str| highlight(query, entry, highlight_style, case_insensitive).is_some();
+        let (top, total, header) = match &self.ranking {
+            Ranking::Stacks(stacks) => {
+                let top = stacks.top(self.top, matches);
+                let header = format!(
+                    "{:>10} {:>6}  stack (top {} of {} stacks, {} samples)",
+                    "samples",
+                    "%",
+                    top.len(),
+                    stacks.len(),
+                    stacks.total()
+                );
+                (top, stacks.total(), header)
+            }
+            Ranking::Words(words) => {
+                // The words of the query are in every matched line,
+                // whatever their case as the words are counted in lowercase.
+                let top = words.top(self.top, |word| {
+                    query.is_empty() || highlight(query, word, highlight_style, true).is_none()
+                });
+                let header = format!(
+                    "{:>10} {:>6}  word (top {} of {} in the last {} matched lines)",
+                    "lines",
+                    "%",
+                    top.len(),
+                    words.len(),
+                    words.lines()
+                );
+                (top, words.lines() as u64, header)
+            }
+        };
+        // Columns left for the entry after the count and percentage
+        let available = (width as usize).saturating_sub(19).max(1);
+
+        let mut table = vec![StyledGraphemes::from(header).apply_style(ContentStyle {
+            foreground_color: Some(Color::DarkGrey),
+            ..Default::default()
+        })];
+        table.extend(top.iter().map(|(entry, count)| {
+            let percentage = *count as f64 * 100.0 / total.max(1) as f64;
+            // Keep the end, which is the leaf frames and the most telling part of a stack.
+            let chars = entry.chars().collect::<Vec<char>>();
+            let entry = if chars.len() > available {
+                let tail = chars[chars.len() - (available - 1)..]
+                    .iter()
+                    .collect::<String>();
+                format!("…{tail}")
+            } else {
+                entry.to_string()
+            };
+            let styled = highlight(query, &entry, highlight_style, case_insensitive)
+                .unwrap_or_else(|| StyledGraphemes::from(&entry));
+            [
+                StyledGraphemes::from(format!("{count:>10} {percentage:>5.1}%  ")),
+                styled,
+            ]
+            .into_iter()
+            .collect()
+        }));
+        table.resize(self.top + 1, StyledGraphemes::from(" "));
+        self.table = table;
+    }
+}
+
+/// What the status bar tells besides the panes' own state.
+#[derive(Clone, Copy)]
+struct Status {
+    paused: bool,
+    passthrough: bool,
+    locked: bool,
+    dropped: usize,
+}
+
+/// How much `Panes::redraw_panes` draws.
+enum Redraw {
+    /// The panes, and the stream too if the layout changed.
+    Panes,
+    /// The panes and the stream.
+    All,
+    /// The panes and the stream, only if the layout changed, e.g. before drawing a line.
+    Resized,
+}
+
+/// What the panes around the stream show but the query and the top-N table,
+/// shared by the event loop and the task drawing the lines.
+#[derive(Clone)]
+struct Panes {
+    alerts: SharedAlerts,
+    sequence: Arc<RwLock<Option<SequenceCheck>>>,
+    comparison: Arc<RwLock<Option<Comparison>>>,
+    pipeline: SharedPipeline,
+    // The selected stage while the pipeline inspector is shown
+    inspector: Arc<RwLock<Option<usize>>>,
+    rule_editor: Arc<RwLock<Option<RuleEditor>>>,
+    keybind_labels: HintKeybindLabels,
+}
+
+impl Panes {
+    /// Create the panes, taking the locks after the query and the top-N table held by the caller.
+    async fn create_panes(
+        &self,
+        text_editor: &text_editor::State,
+        top_table: Option<&TopTable>,
+        status: Status,
+        size: (u16, u16),
+    ) -> Vec<Pane> {
+        create_panes(
+            text_editor,
+            top_table.map(|top_table| top_table.table.as_slice()),
+            status,
+            size,
+            PaneState {
+                alerts: &*self.alerts.read().await,
+                sequence: self.sequence.read().await.as_ref(),
+                comparison: self.comparison.read().await.as_ref(),
+                pipeline: &*self.pipeline.read().await,
+                inspector: *self.inspector.read().await,
+                rule_editor: self.rule_editor.read().await.as_ref(),
+                keybind_labels: &self.keybind_labels,
+            },
+        )
+    }
+
+    async fn redraw_panes(
+        &self,
+        term: &RwLock<Terminal>,
+        text_editor: &text_editor::State,
+        top_table: Option<&TopTable>,
+        status: Status,
+        redraw: Redraw,
+    ) -> anyhow::Result<()> {
+        let size = term.read().await.size()?;
+        let panes = self
+            .create_panes(text_editor, top_table, status, size)
+            .await;
+        let mut term = term.write().await;
+        // Make room for the panes that were shown or hidden, e.g. the inspector or alerts.
+        let resized = term.sync_layout(size, Terminal::pane_rows(size, &panes))?;
+        match redraw {
+            Redraw::Panes if !resized => term.draw_pane(&panes),
+            Redraw::Resized if !resized => Ok(()),
+            _ => term.redraw(&panes),
+        }
+    }
+}
+
+/// The state of the panes read from `Panes` for the time of drawing.
+struct PaneState<'a> {
+    alerts: &'a Alerts,
+    sequence: Option<&'a SequenceCheck>,
+    comparison: Option<&'a Comparison>,
+    pipeline: &'a Pipeline,
+    inspector: Option<usize>,
+    rule_editor: Option<&'a RuleEditor>,
+    keybind_labels: &'a HintKeybindLabels,
+}
+
+fn create_panes(
+    text_editor: &text_editor::State,
+    top_table: Option<&[StyledGraphemes]>,
+    status: Status,
+    size: (u16, u16),
+    state: PaneState,
+) -> Vec<Pane> {
+    let PaneState {
+        alerts,
+        sequence,
+        comparison,
+        pipeline,
+        inspector,
+        rule_editor,
+        keybind_labels,
+    } = state;
+    let Status {
+        paused,
+        passthrough,
+        locked,
+        dropped,
+    } = status;
+    let badge = match (paused, passthrough) {
+        (true, false) => "[PAUSED]",
+        (false, false) => "[RUNNING]",
+        (true, true) => "[PAUSED] [PASSTHROUGH]",
+        (false, true) => "[RUNNING] [PASSTHROUGH]",
+    };
+    let badge = if keybind_labels.read_only {
+        format!("{badge} [READ-ONLY]")
+    } else {
+        badge.to_string()
+    };
+    let badge = if locked {
+        format!("{badge} [LOCKED] Unlock({})", keybind_labels.unlock)
+    } else {
+        badge
+    };
+    let badge = match &alerts.incident {
+        Some(incident) if incident.is_active() => format!(
+            "{badge} [INCIDENT] Dismiss({})",
+            keybind_labels.dismiss_incident
+        ),
+        _ => badge,
+    };
+    let alerts = alerts.firing();
+    let retry_hint = match &keybind_labels.retry {
+        Some(retry) => format!(" | Retry({retry})"),
+        None => String::new(),
+    };
+    let dropped_hint = if dropped > 0 {
+        format!(" | Dropped({dropped})")
+    } else {
+        String::new()
+    };
+    let sequence_hint = match sequence {
+        Some(check) if check.missing > 0 || check.repeated > 0 => {
+            format!(" | {} missing, {} repeated", check.missing, check.repeated)
+        }
+        _ => String::new(),
+    };
+    let comparison_hint = comparison.map(Comparison::hint).unwrap_or_default();
+    let stages = stages_hint(pipeline);
+    let inspector_hint = if pipeline.stages.is_empty() || keybind_labels.read_only {
+        String::new()
+    } else {
+        format!(" | Inspector({})", keybind_labels.inspector)
+    };
+    let rules_hint = if keybind_labels.read_only {
+        String::new()
+    } else {
+        format!(" | Rules({})", keybind_labels.rules)
+    };
+    let hint = text::State {
+        text: text::Text::from(format!(
+            "{badge} Archived({}) | Pause/Resume({}){}{}{}{}{}{}{} | Exit({})",
+            keybind_labels.archived,
+            keybind_labels.pause_resume,
+            retry_hint,
+            dropped_hint,
+            sequence_hint,
+            comparison_hint,
+            stages,
+            inspector_hint,
+            rules_hint,
+            keybind_labels.exit
+        )),
+        config: text::Config {
+            style: Some(ContentStyle {
+                foreground_color: Some(Color::DarkGrey),
+                ..Default::default()
+            }),
+            lines: Some(1),
+        },
+    };
+
+    let mut panes = vec![text_editor.create_pane(size.0, size.1)];
+    if !keybind_labels.quiet {
+        panes.push(hint.create_pane(size.0, size.1));
+    }
+    if !alerts.is_empty() && !keybind_labels.quiet {
+        panes.push(
+            text::State {
+                text: text::Text::from_styled_graphemes(
+                    alerts
+                        .iter()
+                        .map(|alert| StyledGraphemes::from(format!("ALERT {alert}")))
+                        .collect(),
+                ),
+                config: text::Config {
+                    style: Some(ContentStyle {
+                        foreground_color: Some(Color::Red),
+                        ..Default::default()
+                    }),
+                    lines: Some(alerts.len()),
+                },
+            }
+            .create_pane(size.0, size.1),
+        );
+    }
+    if let Some(table) = top_table {
+        panes.push(
+            text::State {
+                text: text::Text::from_styled_graphemes(table.to_vec()),
+                config: text::Config {
+                    style: None,
+                    lines: Some(table.len()),
+                },
+            }
+            .create_pane(size.0, size.1),
+        );
+    }
+    if let Some(cursor) = inspector {
+        panes.push(create_inspector_pane(pipeline, cursor, size));
+    }
+    if let Some(rule_editor) = rule_editor {
+        let rows = rule_editor.rows();
+        panes.push(
+            text::State {
+                config: text::Config {
+                    style: None,
+                    lines: Some(rows.len()),
+                },
+                text: text::Text::from_styled_graphemes(rows),
+            }
+            .create_pane(size.0, size.1),
+        );
+    }
+    panes
+}
+
+/// How `run` reads the source and renders the stream, and the state it keeps across the modes.
+pub struct StreamingOptions<'a> {
+    /// Terminal the stream is drawn on and the keys are read from.
+    pub backend: Arc<dyn Backend>,
+    pub highlight_style: ContentStyle,
+    pub keybinds: StreamingKeybinds,
+    pub render_interval: Option<Duration>,
+    pub case_insensitive: bool,
+    /// The session the lines are read through.
+    pub session: SessionOptions,
+    /// Field whose gaps are marked in the stream, see `--sequence-field`.
+    pub sequence_field: Option<String>,
+    /// Command run on the last line while paused, see `--on-select`.
+    pub on_select: Option<String>,
+    pub alerts: SharedAlerts,
+    pub highlights: SharedHighlights,
+    /// Where the rules edited in the rule editor are saved.
+    pub config_file: Option<&'a Path>,
+    /// The top-N table of `--preset` and its number of rows.
+    pub ranking: Option<(Ranking, usize)>,
+    pub split: bool,
+    pub reverse: bool,
+    pub gutter: bool,
+    pub editor_position: EditorPosition,
+    /// Whether the keys are limited to navigation, see `sig attach --read-only`.
+    pub read_only: bool,
+    pub quiet: bool,
+    pub pick: bool,
+    pub sink: SinkSender,
+    /// Time without a match after which the stream is dimmed, see `--idle-dim-secs`.
+    pub idle_dim: Option<Duration>,
+    pub overflow: &'a mut Overflow,
+    pub passthrough: &'a mut bool,
+    pub slots: &'a mut QuerySlots,
+}
+
+pub async fn run(
+    text_editor: text_editor::State,
+    archive: Archive,
+    source: &dyn Source,
+    options: StreamingOptions<'_>,
+) -> anyhow::Result<(Signal, Archive)> {
+    let StreamingOptions {
+        backend,
+        highlight_style,
+        keybinds,
+        render_interval,
+        case_insensitive,
+        session: session_options,
+        sequence_field,
+        on_select,
+        alerts,
+        highlights,
+        config_file,
+        ranking,
+        split,
+        reverse,
+        gutter,
+        editor_position,
+        read_only,
+        quiet,
+        pick,
+        sink,
+        idle_dim,
+        overflow,
+        passthrough,
+        slots,
+    } = options;
+    let retrieval_timeout = session_options.retrieval_timeout;
+    let pipeline = Arc::clone(&session_options.pipeline);
+    let size = backend.size()?;
+    let retryable = source.restartable() && !read_only;
+    let keybind_labels = create_hint_keybind_labels(&keybinds, retryable, read_only, quiet);
+
+    let top_table = ranking.map(|(ranking, top)| TopTable::new(ranking, top, size.0));
+    let sequence = Arc::new(RwLock::new(sequence_field.map(SequenceCheck::new)));
+    let writable_sequence = Arc::clone(&sequence);
+    // Counts of the lines matching the query pinned with `compare` and the current one
+    let comparison = Arc::new(RwLock::new(None::<Comparison>));
+    let writable_comparison = Arc::clone(&comparison);
+    let shared_panes = Panes {
+        alerts: Arc::clone(&alerts),
+        sequence: Arc::clone(&sequence),
+        comparison: Arc::clone(&comparison),
+        pipeline: Arc::clone(&pipeline),
+        inspector: Arc::new(RwLock::new(None)),
+        rule_editor: Arc::new(RwLock::new(None)),
+        keybind_labels,
+    };
+    let status = Status {
+        paused: false,
+        passthrough: *passthrough,
+        locked: false,
+        dropped: 0,
+    };
+    let panes = shared_panes
+        .create_panes(&text_editor, top_table.as_ref(), status, size)
+        .await;
+    let mut term = Terminal::try_new(Arc::clone(&backend), size, &panes)?;
+    term.set_split(split);
+    term.set_reverse(reverse);
+    term.set_gutter(gutter);
+    term.set_editor_position(editor_position)?;
+    term.set_overflow(*overflow);
+    term.redraw(&panes)?;
+
+    let shared_term = Arc::new(RwLock::new(term));
+    let shared_text_editor = Arc::new(RwLock::new(text_editor));
+    let readonly_term = Arc::clone(&shared_term);
+    let readonly_text_editor = Arc::clone(&shared_text_editor);
+    let (pause_tx, mut pause_rx) = watch::channel(false);
+    // Whether the unmatched lines are rendered too, rather than hidden
+    let (passthrough_tx, passthrough_rx) = watch::channel(*passthrough);
+    // Whether the keys are ignored but `unlock`, which the status bar tells
+    let (locked_tx, locked_rx) = watch::channel(false);
+    // The last rendered line, which is the target of `on_select` while paused
+    let last_line = Arc::new(RwLock::new(None::<String>));
+    let writable_last_line = Arc::clone(&last_line);
+    let panes_for_task = shared_panes.clone();
+    let inspector = Arc::clone(&shared_panes.inspector);
+    let top_table = Arc::new(RwLock::new(top_table));
+    let writable_top_table = Arc::clone(&top_table);
+    let writable_alerts = Arc::clone(&alerts);
+    let rule_editor = Arc::clone(&shared_panes.rule_editor);
+    let readonly_highlights = Arc::clone(&highlights);
+    // When a line last matched, to dim the stream after `idle_dim` without any
+    let last_match = Arc::new(RwLock::new(None::<Instant>));
+    let writable_last_match = Arc::clone(&last_match);
+
+    let mut session = Session::start(source, session_options)?;
+    let dropped = session.dropped_counter();
+
+    let keeping: JoinHandle<anyhow::Result<Archive>> = tokio::spawn(async move {
+        let mut archive = archive;
+        let mut maybe_interval = render_interval.map(|p| time::interval(p));
+        let mut paused = false;
+        let mut diff = DiffTracker::default();
+
+        loop {
+            if paused {
+                if pause_rx.changed().await.is_err() {
+                    break;
+                }
+                paused = *pause_rx.borrow_and_update();
+                continue;
+            }
+
+            if let Some(interval) = &mut maybe_interval {
+                interval.tick().await;
+            }
+
+            tokio::select! {
+                biased;
+                changed = pause_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    paused = *pause_rx.borrow_and_update();
+                }
+                maybe_line = session.recv() => {
+                    match maybe_line {
+                        Some(line) => {
+                            let alert = writable_alerts
+                                .write()
+                                .await
+                                .observe(&line, Instant::now().into_std());
+                            let text_editor = readonly_text_editor.read().await;
+
+                            archive.push(line.clone())?;
+                            sink.route(&line);
+                            let marker = writable_sequence.write().await.as_mut().and_then(|check| {
+                                check.observe(&line).map(|anomaly| anomaly.marker(check.field()))
+                            });
+
+                            let styled = readonly_highlights
+                                .read()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .styled(&line, diff.classify(&line).map(DiffLine::style).unwrap_or_default());
+                            let highlighted = highlight_over(
+                                &text_editor.texteditor.text_without_cursor().to_string(),
+                                &line,
+                                styled.clone(),
+                                highlight_style,
+                                case_insensitive,
+                            );
+                            let matched = highlighted.is_some();
+                            if let Some(comparison) = writable_comparison.write().await.as_mut() {
+                                comparison.observe(
+                                    &line,
+                                    &text_editor.texteditor.text_without_cursor().to_string(),
+                                    matched,
+                                    case_insensitive,
+                                );
+                            }
+                            if let Some(top_table) = writable_top_table.write().await.as_mut() {
+                                top_table.ranking.add(&line, matched);
+                            }
+                            if let Some(marker) = marker {
+                                // Drawn whatever the query, as the gap is in the stream itself.
+                                let marker = StyledGraphemes::from_str(
+                                    marker,
+                                    ContentStyle {
+                                        foreground_color: Some(Color::Red),
+                                        ..Default::default()
+                                    },
+                                );
+                                let mut term = readonly_term.write().await;
+                                term.draw_all(&marker, LineFlags::default())?;
+                                term.draw_stream(&marker, LineFlags::default())?;
+                            }
+                            let flags = LineFlags {
+                                matched,
+                                alert,
+                                ..Default::default()
+                            };
+                            readonly_term
+                                .write()
+                                .await
+                                .draw_all(highlighted.as_ref().unwrap_or(&styled), flags)?;
+
+                            let passthrough = *passthrough_rx.borrow();
+                            let locked = *locked_rx.borrow();
+                            let shown = highlighted.or_else(|| passthrough.then_some(styled));
+                            if let Some(shown) = shown {
+                                if matched {
+                                    sink.send(&line);
+                                    *writable_last_match.write().await = Some(Instant::now());
+                                }
+                                *writable_last_line.write().await = Some(line.clone());
+                                let status = Status {
+                                    paused,
+                                    passthrough,
+                                    locked,
+                                    dropped: session.dropped(),
+                                };
+                                panes_for_task
+                                    .redraw_panes(
+                                        &readonly_term,
+                                        &text_editor,
+                                        writable_top_table.read().await.as_ref(),
+                                        status,
+                                        Redraw::Resized,
+                                    )
+                                    .await?;
+                                let mut term = readonly_term.write().await;
+                                if matched && term.idle().is_some() {
+                                    term.set_idle(None);
+                                    term.redraw_stream()?;
+                                }
+                                term.draw_stream(&shown, flags)?;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+        archive.add_dropped(session.dropped());
+        session.stop().await;
+        Ok(archive)
+    });
+
+    let status = |paused, passthrough, locked| Status {
+        paused,
+        passthrough,
+        locked,
+        dropped: dropped.load(Ordering::Relaxed),
+    };
+    let mut paused = false;
+    let mut locked = false;
+    let mut panes_refreshed = Instant::now();
+    // The last key press, which also keeps the stream from dimming
+    let mut last_key = Instant::now();
+    // Whether a slot 1-9 is awaited after the key of `save_query_slot`
+    let mut saving_slot = false;
+    let mut prev_query = shared_text_editor
+        .read()
+        .await
+        .texteditor
+        .text_without_cursor()
+        .to_string();
+    let signal = loop {
+        // Treat an exhausted input source as archived data.
+        if keeping.is_finished() {
+            break Signal::GotoArchived;
+        }
+
+        if !backend.poll(retrieval_timeout)? {
+            // Keep the stats in the inspector and the top-N table up to date,
+            // including the lines dropped by the pipeline that are never rendered.
+            let cursor = *inspector.read().await;
+            let has_top_table = top_table.read().await.is_some();
+            let has_alerts = !alerts.read().await.is_empty();
+            let has_counters = sequence.read().await.is_some() || comparison.read().await.is_some();
+            if (cursor.is_some() || has_top_table || has_alerts || has_counters)
+                && panes_refreshed.elapsed() >= PANE_REFRESH_INTERVAL
+            {
+                panes_refreshed = Instant::now();
+                let mut text_editor = shared_text_editor.write().await;
+                let mut writable_alerts = alerts.write().await;
+                let fired = writable_alerts.evaluate(Instant::now().into_std());
+                for reason in &fired {
+                    audit::record("streaming", "alert", reason);
+                }
+                if let (false, false, Some(incident)) = (
+                    fired.is_empty(),
+                    read_only,
+                    writable_alerts.incident.as_mut(),
+                ) {
+                    if incident.apply(
+                        &mut text_editor.texteditor,
+                        &mut *pipeline.write().await,
+                        passthrough,
+                    ) {
+                        audit::record("streaming", "incident", "apply");
+                        let _ = passthrough_tx.send(*passthrough);
+                    }
+                }
+                drop(writable_alerts);
+                let size = backend.size()?;
+                let mut top_table_view = top_table.write().await;
+                if let Some(top_table) = top_table_view.as_mut() {
+                    top_table.refresh(
+                        &text_editor.texteditor.text_without_cursor().to_string(),
+                        highlight_style,
+                        case_insensitive,
+                        size.0,
+                    );
+                }
+                // Make room for the alerts that started or stopped firing.
+                shared_panes
+                    .redraw_panes(
+                        &shared_term,
+                        &text_editor,
+                        top_table_view.as_ref(),
+                        status(paused, *passthrough, locked),
+                        Redraw::Panes,
+                    )
+                    .await?;
+            }
+            if let Some(idle_dim) = idle_dim {
+                let last_match = *last_match.read().await;
+                let active = last_match.map_or(last_key, |at| at.max(last_key));
+                let banner = (active.elapsed() >= idle_dim).then(|| match last_match {
+                    Some(at) => format!("last match {} ago", timestamp::rough(at.elapsed())),
+                    None => format!("no match in {}", timestamp::rough(last_key.elapsed())),
+                });
+                let mut term = shared_term.write().await;
+                if term.idle() != banner.as_deref() {
+                    term.set_idle(banner);
+                    term.redraw_stream()?;
+                }
+            }
+            continue;
+        }
+
+        let event = backend.read()?;
+        last_key = Instant::now();
+        {
+            let mut term = shared_term.write().await;
+            if term.idle().is_some() {
+                term.set_idle(None);
+                term.redraw_stream()?;
+            }
+        }
+        let unlocked = locked && matches_keybind(&event, &keybinds.unlock);
+        if locked && !unlocked && matches!(event, Event::Key(_) | Event::Mouse(_) | Event::Paste(_))
+        {
+            continue;
+        }
+        if unlocked {
+            locked = false;
+            audit::record("streaming", "unlock", "");
+            let _ = locked_tx.send(locked);
+        }
+        let mut text_editor = shared_text_editor.write().await;
+        let consumed = unlocked
+            || !read_only && {
+                let mut alerts = alerts.write().await;
+                let mut pipeline = pipeline.write().await;
+                let mut cursor = inspector.write().await;
+                let before = stages_hint(&pipeline);
+                let consumed = evaluate_rules_event(
+                    &event,
+                    &mut *rule_editor.write().await,
+                    &mut pipeline,
+                    &mut alerts,
+                    &highlights,
+                    config_file,
+                    &keybinds,
+                ) || evaluate_slot_event(
+                    &event,
+                    &mut text_editor,
+                    &mut saving_slot,
+                    slots,
+                    &pipeline,
+                    &keybinds,
+                ) || pipeline.toggle_by_event(&event)
+                    || evaluate_inspector_event(&event, &mut pipeline, &mut cursor, &keybinds);
+                let after = stages_hint(&pipeline);
+                if before != after {
+                    audit::record("streaming", "stages", after.trim_start_matches(" | "));
+                }
+                consumed
+            };
+        let action = if consumed {
+            InputAction::Continue
+        } else {
+            evaluate_event(
+                &event,
+                &mut text_editor,
+                retryable,
+                read_only,
+                pick,
+                &keybinds,
+            )?
+        };
+        match action {
+            InputAction::GotoArchived => {
+                audit::record("streaming", "goto_archived", "");
+                break Signal::GotoArchived;
+            }
+            InputAction::GotoStreaming => {
+                audit::record("streaming", "retry", "");
+                break Signal::GotoStreaming;
+            }
+            InputAction::Exit { print } => {
+                break Signal::Exit {
+                    query: text_editor.texteditor.text_without_cursor().to_string(),
+                    print,
+                };
+            }
+            InputAction::Abort => break Signal::Abort,
+            InputAction::TogglePause => {
+                paused = !paused;
+                audit::record("streaming", if paused { "pause" } else { "resume" }, "");
+                let _ = pause_tx.send(paused);
+            }
+            InputAction::TogglePassthrough => {
+                *passthrough = !*passthrough;
+                let _ = passthrough_tx.send(*passthrough);
+            }
+            InputAction::DismissIncident => {
+                if let Some(incident) = alerts.write().await.incident.as_mut() {
+                    if incident.dismiss(
+                        &mut text_editor.texteditor,
+                        &mut *pipeline.write().await,
+                        passthrough,
+                    ) {
+                        audit::record("streaming", "incident", "dismiss");
+                        let _ = passthrough_tx.send(*passthrough);
+                    }
+                }
+            }
+            InputAction::PinComparison => {
+                let mut comparison = comparison.write().await;
+                *comparison = match comparison.take() {
+                    Some(_) => None,
+                    None => {
+                        // The pinned query is A, and the one typed next is B.
+                        let query = text_editor.texteditor.text_without_cursor().to_string();
+                        audit::record("streaming", "compare", &query);
+                        text_editor.texteditor.erase_all();
+                        Some(Comparison::new(query))
+                    }
+                };
+            }
+            InputAction::Lock => {
+                locked = true;
+                audit::record("streaming", "lock", "");
+                let _ = locked_tx.send(locked);
+            }
+            InputAction::ToggleSplit
+            | InputAction::ToggleWrap
+            | InputAction::ScrollLeft
+            | InputAction::ScrollRight => {
+                let mut term = shared_term.write().await;
+                let overflow = term.overflow();
+                match action {
+                    InputAction::ToggleSplit => {
+                        let split = !term.is_split();
+                        term.set_split(split);
+                    }
+                    InputAction::ToggleWrap => term.set_overflow(overflow.toggle()),
+                    InputAction::ScrollLeft => term.set_overflow(overflow.scroll_left()),
+                    _ => term.set_overflow(overflow.scroll_right()),
+                }
+                drop(term);
+                shared_panes
+                    .redraw_panes(
+                        &shared_term,
+                        &text_editor,
+                        top_table.read().await.as_ref(),
+                        status(paused, *passthrough, locked),
+                        Redraw::All,
+                    )
+                    .await?;
+                continue;
+            }
+            InputAction::Exec => {
+                if let (true, Some(line)) = (pick, last_line.read().await.clone()) {
+                    audit::record("streaming", "pick", &line);
+                    break Signal::Pick(vec![line]);
+                }
+                if let (true, Some(template), Some(line)) =
+                    (paused, &on_select, last_line.read().await.clone())
+                {
+                    audit::record("streaming", "exec", &line);
+                    spawn::run_on_select(template, &line).await?;
+                    shared_panes
+                        .redraw_panes(
+                            &shared_term,
+                            &text_editor,
+                            top_table.read().await.as_ref(),
+                            status(paused, *passthrough, locked),
+                            Redraw::All,
+                        )
+                        .await?;
+                    continue;
+                }
+            }
+            InputAction::Continue => {}
+        }
+
+        let query = text_editor.texteditor.text_without_cursor().to_string();
+        if query != prev_query {
+            audit::record("streaming", "query", &query);
+            prev_query = query;
+        }
+
+        let size = backend.size()?;
+        let mut top_table_view = top_table.write().await;
+        if let Some(top_table) = top_table_view.as_mut() {
+            // Rank again for the edited query.
+            top_table.refresh(
+                &text_editor.texteditor.text_without_cursor().to_string(),
+                highlight_style,
+                case_insensitive,
+                size.0,
+            );
+        }
+        // Restore the stream rows when the inspector is shown or hidden.
+        shared_panes
+            .redraw_panes(
+                &shared_term,
+                &text_editor,
+                top_table_view.as_ref(),
+                status(paused, *passthrough, locked),
+                Redraw::Panes,
+            )
+            .await?;
+    };
+
+    // Closing the pause channel stops the keeping task, which stops the session.
+    drop(pause_tx);
+    *overflow = shared_term.read().await.overflow();
+    Ok((signal, keeping.await??))
+}
+
+/// Markers printed around the matches by `print_matches`.
+pub const PLAIN_MARKERS: (&str, &str) = ("[[", "]]");
+
+/// Print the matching lines to `out` as they come, with the matches between `PLAIN_MARKERS`,
+/// for terminals the UI cannot be drawn on, see `--plain`.
+pub async fn print_matches(
+    source: &dyn Source,
+    options: SessionOptions,
+    query: &str,
+    case_insensitive: bool,
+    sink: SinkSender,
+    out: &mut dyn io::Write,
+) -> anyhow::Result<()> {
+    let mut session = Session::start(source, options)?;
+    while let Some(line) = session.recv().await {
+        sink.route(&line);
+        if let Some(marked) = mark(query, &line, case_insensitive, PLAIN_MARKERS) {
+            sink.send(&line);
+            if writeln!(out, "{marked}").and_then(|_| out.flush()).is_err() {
+                // The reader went away, e.g. `sig --plain | head`.
+                break;
+            }
+        }
+    }
+    session.stop().await;
+    Ok(())
+}
+
+/// Read the source into the archive without showing the stream, see `--archived`.
+/// `goto_archived` stops reading early, keeping the lines read so far.
+#[allow(clippy::too_many_arguments)]
+pub async fn collect(
+    backend: &dyn Backend,
+    keybinds: &StreamingKeybinds,
+    archive: Archive,
+    source: &dyn Source,
+    options: SessionOptions,
+    query: &str,
+    case_insensitive: bool,
+    sink: SinkSender,
+) -> anyhow::Result<(Signal, Archive)> {
+    let hint = format!(
+        "Archived({}) | Exit({})",
+        format_keybinds(&keybinds.goto_archived),
+        format_keybinds(&keybinds.exit),
+    );
+    let retrieval_timeout = options.retrieval_timeout;
+    let mut session = Session::start(source, options)?;
+    let (stop_tx, mut stop_rx) = watch::channel(());
+    let collected = Arc::new(AtomicUsize::new(0));
+    let writable_collected = Arc::clone(&collected);
+    let sink_query = query.to_string();
+
+    let collecting: JoinHandle<anyhow::Result<Archive>> = tokio::spawn(async move {
+        let mut archive = archive;
+        loop {
+            tokio::select! {
+                biased;
+                _ = stop_rx.changed() => break,
+                maybe_line = session.recv() => match maybe_line {
+                    Some(line) => {
+                        sink.route(&line);
+                        if highlight(&sink_query, &line, ContentStyle::default(), case_insensitive).is_some() {
+                            sink.send(&line);
+                        }
+                        archive.push(line)?;
+                        writable_collected.fetch_add(1, Ordering::Relaxed);
+                    }
+                    None => break,
+                },
+            }
+        }
+        archive.add_dropped(session.dropped());
+        session.stop().await;
+        Ok(archive)
+    });
+
+    let mut status_refreshed: Option<Instant> = None;
+    let signal = loop {
+        if collecting.is_finished() {
+            break Signal::GotoArchived;
+        }
+
+        if status_refreshed.is_none_or(|refreshed| refreshed.elapsed() >= PANE_REFRESH_INTERVAL) {
+            status_refreshed = Some(Instant::now());
+            backend.move_to(0, 0)?;
+            backend.clear_line()?;
+            backend.print(&StyledGraphemes::from(format!(
+                "Collecting: {} lines | {hint}",
+                collected.load(Ordering::Relaxed)
+            )))?;
+            backend.flush()?;
+        }
+
+        if !backend.poll(retrieval_timeout)? {
+            continue;
+        }
+        let event = backend.read()?;
+        if matches_keybind(&event, &keybinds.goto_archived) {
+            break Signal::GotoArchived;
+        }
+        if matches_keybind(&event, &keybinds.exit) {
+            break Signal::Exit {
+                query: query.to_string(),
+                print: false,
+            };
+        }
+        if matches_keybind(&event, &keybinds.exit_and_print) {
+            break Signal::Exit {
+                query: query.to_string(),
+                print: true,
+            };
+        }
+    };
+
+    // Closing the stop channel ends the collecting task, which stops the session.
+    drop(stop_tx);
+    Ok((signal, collecting.await??))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{Config, DEFAULT_CONFIG},
+        pipeline::{StageConfig, StageKind},
+    };
+
+    use super::*;
+
+    #[test]
+    fn slot_keys_are_left_to_the_stages_bound_to_them() {
+        let config = Config::load_from(DEFAULT_CONFIG).unwrap();
+        let alt = |ch| Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::ALT));
+        let pipeline = Pipeline::try_new(&[StageConfig {
+            name: String::from("distinct"),
+            kind: StageKind::Unique,
+            enabled: true,
+            toggle: HashSet::from([alt('1')]),
+        }])
+        .unwrap();
+        let mut state = text_editor::State {
+            texteditor: TextEditor::new(""),
+            history: Default::default(),
+            config: config.streaming.editor.clone(),
+        };
+        let mut slots = QuerySlots::load(None);
+        let mut saving = false;
+        for (slot, query) in [('1', "ERROR"), ('2', "WARN")] {
+            slots.save(slot, query).unwrap();
+        }
+        let mut evaluate = |event: &Event, state: &mut text_editor::State| {
+            let keybinds = &config.streaming.keybinds;
+            evaluate_slot_event(event, state, &mut saving, &mut slots, &pipeline, keybinds)
+        };
+
+        assert!(!evaluate(&alt('1'), &mut state));
+        assert_eq!(state.texteditor.text_without_cursor().to_string(), "");
+        assert!(evaluate(&alt('2'), &mut state));
+        assert_eq!(state.texteditor.text_without_cursor().to_string(), "WARN");
+        // An empty slot is left alone too.
+        assert!(!evaluate(&alt('3'), &mut state));
+    }
+}
@@ -1,43 +1,86 @@
-use std::io::{self, Write};
+use std::{collections::VecDeque, io, sync::Arc};
 
 use promkit_core::{
-    crossterm::{self, cursor, style, terminal},
+    crossterm::style::{Attribute, Attributes, Color, ContentStyle},
     grapheme::StyledGraphemes,
     pane::Pane,
 };
 
+use crate::{
+    backend::Backend,
+    gutter::{self, LineFlags},
+    layout::{fit, EditorPosition, Overflow},
+};
+
 pub struct Terminal {
+    backend: Arc<dyn Backend>,
     size: (u16, u16),
     pane_rows: u16,
-}
-
-/// Reset the scroll region to the entire terminal.
-fn reset_scroll_region_sequence() -> &'static str {
-    crossterm::csi!("r")
-}
-
-/// Set the scroll region to [top, bottom], where both are 1-based.
-fn set_scroll_region_sequence(top_1based: u16, bottom_1based: u16) -> String {
-    format!(crossterm::csi!("{};{}r"), top_1based, bottom_1based)
+    // Last items drawn in the stream area, to lay them out again on redraw
+    history: VecDeque<(StyledGraphemes, LineFlags)>,
+    // Last items of all lines regardless of the query, shown on the left in split view
+    all_history: VecDeque<(StyledGraphemes, LineFlags)>,
+    // Whether the stream area is split into all lines (left) and matched lines (right)
+    split: bool,
+    overflow: Overflow,
+    // Whether the newest line is at the top of the stream area rather than at the bottom
+    reverse: bool,
+    // Whether the flags of the lines are shown in a gutter ahead of them
+    gutter: bool,
+    editor_position: EditorPosition,
+    // Banner shown over the dimmed stream area while no line matched for a while
+    idle: Option<String>,
 }
 
 impl Terminal {
     /// Create a new Terminal instance and apply the initial scroll region.
-    pub fn try_new(size: (u16, u16), panes: &[Pane]) -> anyhow::Result<Self> {
+    pub fn try_new(
+        backend: Arc<dyn Backend>,
+        size: (u16, u16),
+        panes: &[Pane],
+    ) -> anyhow::Result<Self> {
         let term = Self {
+            backend,
             size,
             pane_rows: Self::pane_rows(size, panes),
+            history: VecDeque::new(),
+            all_history: VecDeque::new(),
+            split: false,
+            overflow: Overflow::default(),
+            reverse: false,
+            gutter: false,
+            editor_position: EditorPosition::default(),
+            idle: None,
         };
         term.apply_scroll_region()?;
-        io::stdout().flush()?;
+        term.backend.flush()?;
         Ok(term)
     }
 
-    /// Draw the stream content, which is displayed below the pane.
-    pub fn draw_stream(&self, items: &[StyledGraphemes]) -> anyhow::Result<()> {
+    /// Size of the terminal, as the backend reports it.
+    pub fn size(&self) -> io::Result<(u16, u16)> {
+        self.backend.size()
+    }
+
+    /// Draw an item in the stream area, which is displayed below or above the pane.
+    pub fn draw_stream(&mut self, item: &StyledGraphemes, flags: LineFlags) -> anyhow::Result<()> {
         let stream_height = self.stream_height();
+        let items = self.item_rows(item, flags, self.stream_width());
         if items.is_empty() || stream_height == 0 {
-            io::stdout().flush()?;
+            self.backend.flush()?;
+            return Ok(());
+        }
+        let capacity = self.size.1 as usize;
+        Self::remember(&mut self.history, item, flags, capacity);
+
+        if self.split {
+            let left = self.left_width();
+            self.draw_column(
+                &self.history,
+                left + 1,
+                self.size.0.saturating_sub(left + 1),
+            )?;
+            self.backend.flush()?;
             return Ok(());
         }
 
@@ -45,13 +88,10 @@ impl Terminal {
         // render directly instead of scrolling to keep pane rows stable.
         if stream_height == 1 {
             let row = items.last().expect("checked non-empty items");
-            crossterm::queue!(
-                io::stdout(),
-                cursor::MoveTo(0, self.stream_top()),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-                style::Print(row.styled_display()),
-            )?;
-            io::stdout().flush()?;
+            self.backend.move_to(0, self.stream_top())?;
+            self.backend.clear_line()?;
+            self.backend.print(row)?;
+            self.backend.flush()?;
             return Ok(());
         }
 
@@ -67,58 +107,192 @@ impl Terminal {
         // If users need to re-check past matches, guide them to Archived mode (Ctrl+F).
         let rows = &items[start..];
         let scroll_rows = rows.len() as u16;
-        let write_from = self.size.1.saturating_sub(scroll_rows);
+        let write_from = if self.reverse {
+            self.stream_top()
+        } else {
+            self.stream_bottom().saturating_sub(scroll_rows)
+        };
 
-        crossterm::queue!(
-            io::stdout(),
-            cursor::MoveTo(0, self.stream_top()),
-            terminal::ScrollUp(scroll_rows),
-        )?;
+        self.backend.move_to(0, self.stream_top())?;
+        if self.reverse {
+            self.backend.scroll_down(scroll_rows)?;
+        } else {
+            self.backend.scroll_up(scroll_rows)?;
+        }
         for (idx, row) in rows.iter().enumerate() {
-            crossterm::queue!(
-                io::stdout(),
-                cursor::MoveTo(0, write_from + idx as u16),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-                style::Print(row.styled_display()),
+            self.backend.move_to(0, write_from + idx as u16)?;
+            self.backend.clear_line()?;
+            self.backend.print(row)?;
+        }
+
+        self.backend.flush()?;
+        Ok(())
+    }
+
+    /// Remember a line regardless of the query,
+    /// drawing it on the left in split view.
+    pub fn draw_all(&mut self, item: &StyledGraphemes, flags: LineFlags) -> anyhow::Result<()> {
+        let capacity = self.size.1 as usize;
+        Self::remember(&mut self.all_history, item, flags, capacity);
+        if self.split && self.stream_height() > 0 {
+            self.draw_column(&self.all_history, 0, self.left_width())?;
+            self.draw_banner()?;
+            self.backend.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
+
+    /// Switch between the single stream and split view.
+    /// The screen should be redrawn afterwards.
+    pub fn set_split(&mut self, split: bool) {
+        self.split = split;
+    }
+
+    /// Draw the newest line at the top of the stream area.
+    /// The screen should be redrawn afterwards.
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+    }
+
+    /// Show the flags of the lines in a gutter ahead of them.
+    /// The screen should be redrawn afterwards.
+    pub fn set_gutter(&mut self, gutter: bool) {
+        self.gutter = gutter;
+    }
+
+    /// Place the pane above or below the stream area.
+    /// The scroll region is applied at once, but the screen should be redrawn afterwards.
+    pub fn set_editor_position(&mut self, position: EditorPosition) -> anyhow::Result<()> {
+        self.editor_position = position;
+        self.apply_scroll_region()
+    }
+
+    pub fn idle(&self) -> Option<&str> {
+        self.idle.as_deref()
+    }
+
+    /// Dim the stream area under the banner, or restore it with None.
+    /// The stream area should be redrawn afterwards.
+    pub fn set_idle(&mut self, banner: Option<String>) {
+        self.idle = banner;
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Change how long lines are laid out.
+    /// The screen should be redrawn afterwards.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Redraw the whole screen, e.g. after the terminal was handed over to another program.
+    pub fn redraw(&self, panes: &[Pane]) -> anyhow::Result<()> {
+        self.redraw_stream()?;
+        self.draw_pane(panes)
+    }
+
+    /// Redraw the stream area, leaving the pane as it is.
+    pub fn redraw_stream(&self) -> anyhow::Result<()> {
+        self.apply_scroll_region()?;
+        self.clear_stream_area()?;
+
+        if self.split {
+            let left = self.left_width();
+            self.draw_column(&self.all_history, 0, left)?;
+            self.draw_column(
+                &self.history,
+                left + 1,
+                self.size.0.saturating_sub(left + 1),
             )?;
+            // Separator between the columns
+            let separator = StyledGraphemes::from_str(
+                "│",
+                ContentStyle {
+                    foreground_color: Some(Color::DarkGrey),
+                    ..Default::default()
+                },
+            );
+            for y in self.stream_top()..self.stream_bottom() {
+                self.backend.move_to(left, y)?;
+                self.backend.print(&separator)?;
+            }
+        } else {
+            for (idx, row) in self
+                .stream_rows(&self.history, self.size.0)
+                .iter()
+                .enumerate()
+            {
+                self.backend.move_to(0, self.stream_top() + idx as u16)?;
+                self.backend.print(row)?;
+            }
         }
 
-        io::stdout().flush()?;
+        self.draw_banner()?;
+        self.backend.flush()?;
+        Ok(())
+    }
+
+    /// Draw the idle banner centered in the stream area, if any.
+    fn draw_banner(&self) -> anyhow::Result<()> {
+        let (Some(banner), true) = (&self.idle, self.stream_height() > 0) else {
+            return Ok(());
+        };
+        let banner = StyledGraphemes::from_str(
+            format!(" {banner} "),
+            ContentStyle {
+                foreground_color: Some(Color::White),
+                attributes: Attributes::from(Attribute::Bold),
+                ..Default::default()
+            },
+        );
+        let banner = fit(&banner, banner.widths().min(self.size.0 as usize));
+        let x = self.size.0.saturating_sub(banner.widths() as u16) / 2;
+        self.backend
+            .move_to(x, self.stream_top() + self.stream_height() / 2)?;
+        self.backend.print(&banner)?;
         Ok(())
     }
 
     /// Draw the pane content.
     /// This should be called after syncing the layout to ensure the pane area is correctly sized.
+    /// At the bottom, the panes are stacked in reverse so that the first one is on the last row.
     pub fn draw_pane(&self, panes: &[Pane]) -> anyhow::Result<()> {
-        for y in 0..self.pane_rows {
-            crossterm::queue!(
-                io::stdout(),
-                cursor::MoveTo(0, y),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-            )?;
-        }
-
-        let mut y = 0u16;
-        for pane in panes {
-            if y >= self.pane_rows {
+        let pane_rows = self.pane_rows as usize;
+        let ordered: Vec<&Pane> = match self.editor_position {
+            EditorPosition::Top => panes.iter().collect(),
+            EditorPosition::Bottom => panes.iter().rev().collect(),
+        };
+        let mut rows = Vec::with_capacity(pane_rows);
+        for pane in ordered {
+            if rows.len() >= pane_rows {
                 break;
             }
+            rows.extend(pane.extract(pane_rows - rows.len()));
+        }
+        rows.truncate(pane_rows);
+        // Keep the rows next to the edge of the screen at the bottom.
+        let offset = match self.editor_position {
+            EditorPosition::Top => 0,
+            EditorPosition::Bottom => pane_rows - rows.len(),
+        };
 
-            let viewport_height = (self.pane_rows - y) as usize;
-            for row in pane.extract(viewport_height) {
-                if y >= self.pane_rows {
-                    break;
-                }
-                crossterm::queue!(
-                    io::stdout(),
-                    cursor::MoveTo(0, y),
-                    style::Print(row.styled_display()),
-                )?;
-                y += 1;
-            }
+        for y in 0..self.pane_rows {
+            self.backend.move_to(0, self.pane_top() + y)?;
+            self.backend.clear_line()?;
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            self.backend
+                .move_to(0, self.pane_top() + (offset + idx) as u16)?;
+            self.backend.print(row)?;
         }
 
-        io::stdout().flush()?;
+        self.backend.flush()?;
         Ok(())
     }
 
@@ -146,8 +320,137 @@ impl Terminal {
             .min(size.1 as usize) as u16
     }
 
+    /// Remember the item, keeping as many as can fill the stream area (each takes a row at least).
+    fn remember(
+        history: &mut VecDeque<(StyledGraphemes, LineFlags)>,
+        item: &StyledGraphemes,
+        flags: LineFlags,
+        capacity: usize,
+    ) {
+        history.push_back((item.clone(), flags));
+        while history.len() > capacity {
+            history.pop_front();
+        }
+    }
+
+    /// Width to lay out matched lines into.
+    fn stream_width(&self) -> u16 {
+        if self.split {
+            self.size.0.saturating_sub(self.left_width() + 1)
+        } else {
+            self.size.0
+        }
+    }
+
+    /// Lay out the last items of the history into the rows of the stream area from its top,
+    /// padded with blank rows on the side of the oldest items.
+    fn stream_rows(
+        &self,
+        history: &VecDeque<(StyledGraphemes, LineFlags)>,
+        width: u16,
+    ) -> Vec<StyledGraphemes> {
+        let stream_height = self.stream_height() as usize;
+        let mut rows = VecDeque::new();
+        for (item, flags) in history.iter().rev() {
+            if rows.len() >= stream_height {
+                break;
+            }
+            let item_rows = self.item_rows(item, *flags, width);
+            if self.reverse {
+                // The rows of an item still read from top to bottom.
+                rows.extend(item_rows);
+            } else {
+                for row in item_rows.into_iter().rev() {
+                    rows.push_front(row);
+                }
+            }
+        }
+        if self.reverse {
+            rows.truncate(stream_height);
+            rows.resize(stream_height, StyledGraphemes::default());
+        } else {
+            let skip = rows.len().saturating_sub(stream_height);
+            rows.drain(..skip);
+            while rows.len() < stream_height {
+                rows.push_front(StyledGraphemes::default());
+            }
+        }
+        rows.into()
+    }
+
+    /// Lay out an item into rows of the width, with the gutter ahead of them if it is shown.
+    fn item_rows(
+        &self,
+        item: &StyledGraphemes,
+        flags: LineFlags,
+        width: u16,
+    ) -> Vec<StyledGraphemes> {
+        let height = self.size.1 as usize;
+        let rows = if self.gutter {
+            let content_width = (width as usize).saturating_sub(gutter::WIDTH);
+            self.overflow
+                .rows(item, content_width, height)
+                .iter()
+                .enumerate()
+                .map(|(idx, row)| {
+                    let gutter = if idx == 0 {
+                        flags.render()
+                    } else {
+                        LineFlags::blank()
+                    };
+                    StyledGraphemes::from_iter([&gutter, row])
+                })
+                .collect()
+        } else {
+            self.overflow.rows(item, width as usize, height)
+        };
+        if self.idle.is_none() {
+            return rows;
+        }
+        rows.into_iter()
+            .map(|row| row.apply_attribute(Attribute::Dim))
+            .collect()
+    }
+
+    /// Width of the left column in split view, leaving a column for the separator.
+    fn left_width(&self) -> u16 {
+        self.size.0.saturating_sub(1) / 2
+    }
+
+    /// Draw the last items of the history into a column of the stream area.
+    fn draw_column(
+        &self,
+        history: &VecDeque<(StyledGraphemes, LineFlags)>,
+        x: u16,
+        width: u16,
+    ) -> anyhow::Result<()> {
+        for (idx, row) in self.stream_rows(history, width).iter().enumerate() {
+            self.backend.move_to(x, self.stream_top() + idx as u16)?;
+            self.backend.print(&fit(row, width as usize))?;
+        }
+        Ok(())
+    }
+
+    fn pane_top(&self) -> u16 {
+        match self.editor_position {
+            EditorPosition::Top => 0,
+            EditorPosition::Bottom => self.size.1.saturating_sub(self.pane_rows),
+        }
+    }
+
     fn stream_top(&self) -> u16 {
-        self.pane_rows
+        match self.editor_position {
+            EditorPosition::Top => self.pane_rows,
+            EditorPosition::Bottom => 0,
+        }
+    }
+
+    /// Row just below the stream area.
+    fn stream_bottom(&self) -> u16 {
+        match self.editor_position {
+            EditorPosition::Top => self.size.1,
+            EditorPosition::Bottom => self.size.1.saturating_sub(self.pane_rows),
+        }
     }
 
     fn stream_height(&self) -> u16 {
@@ -155,12 +458,9 @@ impl Terminal {
     }
 
     fn clear_stream_area(&self) -> anyhow::Result<()> {
-        for y in self.stream_top()..self.size.1 {
-            crossterm::queue!(
-                io::stdout(),
-                cursor::MoveTo(0, y),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-            )?;
+        for y in self.stream_top()..self.stream_bottom() {
+            self.backend.move_to(0, y)?;
+            self.backend.clear_line()?;
         }
         Ok(())
     }
@@ -168,25 +468,260 @@ impl Terminal {
     /// Apply the scroll region to the stream area, excluding the pane area.
     fn apply_scroll_region(&self) -> anyhow::Result<()> {
         if self.stream_height() == 0 {
-            crossterm::queue!(io::stdout(), style::Print(reset_scroll_region_sequence()),)?;
+            self.backend.reset_scroll_region()?;
             return Ok(());
         }
 
         let top = self.stream_top() + 1;
-        let bottom = self.size.1;
+        let bottom = self.stream_bottom();
         // Exclude the pane area from the scroll region,
         // so that only the stream area is scrolled when new lines are added.
-        crossterm::queue!(
-            io::stdout(),
-            style::Print(set_scroll_region_sequence(top, bottom)),
-        )?;
+        self.backend.set_scroll_region(top, bottom)?;
         Ok(())
     }
 }
 
 impl Drop for Terminal {
     fn drop(&mut self) {
-        let _ = crossterm::queue!(io::stdout(), style::Print(reset_scroll_region_sequence()));
-        let _ = io::stdout().flush();
+        let _ = self.backend.reset_scroll_region();
+        let _ = self.backend.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use promkit_core::crossterm::event::Event;
+
+    use super::*;
+
+    /// Backend printing into a grid of characters, as a non-tty backend would.
+    #[derive(Clone)]
+    struct Grid {
+        cells: Arc<Mutex<Vec<Vec<char>>>>,
+        cursor: Arc<Mutex<(u16, u16)>>,
+        // 0-based rows of the scroll region, the bottom one exclusive
+        region: Arc<Mutex<(usize, usize)>>,
+    }
+
+    impl Grid {
+        fn new(width: u16, height: u16) -> Self {
+            Self {
+                cells: Arc::new(Mutex::new(vec![vec![' '; width as usize]; height as usize])),
+                cursor: Arc::new(Mutex::new((0, 0))),
+                region: Arc::new(Mutex::new((0, height as usize))),
+            }
+        }
+
+        fn rows(&self) -> Vec<String> {
+            let cells = self.cells.lock().unwrap();
+            cells
+                .iter()
+                .map(|row| row.iter().collect::<String>().trim_end().to_string())
+                .collect()
+        }
+    }
+
+    impl Backend for Grid {
+        fn size(&self) -> io::Result<(u16, u16)> {
+            let cells = self.cells.lock().unwrap();
+            Ok((cells[0].len() as u16, cells.len() as u16))
+        }
+
+        fn poll(&self, _timeout: Duration) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn read(&self) -> io::Result<Event> {
+            Err(io::Error::from(io::ErrorKind::WouldBlock))
+        }
+
+        fn enable_raw_mode(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn disable_raw_mode(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn show_cursor(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn hide_cursor(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn clear(&self) -> io::Result<()> {
+            for row in self.cells.lock().unwrap().iter_mut() {
+                row.fill(' ');
+            }
+            *self.cursor.lock().unwrap() = (0, 0);
+            Ok(())
+        }
+
+        fn move_to(&self, x: u16, y: u16) -> io::Result<()> {
+            *self.cursor.lock().unwrap() = (x, y);
+            Ok(())
+        }
+
+        fn clear_line(&self) -> io::Result<()> {
+            let y = self.cursor.lock().unwrap().1 as usize;
+            self.cells.lock().unwrap()[y].fill(' ');
+            Ok(())
+        }
+
+        fn print(&self, row: &StyledGraphemes) -> io::Result<()> {
+            let mut cursor = self.cursor.lock().unwrap();
+            let mut cells = self.cells.lock().unwrap();
+            for ch in row.chars() {
+                if let Some(cell) = cells[cursor.1 as usize].get_mut(cursor.0 as usize) {
+                    *cell = ch;
+                }
+                cursor.0 += 1;
+            }
+            Ok(())
+        }
+
+        fn scroll_up(&self, rows: u16) -> io::Result<()> {
+            let (top, bottom) = *self.region.lock().unwrap();
+            let mut cells = self.cells.lock().unwrap();
+            for _ in 0..rows {
+                let width = cells[0].len();
+                cells.remove(top);
+                cells.insert(bottom - 1, vec![' '; width]);
+            }
+            Ok(())
+        }
+
+        fn scroll_down(&self, rows: u16) -> io::Result<()> {
+            let (top, bottom) = *self.region.lock().unwrap();
+            let mut cells = self.cells.lock().unwrap();
+            for _ in 0..rows {
+                let width = cells[0].len();
+                cells.remove(bottom - 1);
+                cells.insert(top, vec![' '; width]);
+            }
+            Ok(())
+        }
+
+        fn set_scroll_region(&self, top: u16, bottom: u16) -> io::Result<()> {
+            *self.region.lock().unwrap() = (top as usize - 1, bottom as usize);
+            Ok(())
+        }
+
+        fn reset_scroll_region(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn split_view_draws_all_and_matched_lines_side_by_side() {
+        let grid = Grid::new(11, 4);
+        let panes = [Pane::new(vec![StyledGraphemes::from("query")], 0)];
+        let mut term = Terminal::try_new(Arc::new(grid.clone()), (11, 4), &panes).unwrap();
+        term.draw_pane(&panes).unwrap();
+
+        term.draw_all(&StyledGraphemes::from("a 1"), LineFlags::default())
+            .unwrap();
+        term.draw_all(&StyledGraphemes::from("b 2"), LineFlags::default())
+            .unwrap();
+        term.draw_stream(&StyledGraphemes::from("b 2"), LineFlags::default())
+            .unwrap();
+        assert_eq!(grid.rows(), vec!["query", "", "", "b 2"]);
+
+        term.set_split(true);
+        term.redraw(&panes).unwrap();
+        assert_eq!(grid.rows(), vec!["query", "     │", "a 1  │", "b 2  │b 2"]);
+    }
+
+    #[test]
+    fn reverse_draws_newest_line_at_top_of_stream() {
+        let grid = Grid::new(11, 4);
+        let panes = [Pane::new(vec![StyledGraphemes::from("query")], 0)];
+        let mut term = Terminal::try_new(Arc::new(grid.clone()), (11, 4), &panes).unwrap();
+        term.set_reverse(true);
+        term.draw_pane(&panes).unwrap();
+
+        term.draw_stream(&StyledGraphemes::from("a 1"), LineFlags::default())
+            .unwrap();
+        term.draw_stream(&StyledGraphemes::from("b 2"), LineFlags::default())
+            .unwrap();
+        assert_eq!(grid.rows(), vec!["query", "b 2", "a 1", ""]);
+
+        term.redraw(&panes).unwrap();
+        assert_eq!(grid.rows(), vec!["query", "b 2", "a 1", ""]);
+    }
+
+    #[test]
+    fn editor_at_bottom_stacks_panes_below_stream() {
+        let grid = Grid::new(11, 5);
+        let panes = [
+            Pane::new(vec![StyledGraphemes::from("query")], 0),
+            Pane::new(vec![StyledGraphemes::from("status")], 0),
+        ];
+        let mut term = Terminal::try_new(Arc::new(grid.clone()), (11, 5), &panes).unwrap();
+        term.set_editor_position(EditorPosition::Bottom).unwrap();
+        term.draw_pane(&panes).unwrap();
+
+        term.draw_stream(&StyledGraphemes::from("a 1"), LineFlags::default())
+            .unwrap();
+        term.draw_stream(&StyledGraphemes::from("b 2"), LineFlags::default())
+            .unwrap();
+        assert_eq!(grid.rows(), vec!["", "a 1", "b 2", "status", "query"]);
+
+        term.redraw(&panes).unwrap();
+        assert_eq!(grid.rows(), vec!["", "a 1", "b 2", "status", "query"]);
+    }
+
+    #[test]
+    fn gutter_flags_the_first_row_of_each_line() {
+        let grid = Grid::new(8, 4);
+        let panes = [Pane::new(vec![StyledGraphemes::from("query")], 0)];
+        let mut term = Terminal::try_new(Arc::new(grid.clone()), (8, 4), &panes).unwrap();
+        term.set_gutter(true);
+        term.draw_pane(&panes).unwrap();
+
+        let matched = LineFlags {
+            matched: true,
+            ..Default::default()
+        };
+        let alert = LineFlags {
+            alert: true,
+            bookmark: true,
+            ..Default::default()
+        };
+        term.draw_stream(&StyledGraphemes::from("a 1"), matched)
+            .unwrap();
+        term.draw_stream(&StyledGraphemes::from("b 23456"), alert)
+            .unwrap();
+        assert_eq!(grid.rows(), vec!["query", "  • a 1", " *! b 23", "    456"]);
+    }
+
+    #[test]
+    fn idle_banner_is_centered_over_the_stream() {
+        let grid = Grid::new(20, 4);
+        let panes = [Pane::new(vec![StyledGraphemes::from("query")], 0)];
+        let mut term = Terminal::try_new(Arc::new(grid.clone()), (20, 4), &panes).unwrap();
+        term.draw_pane(&panes).unwrap();
+        term.draw_stream(&StyledGraphemes::from("a 1"), LineFlags::default())
+            .unwrap();
+
+        term.set_idle(Some(String::from("idle 5m")));
+        term.redraw_stream().unwrap();
+        assert_eq!(grid.rows(), vec!["query", "", "      idle 5m", "a 1"]);
+
+        term.set_idle(None);
+        term.redraw_stream().unwrap();
+        assert_eq!(grid.rows(), vec!["query", "", "", "a 1"]);
     }
 }
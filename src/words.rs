@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Tokens shorter than this are too common to tell anything.
+const MIN_TOKEN_LEN: usize = 3;
+
+/// Split a line into its lowercase words, skipping short ones and those starting with a digit
+/// (e.g. counts and durations), which rarely repeat.
+pub fn tokens(line: &str) -> HashSet<String> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| {
+            token.chars().count() >= MIN_TOKEN_LEN
+                && !token.starts_with(|c: char| c.is_ascii_digit())
+        })
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Number of lines each word occurs in, over a sliding window of the last lines added,
+/// e.g. to find what else co-occurs with the errors matched by the query.
+pub struct WordCounts {
+    window: usize,
+    lines: VecDeque<HashSet<String>>,
+    counts: HashMap<String, u64>,
+}
+
+impl WordCounts {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            lines: VecDeque::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Count the words of the line, forgetting those of the oldest line out of the window.
+    pub fn add(&mut self, line: &str) {
+        let tokens = tokens(line);
+        for token in &tokens {
+            *self.counts.entry(token.clone()).or_default() += 1;
+        }
+        self.lines.push_back(tokens);
+
+        if self.lines.len() > self.window {
+            for token in self.lines.pop_front().unwrap_or_default() {
+                if let Some(count) = self.counts.get_mut(&token) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.counts.remove(&token);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of lines in the window.
+    pub fn lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Number of distinct words in the window.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// The `n` words occurring in the most lines among those accepted by `filter`.
+    pub fn top(&self, n: usize, filter: impl Fn(&str) -> bool) -> Vec<(&str, u64)> {
+        let mut words = self
+            .counts
+            .iter()
+            .filter(|(word, _)| filter(word))
+            .map(|(word, count)| (word.as_str(), *count))
+            .collect::<Vec<(&str, u64)>>();
+        words.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        words.truncate(n);
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_counts_lines_per_word_over_the_window() {
+        let mut words = WordCounts::new(3);
+        for line in [
+            "ERROR upstream timeout on db-1",
+            "ERROR upstream timeout timeout on db-2",
+            "ERROR disk full",
+            "ERROR upstream reset 500ms",
+        ] {
+            words.add(line);
+        }
+
+        // The first line is out of the window, and "timeout" counts once per line.
+        assert_eq!(words.lines(), 3);
+        assert_eq!(
+            words.top(3, |word| word != "error"),
+            vec![("upstream", 2), ("disk", 1), ("full", 1)]
+        );
+        assert!(!tokens("took 500ms").contains("500ms"));
+    }
+
+    #[test]
+    fn tokens_match_the_golden_file() {
+        crate::golden::check("words", |line| {
+            let mut tokens = tokens(line).into_iter().collect::<Vec<_>>();
+            tokens.sort();
+            format!("  words: {}\n", tokens.join(" "))
+        });
+    }
+}
@@ -0,0 +1,193 @@
+//! Just enough XML for the records of legacy systems logging XML elements,
+//! see `--xml-record`: no namespaces, DTDs or validation.
+
+/// Number of elements named `tag` opened and closed in the text,
+/// not counting the self-closing ones.
+pub fn balance(text: &str, tag: &str) -> (usize, usize) {
+    let (mut opened, mut closed) = (0, 0);
+    let mut rest = text;
+    while let Some(at) = rest.find('<') {
+        rest = &rest[at + 1..];
+        let (closing, name) = match rest.strip_prefix('/') {
+            Some(name) => (true, name),
+            None => (false, rest),
+        };
+        let Some(after) = name.strip_prefix(tag) else {
+            continue;
+        };
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            continue;
+        }
+        if closing {
+            closed += 1;
+        } else if !after[..after.find('>').unwrap_or(after.len())].ends_with('/') {
+            opened += 1;
+        }
+    }
+    (opened, closed)
+}
+
+/// Join the lines of an element into a single line, dropping the indentation.
+pub fn join(lines: &[String]) -> String {
+    let mut joined = String::new();
+    for line in lines.iter().map(|line| line.trim()) {
+        if line.is_empty() {
+            continue;
+        }
+        if !(joined.is_empty() || joined.ends_with('>') || line.starts_with('<')) {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+    joined
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse the attributes of a start tag, e.g. `id="7" level='error'`.
+fn attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            break;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        if !name.is_empty() {
+            attributes.push((name.to_string(), unescape(&value[1..end + 1])));
+        }
+        rest = &value[end + 2..];
+    }
+    attributes
+}
+
+/// Extract the fields of an element as XPath-lite paths relative to it:
+/// `@id` for an attribute of the element, `user/name` for the text of a child element,
+/// `user/@id` for an attribute of a child one. The first of repeated paths is kept.
+pub fn parse(text: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut push = |path: String, value: String| {
+        if !fields.iter().any(|(existing, _)| *existing == path) {
+            fields.push((path, value));
+        }
+    };
+    // Names of the open elements below the root, and the text of the innermost one
+    let mut stack: Vec<String> = Vec::new();
+    let mut depth = 0;
+    let mut content = String::new();
+
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some(at) = rest.find('<') else {
+            break;
+        };
+        content.push_str(&rest[..at]);
+        rest = &rest[at..];
+
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            content.push_str(&after[..end]);
+            rest = after.get(end + 3..).unwrap_or_default();
+            continue;
+        }
+        let end = match rest.strip_prefix("<!--") {
+            Some(comment) => comment.find("-->").map(|end| end + 7),
+            None => rest.find('>').map(|end| end + 1),
+        };
+        let Some(end) = end else {
+            break;
+        };
+        let tag = &rest[1..end - 1];
+        rest = &rest[end..];
+        if tag.starts_with(['?', '!']) {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            if depth > 1 && stack.last().is_some_and(|last| last == name.trim()) {
+                let value = unescape(content.trim());
+                if !value.is_empty() {
+                    push(stack.join("/"), value);
+                }
+                stack.pop();
+            }
+            depth -= 1;
+            content.clear();
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        if depth > 0 {
+            stack.push(name.to_string());
+        }
+        let prefix = if stack.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", stack.join("/"))
+        };
+        for (attr, value) in attributes(attrs) {
+            push(format!("{prefix}@{attr}"), value);
+        }
+        content.clear();
+        if self_closing {
+            if depth > 0 {
+                stack.pop();
+            }
+        } else {
+            depth += 1;
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_extracts_paths_from_a_record() {
+        let lines = [
+            r#"<event id="7" level="error">"#,
+            "  <user role='admin'>alice &amp; bob</user>",
+            "  <request><path>/api/a</path><retry/></request>",
+            "  <msg><![CDATA[a < b]]></msg>",
+            "  <!-- not a field -->",
+            "</event>",
+        ]
+        .map(String::from);
+        assert_eq!(balance(&lines[0], "event"), (1, 0));
+        assert_eq!(balance(&lines[5], "event"), (0, 1));
+        assert_eq!(balance(r#"<event id="8"/>"#, "event"), (0, 0));
+        assert_eq!(balance("<events>", "event"), (0, 0));
+
+        let record = join(&lines);
+        assert!(record.starts_with(r#"<event id="7" level="error"><user role='admin'>"#));
+        let fields = parse(&record);
+        assert_eq!(
+            fields
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("@id", "7"),
+                ("@level", "error"),
+                ("user/@role", "admin"),
+                ("user", "alice & bob"),
+                ("request/path", "/api/a"),
+                ("msg", "a < b"),
+            ]
+        );
+    }
+}